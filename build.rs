@@ -5,7 +5,7 @@ fn main() {
     if env::var("CARGO_CFG_TARGET_OS").unwrap() == "windows" {
         // Only run this part if targeting Windows
         let output = Command::new("x86_64-w64-mingw32-windres")
-            .args(&["app.rc", "-O", "coff", "-o", "app.res"])
+            .args(["app.rc", "-O", "coff", "-o", "app.res"])
             .output()
             .expect("Failed to run windres");
 
@@ -0,0 +1,245 @@
+use num_complex::Complex64;
+use num_traits::{ CheckedAdd, CheckedMul };
+use rust_decimal::{ Decimal, MathematicalOps };
+
+use crate::fraction::{ fraction_from_f64, Fraction };
+use crate::units::Quantity;
+
+/// 求和区域的聚合函数
+///
+/// 每个聚合值都可以在求和区域之外的表达式中按名称引用, 例如 `sum`、`avg`、`max`;
+/// 也可以作为函数对任意行求值, 例如 `sum(A:E)`、`avg(B, D, F)`。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Aggregate {
+    Sum,
+    Average,
+    Min,
+    Max,
+    Median,
+    Count,
+    Product,
+    Stdev,
+}
+
+impl Aggregate {
+    pub const ALL: [Aggregate; 8] = [
+        Aggregate::Sum,
+        Aggregate::Average,
+        Aggregate::Min,
+        Aggregate::Max,
+        Aggregate::Median,
+        Aggregate::Count,
+        Aggregate::Product,
+        Aggregate::Stdev,
+    ];
+
+    /// 表达式中引用的名称
+    pub fn name(self) -> &'static str {
+        match self {
+            Aggregate::Sum => "sum",
+            Aggregate::Average => "avg",
+            Aggregate::Min => "min",
+            Aggregate::Max => "max",
+            Aggregate::Median => "median",
+            Aggregate::Count => "count",
+            Aggregate::Product => "product",
+            Aggregate::Stdev => "stdev",
+        }
+    }
+
+    /// 底部合计栏中显示的名称
+    pub fn label(self) -> &'static str {
+        match self {
+            Aggregate::Sum => "Sum",
+            Aggregate::Average => "Average",
+            Aggregate::Min => "Min",
+            Aggregate::Max => "Max",
+            Aggregate::Median => "Median",
+            Aggregate::Count => "Count",
+            Aggregate::Product => "Product",
+            Aggregate::Stdev => "Stdev",
+        }
+    }
+
+    /// 按名称查找聚合函数, 也接受 `average`、`mean` 和 `std` 等别名
+    pub fn from_name(name: &str) -> Option<Aggregate> {
+        match name.trim().to_lowercase().as_str() {
+            "sum" | "z" => Some(Aggregate::Sum),
+            "avg" | "average" | "mean" => Some(Aggregate::Average),
+            "min" => Some(Aggregate::Min),
+            "max" => Some(Aggregate::Max),
+            "median" => Some(Aggregate::Median),
+            "count" => Some(Aggregate::Count),
+            "product" => Some(Aggregate::Product),
+            "stdev" | "std" => Some(Aggregate::Stdev),
+            _ => None,
+        }
+    }
+
+    /// 计算聚合值, 没有数值时结果为 0
+    pub fn apply(self, values: &[f64]) -> f64 {
+        if values.is_empty() {
+            return 0.0;
+        }
+        let count = values.len() as f64;
+        match self {
+            Aggregate::Sum => values.iter().sum(),
+            Aggregate::Average => values.iter().sum::<f64>() / count,
+            Aggregate::Min => values.iter().copied().fold(f64::INFINITY, f64::min),
+            Aggregate::Max => values.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+            Aggregate::Median => {
+                let mut sorted = values.to_vec();
+                sorted.sort_by(|a, b| a.total_cmp(b));
+                let mid = sorted.len() / 2;
+                if sorted.len().is_multiple_of(2) { (sorted[mid - 1] + sorted[mid]) / 2.0 } else { sorted[mid] }
+            }
+            Aggregate::Count => count,
+            Aggregate::Product => values.iter().product(),
+            // 样本标准差, 只有一个数值时为 0
+            Aggregate::Stdev => {
+                if values.len() < 2 {
+                    return 0.0;
+                }
+                let mean = values.iter().sum::<f64>() / count;
+                let variance =
+                    values
+                        .iter()
+                        .map(|value| (value - mean).powi(2))
+                        .sum::<f64>() / (count - 1.0);
+                variance.sqrt()
+            }
+        }
+    }
+
+    /// 以十进制精确计算聚合值, 没有数值时结果为 0, 溢出时返回 None
+    pub fn apply_decimal(self, values: &[Decimal]) -> Option<Decimal> {
+        if values.is_empty() {
+            return Some(Decimal::ZERO);
+        }
+        let count = Decimal::from(values.len());
+        let sum = || values.iter().try_fold(Decimal::ZERO, |sum, value| sum.checked_add(*value));
+        match self {
+            Aggregate::Sum => sum(),
+            Aggregate::Average => sum()?.checked_div(count),
+            Aggregate::Min => values.iter().copied().min(),
+            Aggregate::Max => values.iter().copied().max(),
+            Aggregate::Median => {
+                let mut sorted = values.to_vec();
+                sorted.sort();
+                let mid = sorted.len() / 2;
+                if sorted.len().is_multiple_of(2) {
+                    sorted[mid - 1].checked_add(sorted[mid]).map(|total| total / Decimal::TWO)
+                } else {
+                    Some(sorted[mid])
+                }
+            }
+            Aggregate::Count => Some(count),
+            Aggregate::Product => values.iter().try_fold(Decimal::ONE, |product, value| product.checked_mul(*value)),
+            Aggregate::Stdev => {
+                if values.len() < 2 {
+                    return Some(Decimal::ZERO);
+                }
+                let mean = sum()?.checked_div(count)?;
+                let squares = values
+                    .iter()
+                    .try_fold(Decimal::ZERO, |total, value| total.checked_add((value - mean).checked_powi(2)?))?;
+                squares.checked_div(count - Decimal::ONE)?.sqrt()
+            }
+        }
+    }
+
+    /// 以分数精确计算聚合值, 标准差一般不是有理数, 借助浮点数计算后还原
+    pub fn apply_fraction(self, values: &[Fraction]) -> Option<Fraction> {
+        if values.is_empty() {
+            return Some(Fraction::from_integer(0));
+        }
+        let count = Fraction::from_integer(values.len() as i128);
+        let sum = || values.iter().try_fold(Fraction::from_integer(0), |sum, value| sum.checked_add(value));
+        match self {
+            Aggregate::Sum => sum(),
+            Aggregate::Average => Some(sum()? / count),
+            Aggregate::Min => values.iter().copied().min(),
+            Aggregate::Max => values.iter().copied().max(),
+            Aggregate::Median => {
+                let mut sorted = values.to_vec();
+                sorted.sort();
+                let mid = sorted.len() / 2;
+                if sorted.len().is_multiple_of(2) {
+                    sorted[mid - 1].checked_add(&sorted[mid]).map(|total| total / 2)
+                } else {
+                    Some(sorted[mid])
+                }
+            }
+            Aggregate::Count => Some(count),
+            Aggregate::Product =>
+                values.iter().try_fold(Fraction::from_integer(1), |product, value| product.checked_mul(value)),
+            Aggregate::Stdev => {
+                let values: Vec<f64> = values
+                    .iter()
+                    .map(|value| (*value.numer() as f64) / (*value.denom() as f64))
+                    .collect();
+                fraction_from_f64(self.apply(&values))
+            }
+        }
+    }
+
+    /// 带单位数值的聚合值, 量纲不同时返回 None, 结果使用第一个数值的单位
+    pub fn apply_quantity(self, values: &[Quantity]) -> Option<Quantity> {
+        let first = match values.first() {
+            Some(first) => first,
+            None => {
+                return Some(Quantity::number(0.0));
+            }
+        };
+        if values.iter().any(|value| value.dims != first.dims) {
+            return None;
+        }
+        let value = self.apply(
+            &values
+                .iter()
+                .map(|value| value.value)
+                .collect::<Vec<_>>()
+        );
+        Some(match self {
+            Aggregate::Count => Quantity::number(value),
+            Aggregate::Product =>
+                Quantity {
+                    value,
+                    dims: first.dims.map(|d| d * (values.len() as i8)),
+                    unit: None,
+                },
+            _ => Quantity { value, ..first.clone() },
+        })
+    }
+
+    /// 复数的总和、平均值、乘积和个数, 其他聚合值只对实数有意义
+    pub fn apply_complex(self, values: &[Complex64]) -> Option<Complex64> {
+        if values.is_empty() {
+            return Some(Complex64::new(0.0, 0.0));
+        }
+        let count = values.len() as f64;
+        match self {
+            Aggregate::Sum => Some(values.iter().sum()),
+            Aggregate::Average => Some(values.iter().sum::<Complex64>() / count),
+            Aggregate::Product => Some(values.iter().product()),
+            Aggregate::Count => Some(Complex64::new(count, 0.0)),
+            _ => {
+                let values = values
+                    .iter()
+                    .map(|value| (value.im == 0.0).then_some(value.re))
+                    .collect::<Option<Vec<f64>>>()?;
+                Some(Complex64::new(self.apply(&values), 0.0))
+            }
+        }
+    }
+}
+
+/// 解析 `sum, avg, max` 形式的聚合函数列表, 含有未知名称或列表为空时返回 None
+pub fn parse_aggregates(spec: &str) -> Option<Vec<Aggregate>> {
+    let aggregates = spec
+        .split([',', ' '])
+        .filter(|name| !name.trim().is_empty())
+        .map(Aggregate::from_name)
+        .collect::<Option<Vec<_>>>()?;
+    if aggregates.is_empty() { None } else { Some(aggregates) }
+}
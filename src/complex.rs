@@ -0,0 +1,215 @@
+use num_complex::Complex64;
+
+use crate::aggregate::Aggregate;
+use crate::evaluator::{ call_function, call_list_function, Number };
+use crate::format::{ format_with_thousands_separator, parse_number };
+use crate::parser::BinaryOp;
+
+/// 复数结果的显示方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ComplexForm {
+    /// 直角坐标 `3+4i`
+    #[default]
+    Rectangular,
+    /// 极坐标 `5∠53.1301`, 角度以度为单位
+    Polar,
+}
+
+impl ComplexForm {
+    /// 按 .func.toml 中 `complex` 的取值查找
+    pub fn from_name(name: &str) -> Option<ComplexForm> {
+        match name.trim().to_lowercase().as_str() {
+            "rectangular" | "cartesian" => Some(ComplexForm::Rectangular),
+            "polar" => Some(ComplexForm::Polar),
+            _ => None,
+        }
+    }
+}
+
+/// 解析 `3+4i`、`-2j`、`5∠53.13` 或实数形式的复数
+pub fn parse_complex(text: &str) -> Option<Complex64> {
+    let text = text.trim();
+    if let Some((magnitude, angle)) = text.split_once('∠') {
+        return Some(polar(parse_number(magnitude)?, parse_number(angle)?));
+    }
+    let body = match text.strip_suffix(['i', 'j']) {
+        Some(body) => body,
+        None => {
+            return parse_number(text).map(|re| Complex64::new(re, 0.0));
+        }
+    };
+    // 实部与虚部之间的符号, 跳过开头的符号和指数中的符号
+    let split = body
+        .char_indices()
+        .skip(1)
+        .filter(|(i, c)| matches!(c, '+' | '-') && !body[..*i].ends_with(['e', 'E']))
+        .map(|(i, _)| i)
+        .last();
+    match split {
+        Some(i) => Some(Complex64::new(parse_number(&body[..i])?, body[i..].parse::<f64>().ok()?)),
+        None => Some(Complex64::new(0.0, parse_number(body)?)),
+    }
+}
+
+/// 由模和角度 (度) 得到复数
+pub fn polar(magnitude: f64, degrees: f64) -> Complex64 {
+    Complex64::from_polar(magnitude, degrees.to_radians())
+}
+
+/// 行变量中保存的完整精度写法, 例如 `11-2i`
+pub fn complex_text(value: &Complex64) -> String {
+    let sign = if value.im.is_sign_negative() { '-' } else { '+' };
+    format!("{}{}{}i", value.re, sign, value.im.abs())
+}
+
+/// 按显示方式格式化复数, 超出 `max_len` 个字符时减少小数位数
+pub fn format_complex(
+    value: &Complex64,
+    form: ComplexForm,
+    decimal_places: usize,
+    max_len: usize
+) -> String {
+    let format = |places: usize| {
+        match form {
+            ComplexForm::Rectangular => {
+                let im = format!("{}i", format_with_thousands_separator(value.im.abs(), places));
+                if value.re == 0.0 {
+                    let sign = if value.im < 0.0 { "-" } else { "" };
+                    return format!("{}{}", sign, im);
+                }
+                let sign = if value.im < 0.0 { '-' } else { '+' };
+                format!("{}{}{}", format_with_thousands_separator(value.re, places), sign, im)
+            }
+            ComplexForm::Polar =>
+                format!(
+                    "{}∠{}",
+                    format_with_thousands_separator(value.norm(), places),
+                    format_with_thousands_separator(value.arg().to_degrees(), places)
+                ),
+        }
+    };
+    (0..=decimal_places)
+        .rev()
+        .map(format)
+        .find(|text| text.chars().count() <= max_len)
+        .unwrap_or_else(|| format(0))
+}
+
+/// 参数都是实数时返回实部
+fn real_args(name: &str, args: &[Complex64]) -> Result<Vec<f64>, String> {
+    args.iter()
+        .map(|arg| if arg.im == 0.0 { Ok(arg.re) } else { Err(format!("`{}` needs real arguments", name)) })
+        .collect()
+}
+
+/// 复数运算, 角度 (`∠`、`arg`) 以度为单位, 三角函数仍使用弧度
+impl Number for Complex64 {
+    fn literal(value: f64, _digits: &str) -> Result<Self, String> {
+        Ok(Complex64::new(value, 0.0))
+    }
+
+    fn imaginary(value: f64) -> Result<Self, String> {
+        Ok(Complex64::new(0.0, value))
+    }
+
+    fn parse(text: &str) -> Option<Self> {
+        parse_complex(text)
+    }
+
+    fn from_f64(value: f64) -> Option<Self> {
+        Some(Complex64::new(value, 0.0))
+    }
+
+    /// 只取实部
+    fn to_f64(self) -> f64 {
+        self.re
+    }
+
+    fn binary(op: BinaryOp, lhs: Self, rhs: Self) -> Result<Self, String> {
+        if matches!(op, BinaryOp::Div | BinaryOp::Rem) && rhs == Complex64::new(0.0, 0.0) {
+            return Err("Division by zero".to_string());
+        }
+        Ok(match op {
+            BinaryOp::Add => lhs + rhs,
+            BinaryOp::Sub => lhs - rhs,
+            BinaryOp::Mul => lhs * rhs,
+            BinaryOp::Div => lhs / rhs,
+            BinaryOp::Rem => {
+                let args = real_args("%", &[lhs, rhs])?;
+                Complex64::new(args[0] % args[1], 0.0)
+            }
+            // 整数次幂逐次相乘, 使 `(1i)^2` 恰好为 -1
+            BinaryOp::Pow if rhs.im == 0.0 && rhs.re.fract() == 0.0 && rhs.re.abs() <= 1024.0 =>
+                lhs.powi(rhs.re as i32),
+            BinaryOp::Pow if lhs == Complex64::new(0.0, 0.0) => lhs,
+            BinaryOp::Pow => lhs.powc(rhs),
+        })
+    }
+
+    /// 由 0 减去, 避免 `-4` 的虚部为 -0 使 `sqrt(-4)` 落在分支切割的另一侧
+    fn neg(self) -> Self {
+        Complex64::new(0.0, 0.0) - self
+    }
+
+    fn percent(self) -> Self {
+        self / 100.0
+    }
+
+    fn call(name: &str, args: &[Self]) -> Result<Self, String> {
+        let x = args[0];
+        Ok(match name {
+            "sin" => x.sin(),
+            "cos" => x.cos(),
+            "tan" => x.tan(),
+            "asin" => x.asin(),
+            "acos" => x.acos(),
+            "atan" => x.atan(),
+            "sinh" => x.sinh(),
+            "cosh" => x.cosh(),
+            "tanh" => x.tanh(),
+            "asinh" => x.asinh(),
+            "acosh" => x.acosh(),
+            "atanh" => x.atanh(),
+            "ln" => x.ln(),
+            "log" => {
+                match args.get(1) {
+                    Some(base) => x.ln() / base.ln(),
+                    None => x.log10(),
+                }
+            }
+            "log2" => x.log2(),
+            "log10" => x.log10(),
+            "exp" => x.exp(),
+            "exp2" => x.exp2(),
+            "sqrt" => x.sqrt(),
+            "cbrt" => x.cbrt(),
+            "abs" => Complex64::new(x.norm(), 0.0),
+            "arg" => Complex64::new(x.arg().to_degrees(), 0.0),
+            "re" => Complex64::new(x.re, 0.0),
+            "im" => Complex64::new(x.im, 0.0),
+            "conj" => x.conj(),
+            "floor" => Complex64::new(x.re.floor(), x.im.floor()),
+            "ceil" => Complex64::new(x.re.ceil(), x.im.ceil()),
+            "round" => Complex64::new(x.re.round(), x.im.round()),
+            "pow" => Self::binary(BinaryOp::Pow, x, args[1])?,
+            "polar" => {
+                let args = real_args(name, args)?;
+                polar(args[0], args[1])
+            }
+            // min、max、hypot 和 atan2 只对实数有意义
+            _ => Complex64::new(call_function(name, &real_args(name, args)?), 0.0),
+        })
+    }
+
+    fn call_lists(name: &str, args: &[Vec<Self>]) -> Result<Self, String> {
+        let args = args
+            .iter()
+            .map(|values| real_args(name, values))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Complex64::new(call_list_function(name, &args)?, 0.0))
+    }
+
+    fn aggregate(aggregate: Aggregate, values: &[Self]) -> Option<Self> {
+        aggregate.apply_complex(values)
+    }
+}
@@ -0,0 +1,283 @@
+use std::time::{ SystemTime, UNIX_EPOCH };
+
+use crate::format::format_with_thousands_separator;
+
+// 日期以天数表示, 与电子表格相同从 1899-12-30 起算, 小数部分为一天中的时间
+
+/// 1970-01-01 的天数, 用于与 Unix 时间换算
+const UNIX_EPOCH_DAYS: i64 = 25569;
+
+/// `workdays` 和 `addworkdays` 逐日计算的最大天数, 约 2700 年
+const MAX_DAYS: i64 = 1_000_000;
+
+/// 日期函数及其参数个数范围
+pub const FUNCTIONS: [(&str, usize, usize); 10] = [
+    ("today", 0, 0),
+    // 当前日期和时间
+    ("now", 0, 0),
+    // date(2026-12-31) 或 date(2026, 12, 31)
+    ("date", 1, 3),
+    // addmonths(date, n): n 个月后的同一天, 超过月末时取月末
+    ("addmonths", 2, 2),
+    // workdays(start, end): 两个日期之间 (含两端) 的工作日数, 不含周末和节假日
+    ("workdays", 2, 2),
+    // addworkdays(date, n): n 个工作日之后的日期
+    ("addworkdays", 2, 2),
+    ("year", 1, 1),
+    ("month", 1, 1),
+    ("day", 1, 1),
+    // 星期一为 1, 星期日为 7
+    ("weekday", 1, 1),
+];
+
+/// 是否为日期函数
+pub fn is_date_function(name: &str) -> bool {
+    FUNCTIONS.iter().any(|(function, _, _)| *function == name)
+}
+
+/// 计算日期时使用的当前时间和节假日
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Calendar {
+    /// 相对 UTC 的小时数, `today()` 按此时区取日期
+    pub utc_offset: f64,
+    /// 节假日的天数, 已排序
+    pub holidays: Vec<i64>,
+}
+
+impl Calendar {
+    /// 当前日期和时间的天数
+    pub fn now(&self) -> f64 {
+        let seconds = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0.0, |duration| duration.as_secs_f64());
+        seconds / 86400.0 + self.utc_offset / 24.0 + (UNIX_EPOCH_DAYS as f64)
+    }
+
+    pub fn today(&self) -> f64 {
+        self.now().floor()
+    }
+
+    fn is_workday(&self, day: i64) -> bool {
+        weekday(day) <= 5 && self.holidays.binary_search(&day).is_err()
+    }
+}
+
+/// 公历日期的天数, 月份超出 1 ~ 12 时进位到相邻的年份
+pub fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = year + (month - 1).div_euclid(12);
+    let month = (month - 1).rem_euclid(12) + 1;
+    // 以三月为一年的开始, 闰日在年末
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let year_of_era = y - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468 + UNIX_EPOCH_DAYS + (day - 1)
+}
+
+/// 天数对应的年、月、日
+pub fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days - UNIX_EPOCH_DAYS + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + ((month <= 2) as i64);
+    (year, month, day)
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    days_from_civil(year, month + 1, 1) - days_from_civil(year, month, 1)
+}
+
+/// 星期一为 1, 星期日为 7
+pub fn weekday(days: i64) -> i64 {
+    // 1899-12-30 为星期六
+    (days + 5).rem_euclid(7) + 1
+}
+
+/// 检查年月日是否有效并换算为天数
+pub fn checked_date(year: i64, month: i64, day: i64) -> Option<i64> {
+    if (1..=12).contains(&month) && day >= 1 && day <= days_in_month(year, month) {
+        Some(days_from_civil(year, month, day))
+    } else {
+        None
+    }
+}
+
+/// 解析 `2026-10-17` 或 `2026-10-17T14:30`, 不是日期时返回 None
+pub fn parse_date(text: &str) -> Option<f64> {
+    let text = text.trim();
+    let (date, time) = match text.split_once('T') {
+        Some((date, time)) => (date, Some(time)),
+        None => (text, None),
+    };
+    let mut parts = date.split('-');
+    let year = parts.next().filter(|part| part.len() == 4)?;
+    let month = parts.next().filter(|part| (1..=2).contains(&part.len()))?;
+    let day = parts.next().filter(|part| (1..=2).contains(&part.len()))?;
+    if parts.next().is_some() || ![year, month, day].iter().all(|part| part.bytes().all(|b| b.is_ascii_digit())) {
+        return None;
+    }
+    let days = checked_date(year.parse().ok()?, month.parse().ok()?, day.parse().ok()?)? as f64;
+    match time {
+        Some(time) => Some(days + parse_time(time)?),
+        None => Some(days),
+    }
+}
+
+/// 解析 `14:30` 或 `14:30:05` 为一天的几分之几
+pub fn parse_time(text: &str) -> Option<f64> {
+    let parts: Vec<&str> = text.trim().split(':').collect();
+    if !(2..=3).contains(&parts.len()) || !parts.iter().all(|part| !part.is_empty() && part.bytes().all(|b| b.is_ascii_digit())) {
+        return None;
+    }
+    let numbers: Vec<f64> = parts
+        .iter()
+        .map(|part| part.parse::<f64>().ok())
+        .collect::<Option<_>>()?;
+    if parts[0].len() > 2 || parts[1..].iter().any(|part| part.len() != 2) || numbers[1..].iter().any(|n| *n >= 60.0) {
+        return None;
+    }
+    let seconds = numbers[0] * 3600.0 + numbers[1] * 60.0 + numbers.get(2).copied().unwrap_or(0.0);
+    Some(seconds / 86400.0)
+}
+
+/// 显示日期, 有时间时附带 `T14:30`, 秒不为零时附带秒
+pub fn format_date(days: f64) -> String {
+    let seconds = (days * 86400.0).round() as i64;
+    let (date, seconds) = (seconds.div_euclid(86400), seconds.rem_euclid(86400));
+    let (year, month, day) = civil_from_days(date);
+    let mut text = format!("{:04}-{:02}-{:02}", year, month, day);
+    if seconds != 0 {
+        text.push_str(&format!("T{:02}:{:02}", seconds / 3600, (seconds % 3600) / 60));
+        if seconds % 60 != 0 {
+            text.push_str(&format!(":{:02}", seconds % 60));
+        }
+    }
+    text
+}
+
+/// 显示时长: 整天数为 `75 days`, 其他为 `2d 4h 30m`
+pub fn format_duration(days: f64) -> String {
+    let minutes = (days.abs() * 1440.0).round() as i64;
+    let sign = if days < 0.0 && minutes != 0 { "-" } else { "" };
+    if minutes % 1440 == 0 {
+        let count = minutes / 1440;
+        let unit = if count == 1 { "day" } else { "days" };
+        return format!("{}{} {}", sign, format_with_thousands_separator(count as f64, 0), unit);
+    }
+    let mut parts = Vec::new();
+    if minutes >= 1440 {
+        parts.push(format!("{}d", format_with_thousands_separator((minutes / 1440) as f64, 0)));
+    }
+    if minutes % 1440 >= 60 {
+        parts.push(format!("{}h", (minutes % 1440) / 60));
+    }
+    if minutes % 60 != 0 {
+        parts.push(format!("{}m", minutes % 60));
+    }
+    format!("{}{}", sign, parts.join(" "))
+}
+
+/// 调用日期函数, 日期参数为天数
+pub fn call_date(name: &str, args: &[f64], calendar: &Calendar) -> Result<f64, String> {
+    let whole = |value: f64| value.floor() as i64;
+    Ok(match name {
+        "today" => calendar.today(),
+        "now" => calendar.now(),
+        "date" =>
+            match args {
+                [date] => *date,
+                [year, month, day] => {
+                    if [year, month, day].iter().any(|value| value.fract() != 0.0) {
+                        return Err("`date` needs whole numbers for year, month and day".to_string());
+                    }
+                    (days_from_civil(*year as i64, *month as i64, 1) + (*day as i64) - 1) as f64
+                }
+                _ => {
+                    return Err("`date` takes a date, or a year, month and day".to_string());
+                }
+            }
+        "addmonths" => {
+            if args[1].fract() != 0.0 {
+                return Err("`addmonths` needs a whole number of months".to_string());
+            }
+            let (year, month, day) = civil_from_days(whole(args[0]));
+            let months = month - 1 + (args[1] as i64);
+            let (year, month) = (year + months.div_euclid(12), months.rem_euclid(12) + 1);
+            let day = day.min(days_in_month(year, month));
+            (days_from_civil(year, month, day) as f64) + args[0].fract()
+        }
+        "workdays" => {
+            let (start, end) = (whole(args[0]), whole(args[1]));
+            if (end - start).abs() > MAX_DAYS {
+                return Err("`workdays` range is too long".to_string());
+            }
+            let count = (start.min(end)..=start.max(end))
+                .filter(|day| calendar.is_workday(*day))
+                .count() as f64;
+            if end < start { -count } else { count }
+        }
+        "addworkdays" => {
+            if args[1].fract() != 0.0 || args[1].abs() > (MAX_DAYS as f64) {
+                return Err("`addworkdays` needs a whole number of days".to_string());
+            }
+            let step = if args[1] < 0.0 { -1 } else { 1 };
+            let mut day = whole(args[0]);
+            let mut remaining = (args[1] as i64).abs();
+            while remaining > 0 {
+                day += step;
+                if calendar.is_workday(day) {
+                    remaining -= 1;
+                }
+            }
+            day as f64
+        }
+        "year" => civil_from_days(whole(args[0])).0 as f64,
+        "month" => civil_from_days(whole(args[0])).1 as f64,
+        "day" => civil_from_days(whole(args[0])).2 as f64,
+        "weekday" => weekday(whole(args[0])) as f64,
+        _ => {
+            return Err(format!("Unknown function `{}`", name));
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(text: &str) -> f64 {
+        parse_date(text).unwrap()
+    }
+
+    fn call(name: &str, args: &[f64]) -> f64 {
+        call_date(name, args, &Calendar::default()).unwrap()
+    }
+
+    #[test]
+    fn add_months_clamps_to_month_end() {
+        assert_eq!(format_date(call("addmonths", &[date("2026-01-31"), 1.0])), "2026-02-28");
+        assert_eq!(format_date(call("addmonths", &[date("2028-01-31"), 1.0])), "2028-02-29");
+        assert_eq!(format_date(call("addmonths", &[date("2026-03-15"), -3.0])), "2025-12-15");
+    }
+
+    #[test]
+    fn civil_dates() {
+        assert_eq!(days_from_civil(1899, 12, 30), 0);
+        assert_eq!(civil_from_days(days_from_civil(2026, 10, 17)), (2026, 10, 17));
+        // 2026-10-17 为星期六
+        assert_eq!(call("weekday", &[date("2026-10-17")]), 6.0);
+        assert_eq!(parse_date("2026-02-30"), None);
+    }
+
+    #[test]
+    fn workdays_skip_weekends() {
+        assert_eq!(call("workdays", &[date("2026-10-12"), date("2026-10-18")]), 5.0);
+        assert_eq!(format_date(call("addworkdays", &[date("2026-10-16"), 1.0])), "2026-10-19");
+    }
+}
@@ -0,0 +1,171 @@
+use rust_decimal::prelude::{ FromPrimitive, ToPrimitive };
+use rust_decimal::{ Decimal, MathematicalOps, RoundingStrategy };
+use std::str::FromStr;
+
+use crate::aggregate::Aggregate;
+use crate::evaluator::{ call_function, Number };
+use crate::parser::BinaryOp;
+
+/// 数值的计算方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Arithmetic {
+    /// 二进制浮点数, 速度快但 `0.1 + 0.2` 等有舍入误差
+    #[default]
+    Float,
+    /// 十进制定点数, 金额计算不产生二进制舍入误差
+    Decimal,
+    /// 有理数, `1/3 + 1/6` 得到 `1/2`
+    Fraction,
+    /// 复数, `sqrt(-4)` 得到 `2i`, 多项式方程给出全部复根
+    Complex,
+    /// 定宽整数 (程序员模式), 除法向零取整, 运算结果按 `word_size` 回绕
+    Integer,
+}
+
+impl Arithmetic {
+    /// 按 .func.toml 中 `arithmetic` 的取值查找
+    pub fn from_name(name: &str) -> Option<Arithmetic> {
+        match name.trim().to_lowercase().as_str() {
+            "float" | "f64" => Some(Arithmetic::Float),
+            "decimal" | "exact" => Some(Arithmetic::Decimal),
+            "fraction" | "rational" => Some(Arithmetic::Fraction),
+            "complex" => Some(Arithmetic::Complex),
+            "integer" | "int" | "programmer" => Some(Arithmetic::Integer),
+            _ => None,
+        }
+    }
+}
+
+/// 十进制模式下结果保留到指定小数位时的舍入方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Rounding {
+    /// 四舍五入
+    #[default]
+    HalfUp,
+    /// 银行家舍入 (四舍六入五成双)
+    HalfEven,
+}
+
+impl Rounding {
+    /// 按 .func.toml 中 `rounding` 的取值查找
+    pub fn from_name(name: &str) -> Option<Rounding> {
+        match name.trim().to_lowercase().replace(['-', ' '], "_").as_str() {
+            "half_up" => Some(Rounding::HalfUp),
+            "half_even" | "bankers" | "banker" => Some(Rounding::HalfEven),
+            _ => None,
+        }
+    }
+
+    pub fn round(self, value: Decimal, decimal_places: u32) -> Decimal {
+        let strategy = match self {
+            Rounding::HalfUp => RoundingStrategy::MidpointAwayFromZero,
+            Rounding::HalfEven => RoundingStrategy::MidpointNearestEven,
+        };
+        value.round_dp_with_strategy(decimal_places, strategy)
+    }
+}
+
+/// 解析不带千位分隔符的数值, 支持 `1.5e3` 形式
+pub fn parse_decimal(text: &str) -> Option<Decimal> {
+    let text = text.trim();
+    if text.contains(['e', 'E']) {
+        Decimal::from_scientific(text).ok()
+    } else {
+        Decimal::from_str(text).ok()
+    }
+}
+
+/// 十进制运算溢出或除以零时报错, 超越函数借助浮点数计算
+impl Number for Decimal {
+    fn literal(_value: f64, digits: &str) -> Result<Self, String> {
+        parse_decimal(digits).ok_or_else(|| format!("Number `{}` is out of range", digits))
+    }
+
+    fn parse(text: &str) -> Option<Self> {
+        parse_decimal(text)
+    }
+
+    fn from_f64(value: f64) -> Option<Self> {
+        <Decimal as FromPrimitive>::from_f64(value)
+    }
+
+    fn to_f64(self) -> f64 {
+        ToPrimitive::to_f64(&self).unwrap_or(f64::NAN)
+    }
+
+    fn binary(op: BinaryOp, lhs: Self, rhs: Self) -> Result<Self, String> {
+        if matches!(op, BinaryOp::Div | BinaryOp::Rem) && rhs.is_zero() {
+            return Err("Division by zero".to_string());
+        }
+        let value = match op {
+            BinaryOp::Add => lhs.checked_add(rhs),
+            BinaryOp::Sub => lhs.checked_sub(rhs),
+            BinaryOp::Mul => lhs.checked_mul(rhs),
+            BinaryOp::Div => lhs.checked_div(rhs),
+            BinaryOp::Rem => lhs.checked_rem(rhs),
+            // 整数次幂精确计算
+            BinaryOp::Pow =>
+                match rhs.to_i64() {
+                    Some(exponent) if rhs.fract().is_zero() => lhs.checked_powi(exponent),
+                    _ => lhs.checked_powd(rhs),
+                }
+        };
+        value.ok_or_else(|| "Overflow".to_string())
+    }
+
+    fn neg(self) -> Self {
+        -self
+    }
+
+    fn percent(self) -> Self {
+        self / Decimal::ONE_HUNDRED
+    }
+
+    fn call(name: &str, args: &[Self]) -> Result<Self, String> {
+        let x = args[0];
+        let value = match name {
+            "abs" => Some(x.abs()),
+            "floor" => Some(x.floor()),
+            "ceil" => Some(x.ceil()),
+            "round" => Some(x.round_dp_with_strategy(0, RoundingStrategy::MidpointAwayFromZero)),
+            "min" => args.iter().copied().min(),
+            "max" => args.iter().copied().max(),
+            "sqrt" => x.sqrt(),
+            _ => {
+                let args: Vec<f64> = args
+                    .iter()
+                    .map(|arg| Number::to_f64(*arg))
+                    .collect();
+                <Decimal as FromPrimitive>::from_f64(call_function(name, &args))
+            }
+        };
+        value.ok_or_else(|| format!("`{}` is undefined for this value", name))
+    }
+
+    fn aggregate(aggregate: Aggregate, values: &[Self]) -> Option<Self> {
+        aggregate.apply_decimal(values)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decimal(text: &str) -> Decimal {
+        parse_decimal(text).unwrap()
+    }
+
+    #[test]
+    fn exact_decimal_sums() {
+        let sum = Decimal::binary(BinaryOp::Add, decimal("0.1"), decimal("0.2")).unwrap();
+        assert_eq!(sum, decimal("0.3"));
+        assert_eq!(decimal("1.5e3"), decimal("1500"));
+    }
+
+    #[test]
+    fn rounding_modes() {
+        assert_eq!(Rounding::HalfUp.round(decimal("2.345"), 2), decimal("2.35"));
+        assert_eq!(Rounding::HalfEven.round(decimal("2.345"), 2), decimal("2.34"));
+        assert_eq!(Rounding::HalfEven.round(decimal("2.355"), 2), decimal("2.36"));
+    }
+}
@@ -0,0 +1,1644 @@
+use num_complex::Complex64;
+use rust_decimal::Decimal;
+use std::collections::HashMap;
+use std::ops::Range;
+
+use crate::aggregate::Aggregate;
+use crate::complex::{ complex_text, format_complex, parse_complex, ComplexForm };
+use crate::dates::{ format_date, format_duration, parse_date, Calendar };
+use crate::decimal::{ Arithmetic, Rounding };
+use crate::error::EvalError;
+use crate::evaluator::{ evaluate, Number, Scope };
+use crate::format::{
+    display_width,
+    format_decimal,
+    format_with_thousands_separator,
+    parse_number,
+    remove_thousands_separator,
+    NumberFormat,
+};
+use crate::fraction::{ format_fraction, fraction_from_f64, fraction_text, Fraction, FractionStyle };
+use crate::functions::{ UserFunction, UserFunctions };
+use crate::integer::{ format_integer, Base, WordSize };
+use crate::locale::Locale;
+use crate::matrix::{ format_matrix, is_matrix_function, matrix_text, Matrix };
+use crate::parser::{ parse_with_functions, BinaryOp, Expr, ExprKind, RootHint, Span, Statement };
+use crate::solver::{
+    complex_roots,
+    polynomial_coefficients,
+    real_roots,
+    root_near,
+    roots_in_interval,
+    solve_linear_system,
+};
+use crate::stats::Summary;
+use crate::units::{ format_quantity, parse_quantity, Quantity };
+
+/// 每个 section 的默认行数 (A ~ T)
+pub const ROW_COUNT: usize = 20;
+
+/// 每个 section 的最大行数 (A ~ T, AA ~ ZZ)
+pub const MAX_ROWS: usize = ROW_COUNT + 26 * 26;
+
+/// 默认参与求和与平均值计算的行数 (A ~ Q)
+pub const SUM_ROWS: usize = 17;
+
+/// 将行号转换为标签, 0 -> A ... 19 -> T, 20 -> AA, 21 -> AB ...
+///
+/// T 之后直接使用两个字母, 跳过 U ~ Z, 避免与未知数 `x` 和总和 `z` 冲突。
+pub fn row_label(index: usize) -> String {
+    if index < ROW_COUNT {
+        return ((b'A' + (index as u8)) as char).to_string();
+    }
+    let index = index - ROW_COUNT;
+    let first = (b'A' + ((index / 26) as u8)) as char;
+    let second = (b'A' + ((index % 26) as u8)) as char;
+    format!("{}{}", first, second)
+}
+
+/// 将标签转换为行号, A -> 0, AA -> 20 ..., 不是有效行标签时返回 None
+pub fn row_index(label: &str) -> Option<usize> {
+    let bytes = label.as_bytes();
+    match bytes {
+        [c @ b'A'..=b'Z'] => {
+            let index = (c - b'A') as usize;
+            if index < ROW_COUNT { Some(index) } else { None }
+        }
+        [first @ b'A'..=b'Z', second @ b'A'..=b'Z'] => {
+            Some(ROW_COUNT + ((first - b'A') as usize) * 26 + ((second - b'A') as usize))
+        }
+        _ => None,
+    }
+}
+
+/// 解析 `A:Q` 或 `A-Q` 形式的行区间, 两端都包含在内
+pub fn parse_row_range(spec: &str) -> Option<Range<usize>> {
+    let (start, end) = spec.split_once([':', '-'])?;
+    let start = row_index(&start.trim().to_uppercase())?;
+    let end = row_index(&end.trim().to_uppercase())?;
+    if start <= end { Some(start..end + 1) } else { None }
+}
+
+/// 行区间的显示名称, 例如 `A - Q`
+pub fn range_label(rows: &Range<usize>) -> String {
+    format!("{} - {}", row_label(rows.start), row_label(rows.end.saturating_sub(1)))
+}
+
+/// 一行的计算结果: 显示的文本和作为行变量导出的值
+///
+/// 方程有多个根时显示全部的根, 但只导出其中一个。
+struct Solution {
+    display: String,
+    value: String,
+}
+
+impl From<String> for Solution {
+    fn from(value: String) -> Self {
+        Self { display: value.clone(), value }
+    }
+}
+
+/// 表达式结果的种类, 日期和时长按天数计算, 显示为 `2026-10-17` 和 `75 days`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ValueKind {
+    Number,
+    Date,
+    Duration,
+}
+
+/// 由若干行组成的线性方程组
+#[derive(Debug, Clone)]
+struct LinearSystem {
+    rows: Vec<usize>,
+    inputs: Vec<String>,
+    /// 按首次出现的顺序排列
+    unknowns: Vec<String>,
+    /// 在方程组的第一行求解一次
+    solution: Option<Result<Vec<f64>, EvalError>>,
+}
+
+/// 一行引用的其他行以及引用处在输入中的位置
+///
+/// 聚合值依赖整个求和区域, 已求解的变量依赖其所在方程组的全部行。
+#[derive(Debug, Clone, Default)]
+struct Dependencies {
+    rows: Vec<(usize, Option<Span>)>,
+    aggregates: bool,
+}
+
+/// 上次评估整个工作表时的输入和结果
+#[derive(Debug, Clone)]
+struct SheetCache {
+    inputs: Vec<String>,
+    results: Vec<Result<String, EvalError>>,
+}
+
+/// 按依赖关系评估时每行的状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Visit {
+    Pending,
+    Active,
+    Done,
+}
+
+/// 求值时名称的取值来源: 行变量、聚合值、正在求解的未知数以及方程组中已求解的变量
+struct RowScope<'a> {
+    engine: &'a Engine,
+    row: usize,
+    unknowns: &'a [String],
+    values: &'a [f64],
+    /// 是否代入方程组中已求解的变量
+    solved: bool,
+}
+
+impl<N: Number> Scope<N> for RowScope<'_> {
+    fn row(&self, index: usize) -> Result<N, String> {
+        let label = row_label(index);
+        if index == self.row {
+            return Err(format!("Row {} refers to itself", label));
+        }
+        match self.engine.variables.get(&label) {
+            Some(value) if value.is_empty() => Err(format!("Row {} is empty", label)),
+            Some(value) =>
+                parse_value(value).ok_or_else(|| {
+                    format!("Row {} is not a number", label)
+                }),
+            None => Err(format!("Row {} has no value", label)),
+        }
+    }
+
+    fn optional_row(&self, index: usize) -> Result<Option<N>, String> {
+        if index == self.row {
+            return Err(format!("Row {} refers to itself", row_label(index)));
+        }
+        Ok(
+            self.engine.variables
+                .get(&row_label(index))
+                .and_then(|value| parse_value(value))
+        )
+    }
+
+    fn name(&self, name: &str) -> Option<N> {
+        let value = match self.unknowns.iter().position(|unknown| unknown == name) {
+            Some(index) => Some(self.values[index]),
+            None if self.solved => self.engine.solved_value(name, self.row),
+            None => None,
+        };
+        N::from_f64(value?)
+    }
+
+    /// 求和区域内的行不能引用聚合值, 否则结果依赖于自身
+    fn aggregate(&self, aggregate: Aggregate) -> Result<N, String> {
+        if self.engine.sum_rows.contains(&self.row) {
+            return Err(
+                format!(
+                    "`{}` is not allowed in the {} area",
+                    aggregate.name(),
+                    range_label(&self.engine.sum_rows)
+                )
+            );
+        }
+        self.engine.aggregate_as::<N>(aggregate).ok_or_else(|| {
+            if self.engine.region_has_units() {
+                format!("Incompatible units in the {} area", range_label(&self.engine.sum_rows))
+            } else {
+                format!("`{}` is out of range", aggregate.name())
+            }
+        })
+    }
+
+    fn calendar(&self) -> &Calendar {
+        &self.engine.calendar
+    }
+
+    fn word_size(&self) -> WordSize {
+        self.engine.word_size
+    }
+
+    fn function(&self, name: &str) -> Option<&UserFunction> {
+        self.engine.functions.get(name)
+    }
+}
+
+/// 行变量的值, 日期行取其天数
+fn parse_value<N: Number>(value: &str) -> Option<N> {
+    N::parse(&remove_thousands_separator(value)).or_else(|| N::from_f64(parse_date(value)?))
+}
+
+/// 工作表计算引擎
+///
+/// 持有变量表、求和区域 (默认 A - Q) 的数值以及小数位数等状态, 每个实例互不影响。
+#[derive(Debug, Clone)]
+pub struct Engine {
+    variables: HashMap<String, String>,
+    region_values: Vec<f64>,
+    /// 求和区域中各行导出的数值文本, 十进制模式由此精确计算聚合值
+    region_texts: Vec<String>,
+    /// 求和区域无法合计的原因, 例如单位不一致
+    region_error: Option<String>,
+    sum_rows: Range<usize>,
+    aggregates: Vec<Aggregate>,
+    decimal_places: usize,
+    arithmetic: Arithmetic,
+    rounding: Rounding,
+    fraction_style: FractionStyle,
+    complex_form: ComplexForm,
+    /// 程序员模式的整数宽度和结果列的显示进制
+    word_size: WordSize,
+    base: Base,
+    /// 没有 `@` 指令的表达式行使用的显示格式
+    format: Option<NumberFormat>,
+    /// `@currency` 未指定币种时使用的币种
+    currency: String,
+    /// 输入和显示使用的小数点与分组符号, 行变量仍保存标准写法
+    locale: Locale,
+    /// `today()` 的时区以及 `workdays` 跳过的节假日
+    calendar: Calendar,
+    /// .func.toml 的 [functions] 中定义的函数
+    functions: UserFunctions,
+    max_result_len: usize,
+    systems: Vec<LinearSystem>,
+    /// 输入和设置都未改动时, 只重新计算改动的行及依赖它们的行
+    cache: Option<SheetCache>,
+}
+
+impl Default for Engine {
+    fn default() -> Self {
+        Self {
+            variables: HashMap::new(),
+            region_values: Vec::new(),
+            region_texts: Vec::new(),
+            region_error: None,
+            sum_rows: 0..SUM_ROWS,
+            aggregates: vec![Aggregate::Sum, Aggregate::Average],
+            decimal_places: 4, // 默认保留四位小数, 可由 precision 或行末的 `@2` 修改
+            arithmetic: Arithmetic::Float,
+            rounding: Rounding::HalfUp,
+            fraction_style: FractionStyle::Fraction,
+            complex_form: ComplexForm::Rectangular,
+            word_size: WordSize::default(),
+            base: Base::Decimal,
+            format: None,
+            currency: "CNY".to_string(),
+            locale: Locale::default(),
+            calendar: Calendar::default(),
+            functions: UserFunctions::default(),
+            max_result_len: 17,
+            systems: Vec::new(),
+            cache: None,
+        }
+    }
+}
+
+impl Engine {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn decimal_places(&self) -> usize {
+        self.decimal_places
+    }
+
+    pub fn set_decimal_places(&mut self, decimal_places: usize) {
+        if self.decimal_places != decimal_places {
+            self.decimal_places = decimal_places;
+            self.cache = None;
+        }
+    }
+
+    /// 浮点数、十进制、分数或复数计算
+    pub fn arithmetic(&self) -> Arithmetic {
+        self.arithmetic
+    }
+
+    pub fn set_arithmetic(&mut self, arithmetic: Arithmetic) {
+        if self.arithmetic != arithmetic {
+            self.arithmetic = arithmetic;
+            self.cache = None;
+        }
+    }
+
+    /// 十进制模式下结果保留到小数位数时的舍入方式
+    pub fn rounding(&self) -> Rounding {
+        self.rounding
+    }
+
+    pub fn set_rounding(&mut self, rounding: Rounding) {
+        if self.rounding != rounding {
+            self.rounding = rounding;
+            self.cache = None;
+        }
+    }
+
+    /// 分数模式下结果显示为分数、带分数或小数
+    pub fn fraction_style(&self) -> FractionStyle {
+        self.fraction_style
+    }
+
+    pub fn set_fraction_style(&mut self, fraction_style: FractionStyle) {
+        if self.fraction_style != fraction_style {
+            self.fraction_style = fraction_style;
+            self.cache = None;
+        }
+    }
+
+    /// 复数结果显示为直角坐标或极坐标
+    pub fn complex_form(&self) -> ComplexForm {
+        self.complex_form
+    }
+
+    pub fn set_complex_form(&mut self, complex_form: ComplexForm) {
+        if self.complex_form != complex_form {
+            self.complex_form = complex_form;
+            self.cache = None;
+        }
+    }
+
+    /// 程序员模式的整数宽度, 每步运算按宽度回绕
+    pub fn word_size(&self) -> WordSize {
+        self.word_size
+    }
+
+    pub fn set_word_size(&mut self, word_size: WordSize) {
+        if self.word_size != word_size {
+            self.word_size = word_size;
+            self.cache = None;
+        }
+    }
+
+    /// 程序员模式下结果列的显示进制
+    pub fn base(&self) -> Base {
+        self.base
+    }
+
+    pub fn set_base(&mut self, base: Base) {
+        if self.base != base {
+            self.base = base;
+            self.cache = None;
+        }
+    }
+
+    /// 表达式行的默认显示格式, 行末的 `@` 指令优先
+    pub fn format(&self) -> Option<&NumberFormat> {
+        self.format.as_ref()
+    }
+
+    pub fn set_format(&mut self, format: Option<NumberFormat>) {
+        if self.format != format {
+            self.format = format;
+            self.cache = None;
+        }
+    }
+
+    /// `@currency` 的默认币种, 例如 `CNY`、`USD`
+    pub fn currency(&self) -> &str {
+        &self.currency
+    }
+
+    pub fn set_currency(&mut self, currency: &str) {
+        let currency = currency.trim().to_uppercase();
+        if self.currency != currency {
+            self.currency = currency;
+            self.cache = None;
+        }
+    }
+
+    /// 小数点、分组符号和分组方式
+    pub fn locale(&self) -> &Locale {
+        &self.locale
+    }
+
+    pub fn set_locale(&mut self, locale: Locale) {
+        if self.locale != locale {
+            self.locale = locale;
+            self.cache = None;
+        }
+    }
+
+    /// `today()` 的时区以及 `workdays` 跳过的节假日
+    pub fn calendar(&self) -> &Calendar {
+        &self.calendar
+    }
+
+    pub fn set_calendar(&mut self, calendar: Calendar) {
+        if self.calendar != calendar {
+            self.calendar = calendar;
+            self.cache = None;
+        }
+    }
+
+    pub fn functions(&self) -> &UserFunctions {
+        &self.functions
+    }
+
+    /// 替换用户函数, 重新载入 .func.toml 时调用
+    pub fn set_functions(&mut self, functions: UserFunctions) {
+        self.functions = functions;
+        self.cache = None;
+    }
+
+    /// 结果超过该长度时视为错误 (TUI 结果列宽度减去边框)
+    pub fn set_max_result_len(&mut self, max_result_len: usize) {
+        if self.max_result_len != max_result_len {
+            self.max_result_len = max_result_len;
+            self.cache = None;
+        }
+    }
+
+    /// 参与求和与平均值计算的行
+    pub fn sum_rows(&self) -> Range<usize> {
+        self.sum_rows.clone()
+    }
+
+    pub fn set_sum_rows(&mut self, sum_rows: Range<usize>) {
+        if self.sum_rows != sum_rows {
+            self.sum_rows = sum_rows;
+            self.cache = None;
+        }
+    }
+
+    /// 底部合计栏显示的聚合函数, 默认为 Sum 和 Average
+    pub fn aggregates(&self) -> &[Aggregate] {
+        &self.aggregates
+    }
+
+    pub fn set_aggregates(&mut self, aggregates: Vec<Aggregate>) {
+        self.aggregates = aggregates;
+    }
+
+    pub fn variables(&self) -> &HashMap<String, String> {
+        &self.variables
+    }
+
+    pub fn set_variable(&mut self, label: &str, value: String) {
+        self.variables.insert(label.to_string(), value);
+        self.cache = None;
+    }
+
+    pub fn remove_variable(&mut self, label: &str) {
+        self.variables.remove(label);
+        self.cache = None;
+    }
+
+    pub fn clear_variables(&mut self) {
+        self.variables.clear();
+        self.cache = None;
+    }
+
+    /// 求和区域的总和, 即表达式中的 `z` 或 `sum`
+    pub fn sum(&self) -> f64 {
+        self.aggregate(Aggregate::Sum)
+    }
+
+    /// 求和区域中有效数值的个数, 与合计栏使用相同的数值
+    pub fn count(&self) -> usize {
+        match self.arithmetic {
+            Arithmetic::Complex => self.aggregate(Aggregate::Count) as usize,
+            _ => self.region_values.len(),
+        }
+    }
+
+    /// 求和区域的平均值
+    pub fn average(&self) -> f64 {
+        self.aggregate(Aggregate::Average)
+    }
+
+    /// 求和区域的描述统计, 区域中没有数值时返回 None
+    pub fn summary(&self) -> Option<Summary> {
+        Summary::new(&self.region_values)
+    }
+
+    /// 求和区域的任意聚合值, 区域无法合计或复数模式下结果不是实数时为 NaN
+    pub fn aggregate(&self, aggregate: Aggregate) -> f64 {
+        if self.region_error.is_some() {
+            return f64::NAN;
+        }
+        if self.arithmetic == Arithmetic::Complex && !self.region_has_units() {
+            return match self.aggregate_as::<Complex64>(aggregate).map(clean_complex) {
+                Some(value) if value.im == 0.0 => value.re,
+                _ => f64::NAN,
+            };
+        }
+        aggregate.apply(&self.region_values)
+    }
+
+    /// 求和区域无法合计的原因, 例如 ``Incompatible units: km and s``
+    pub fn region_error(&self) -> Option<&str> {
+        self.region_error.as_deref()
+    }
+
+    /// 以指定的数值类型计算求和区域的聚合值
+    fn aggregate_as<N: Number>(&self, aggregate: Aggregate) -> Option<N> {
+        let values: Vec<N> = self.region_texts
+            .iter()
+            .filter_map(|text| N::parse(text))
+            .collect();
+        N::aggregate(aggregate, &values)
+    }
+
+    /// 求和区域中是否有带单位的数值
+    fn region_has_units(&self) -> bool {
+        self.region_texts.iter().any(|text| has_unit(text))
+    }
+
+    /// 按当前计算方式和区域格式化聚合值, 十进制和分数模式下精确计算, 带单位时保留单位
+    pub fn format_aggregate(&self, aggregate: Aggregate) -> String {
+        self.locale.localize(&self.aggregate_text(aggregate))
+    }
+
+    fn aggregate_text(&self, aggregate: Aggregate) -> String {
+        if let Some(err) = &self.region_error {
+            return err.clone();
+        }
+        if self.region_has_units() {
+            if let Some(value) = self.aggregate_as::<Quantity>(aggregate) {
+                return format_quantity(&value, self.decimal_places);
+            }
+        }
+        match self.arithmetic {
+            Arithmetic::Float => self.format_number(self.aggregate(aggregate)),
+            Arithmetic::Decimal =>
+                match self.aggregate_as::<Decimal>(aggregate) {
+                    Some(value) => self.format_decimal(value),
+                    None => self.format_number(self.aggregate(aggregate)),
+                }
+            Arithmetic::Fraction =>
+                match self.aggregate_as::<Fraction>(aggregate) {
+                    Some(value) => self.format_fraction(&value),
+                    None => self.format_number(self.aggregate(aggregate)),
+                }
+            Arithmetic::Complex =>
+                match self.aggregate_as::<Complex64>(aggregate) {
+                    Some(value) => self.format_complex(value),
+                    // 中位数、最值等只对实数有意义
+                    None => "n/a".to_string(),
+                }
+            Arithmetic::Integer =>
+                match self.aggregate_as::<i128>(aggregate) {
+                    Some(value) => format_integer(self.word_size.wrap(value), self.base, self.word_size),
+                    None => self.format_number(self.aggregate(aggregate)),
+                }
+        }
+    }
+
+    /// 按当前小数位数格式化数值, 分数模式下能还原为分数时显示分数
+    pub fn format_number(&self, value: f64) -> String {
+        match self.arithmetic {
+            Arithmetic::Decimal =>
+                match <Decimal as Number>::from_f64(value) {
+                    Some(value) => self.format_decimal(value),
+                    None => format_with_thousands_separator(value, self.decimal_places),
+                }
+            Arithmetic::Fraction =>
+                match fraction_from_f64(value) {
+                    Some(value) => self.format_fraction(&value),
+                    None => format_with_thousands_separator(value, self.decimal_places),
+                }
+            Arithmetic::Integer if value.fract() == 0.0 && value.abs() < 9.2e18 =>
+                format_integer(value as i128, self.base, self.word_size),
+            Arithmetic::Float | Arithmetic::Complex | Arithmetic::Integer =>
+                format_with_thousands_separator(value, self.decimal_places),
+        }
+    }
+
+    /// 按当前显示方式格式化分数
+    pub fn format_fraction(&self, value: &Fraction) -> String {
+        format_fraction(value, self.fraction_style, self.decimal_places)
+    }
+
+    /// 按当前显示方式格式化复数, 虚部为零时按实数格式化
+    pub fn format_complex(&self, value: Complex64) -> String {
+        let value = clean_complex(value);
+        if value.im == 0.0 {
+            return self.format_number(value.re);
+        }
+        format_complex(&value, self.complex_form, self.decimal_places, self.max_result_len)
+    }
+
+    /// 复数结果的显示和导出, 虚部为零时与实数结果相同
+    fn complex_solution(&self, value: Complex64) -> Result<Solution, EvalError> {
+        if !value.is_finite() {
+            return Err(EvalError::new(if value.is_nan() { "Result is not a number" } else { "Division by zero or overflow" }));
+        }
+        let value = clean_complex(value);
+        if value.im == 0.0 {
+            return Ok(self.number_solution(value.re));
+        }
+        Ok(Solution { display: self.format_complex(value), value: complex_text(&value) })
+    }
+
+    /// 表达式是否需要按复数计算: 含有虚数、极坐标或引用了复数结果的行
+    fn needs_complex(&self, expr: &Expr) -> bool {
+        let mut complex = false;
+        expr.walk(
+            &mut (|expr| {
+                complex |= match &expr.kind {
+                    ExprKind::Imaginary(_) => true,
+                    ExprKind::Call(name, _) => name == "polar",
+                    ExprKind::Row(index) => self.row_is_complex(*index),
+                    ExprKind::Range(rows) => rows.clone().any(|index| self.row_is_complex(index)),
+                    _ => false,
+                };
+            })
+        );
+        complex
+    }
+
+    /// 表达式是否需要带单位计算: 含有单位、换算或引用了带单位的行和聚合值
+    fn needs_units(&self, expr: &Expr) -> bool {
+        let mut units = false;
+        expr.walk(
+            &mut (|expr| {
+                units |= match &expr.kind {
+                    ExprKind::Unit(_) | ExprKind::Convert(..) => true,
+                    ExprKind::Row(index) => self.row_has_unit(*index),
+                    ExprKind::Range(rows) => rows.clone().any(|index| self.row_has_unit(index)),
+                    ExprKind::Aggregate(_) => self.region_has_units(),
+                    _ => false,
+                };
+            })
+        );
+        units
+    }
+
+    /// 表达式是否需要按矩阵计算: 含有矩阵、矩阵函数或引用了矩阵结果的行
+    fn needs_matrix(&self, expr: &Expr) -> bool {
+        let mut matrix = false;
+        expr.walk(
+            &mut (|expr| {
+                matrix |= match &expr.kind {
+                    ExprKind::Matrix(_) => true,
+                    ExprKind::Call(name, _) => is_matrix_function(name),
+                    ExprKind::Row(index) => self.row_is_matrix(*index),
+                    ExprKind::Range(rows) => rows.clone().any(|index| self.row_is_matrix(index)),
+                    _ => false,
+                };
+            })
+        );
+        matrix
+    }
+
+    /// 表达式的结果是数值、日期还是时长
+    ///
+    /// 日期只能加减: 日期加减数值或时长得到日期, 两个日期相减得到时长, 时长之间的加减和与数值的乘除仍为时长。
+    fn value_kind(&self, expr: &Expr) -> Result<ValueKind, EvalError> {
+        let kind = match &expr.kind {
+            ExprKind::Date(_) => ValueKind::Date,
+            ExprKind::Duration(_) => ValueKind::Duration,
+            ExprKind::Row(index) => {
+                let is_date = self.variables.get(&row_label(*index)).is_some_and(|value| parse_date(value).is_some());
+                if is_date { ValueKind::Date } else { ValueKind::Number }
+            }
+            ExprKind::Neg(operand) =>
+                match self.value_kind(operand)? {
+                    ValueKind::Date => {
+                        return Err(EvalError::at("A date cannot be negated", expr.span.clone()));
+                    }
+                    kind => kind,
+                }
+            ExprKind::Percent(operand) | ExprKind::BitNot(operand) | ExprKind::Convert(operand, _) => {
+                if self.value_kind(operand)? != ValueKind::Number {
+                    return Err(EvalError::at("Dates and durations can only be added, subtracted or scaled", expr.span.clone()));
+                }
+                ValueKind::Number
+            }
+            ExprKind::Binary(op, lhs, rhs) => {
+                use ValueKind::{ Date, Duration, Number };
+                let kinds = (self.value_kind(lhs)?, self.value_kind(rhs)?);
+                match (op, kinds) {
+                    (_, (Number, Number)) => Number,
+                    (BinaryOp::Add, (Date, Number | Duration) | (Number | Duration, Date)) => Date,
+                    (BinaryOp::Sub, (Date, Number | Duration)) => Date,
+                    (BinaryOp::Sub, (Date, Date)) => Duration,
+                    (BinaryOp::Add | BinaryOp::Sub, (Duration, Number | Duration) | (Number, Duration)) => Duration,
+                    (BinaryOp::Mul, (Duration, Number) | (Number, Duration)) => Duration,
+                    (BinaryOp::Div, (Duration, Number)) => Duration,
+                    (BinaryOp::Div, (Duration, Duration)) => Number,
+                    (BinaryOp::Add, (Date, Date)) => {
+                        return Err(EvalError::at("Two dates cannot be added", expr.span.clone()));
+                    }
+                    (BinaryOp::Sub, (Number | Duration, Date)) => {
+                        return Err(EvalError::at("A date can only be subtracted from another date", expr.span.clone()));
+                    }
+                    _ => {
+                        return Err(EvalError::at("Dates and durations can only be added, subtracted or scaled", expr.span.clone()));
+                    }
+                }
+            }
+            ExprKind::Bitwise(_, lhs, rhs) => {
+                if self.value_kind(lhs)? != ValueKind::Number || self.value_kind(rhs)? != ValueKind::Number {
+                    return Err(EvalError::at("Dates and durations can only be added, subtracted or scaled", expr.span.clone()));
+                }
+                ValueKind::Number
+            }
+            ExprKind::Call(name, args) => {
+                for arg in args {
+                    self.value_kind(arg)?;
+                }
+                if matches!(name.as_str(), "today" | "now" | "date" | "addmonths" | "addworkdays") {
+                    ValueKind::Date
+                } else {
+                    ValueKind::Number
+                }
+            }
+            _ => ValueKind::Number,
+        };
+        Ok(kind)
+    }
+
+    /// 行变量是否为复数
+    fn row_is_complex(&self, index: usize) -> bool {
+        self.variables
+            .get(&row_label(index))
+            .is_some_and(|value| parse_number(value).is_none() && parse_complex(value).is_some())
+    }
+
+    /// 行变量是否为向量或矩阵
+    fn row_is_matrix(&self, index: usize) -> bool {
+        self.variables.get(&row_label(index)).is_some_and(|value| value.starts_with('['))
+    }
+
+    /// 行变量是否带单位
+    fn row_has_unit(&self, index: usize) -> bool {
+        self.variables.get(&row_label(index)).is_some_and(|value| has_unit(value))
+    }
+
+    /// 带单位结果的显示和导出, 量纲抵消时与数值结果相同
+    fn quantity_solution(&self, value: Quantity) -> Result<Solution, EvalError> {
+        if !value.value.is_finite() {
+            return Err(EvalError::new(if value.value.is_nan() { "Result is not a real number" } else { "Division by zero or overflow" }));
+        }
+        if value.is_dimensionless() {
+            return Ok(self.number_solution(value.value));
+        }
+        Ok(format_quantity(&value, self.decimal_places).into())
+    }
+
+    /// 数值结果的显示和导出, 分数模式下导出假分数, 使引用该行的表达式保持精确
+    fn number_solution(&self, value: f64) -> Solution {
+        let display = self.format_number(value);
+        match fraction_from_f64(value) {
+            Some(fraction) if self.arithmetic == Arithmetic::Fraction =>
+                Solution { display, value: fraction_text(&fraction) },
+            _ => display.into(),
+        }
+    }
+
+    /// 实数结果的显示和导出
+    fn real_solution(&self, value: f64, format: Option<&NumberFormat>) -> Result<Solution, EvalError> {
+        if value.is_infinite() {
+            return Err(EvalError::new("Division by zero or overflow"));
+        }
+        if value.is_nan() {
+            return Err(EvalError::new("Result is not a real number"));
+        }
+        match format {
+            Some(format) => self.formatted_solution(value, format),
+            None => Ok(self.number_solution(value)),
+        }
+    }
+
+    /// 矩阵结果的显示和导出, 1×1 的结果与数值相同
+    ///
+    /// 结果列放得下时单行显示向量, 否则显示大小, 方阵附带行列式, 例如 `2×2 det=-2`。
+    /// 默认格式只用于 1×1 的结果, 行内的 `@` 指令不能用于矩阵。
+    fn matrix_solution(
+        &self,
+        value: Matrix,
+        directive: Option<&NumberFormat>,
+        format: Option<&NumberFormat>
+    ) -> Result<Solution, EvalError> {
+        if value.is_scalar() {
+            return self.real_solution(value.to_f64(), format);
+        }
+        if !value.is_finite() {
+            return Err(EvalError::new("Division by zero or overflow"));
+        }
+        if let Some(format) = directive {
+            return Err(EvalError::new(format!("`{}` cannot be used with matrices", format.name())));
+        }
+        let mut candidates = Vec::new();
+        if value.is_vector() {
+            let (columns, rows) = if self.locale.argument_separator() == ';' { ("; ", " | ") } else { (", ", "; ") };
+            candidates.push(format_matrix(&value, self.decimal_places, columns, rows));
+        }
+        if let Ok(det) = value.determinant() {
+            candidates.push(format!("{} det={}", value.shape(), self.format_number(det)));
+        }
+        let display = candidates
+            .into_iter()
+            .find(|display| display_width(&self.locale.localize(display)) <= self.max_result_len)
+            .unwrap_or_else(|| value.shape());
+        Ok(Solution { display, value: matrix_text(&value) })
+    }
+
+    /// 按 `@` 指令显示数值
+    fn apply_format(&self, value: f64, format: &NumberFormat) -> Result<String, EvalError> {
+        format.apply(value, self.decimal_places, &self.currency).map_err(EvalError::new)
+    }
+
+    /// 按 `@` 指令显示的结果, 行变量导出完整精度的值
+    fn formatted_solution(&self, value: f64, format: &NumberFormat) -> Result<Solution, EvalError> {
+        Ok(Solution { display: self.apply_format(value, format)?, value: value.to_string() })
+    }
+
+    /// 十进制模式下 `@2` 按舍入方式保留小数位数, 其他格式借助浮点数显示
+    fn formatted_decimal(&self, value: Decimal, format: &NumberFormat) -> Result<Solution, EvalError> {
+        let display = match format {
+            NumberFormat::Fixed(places) => self.apply_format(Number::to_f64(self.rounding.round(value, *places as u32)), format)?,
+            _ => self.apply_format(Number::to_f64(value), format)?,
+        };
+        Ok(Solution { display, value: value.normalize().to_string() })
+    }
+
+    /// 带单位的结果只支持 `@2`、`@sci` 和 `@eng`, 格式作用于显示单位下的数值
+    fn formatted_quantity(&self, value: Quantity, format: &NumberFormat) -> Result<Solution, EvalError> {
+        self.quantity_solution(value.clone())?;
+        if value.is_dimensionless() {
+            return self.formatted_solution(value.value, format);
+        }
+        if !matches!(format, NumberFormat::Fixed(_) | NumberFormat::Scientific(_) | NumberFormat::Engineering(_)) {
+            return Err(EvalError::new(format!("`{}` cannot be used with units", format.name())));
+        }
+        let display = format!("{} {}", self.apply_format(value.display_value(), format)?, value.unit_name());
+        Ok(Solution { display, value: format!("{} {}", value.display_value(), value.unit_name()) })
+    }
+
+    /// 程序员模式的结果, `@hex`、`@oct` 和 `@bin` 显示按宽度截取的补码, 其他格式借助浮点数显示
+    fn integer_solution(&self, value: i128, format: Option<&NumberFormat>) -> Result<Solution, EvalError> {
+        let base = match format {
+            None => self.base,
+            Some(NumberFormat::Hex) => Base::Hex,
+            Some(NumberFormat::Octal) => Base::Octal,
+            Some(NumberFormat::Binary) => Base::Binary,
+            Some(format) => {
+                return self.formatted_solution(value as f64, format);
+            }
+        };
+        Ok(Solution { display: format_integer(value, base, self.word_size), value: value.to_string() })
+    }
+
+    /// 按舍入方式保留到当前小数位数并格式化十进制数
+    pub fn format_decimal(&self, value: Decimal) -> String {
+        format_decimal(self.rounding.round(value, self.decimal_places as u32))
+    }
+
+    /// 按区域设置解析一行输入, 可以调用用户函数
+    fn parse(&self, input: &str) -> Result<Statement, EvalError> {
+        parse_with_functions(input, &self.locale, &self.functions)
+    }
+
+    /// 评估单行输入并更新变量表
+    ///
+    /// 空行返回空字符串, 出错或结果过长时移除该行的变量。
+    pub fn evaluate_row(&mut self, input: &str, row: usize) -> Result<String, EvalError> {
+        self.cache = None;
+        self.evaluate_one(input, row)
+    }
+
+    fn evaluate_one(&mut self, input: &str, row: usize) -> Result<String, EvalError> {
+        let label = row_label(row);
+        if
+            let Some(index) = self.systems
+                .iter()
+                .position(|system| system.rows.contains(&row) && system.solution.is_none())
+        {
+            let solution = self.solve_system(&self.systems[index]);
+            self.systems[index].solution = Some(solution);
+        }
+        let result = if input.trim().is_empty() {
+            Ok(String::new().into())
+        } else {
+            self.solve(input, row).and_then(|solution| {
+                let solution = Solution { display: self.locale.localize(&solution.display), ..solution };
+                if display_width(&solution.display) <= self.max_result_len {
+                    Ok(solution)
+                } else {
+                    Err(EvalError::new("Result is too long to display."))
+                }
+            })
+        };
+
+        match result {
+            Ok(solution) => {
+                self.variables.insert(label, solution.value);
+                Ok(solution.display)
+            }
+            Err(err) => {
+                self.variables.remove(&label);
+                Err(err)
+            }
+        }
+    }
+
+    /// 按行之间的依赖关系评估整个工作表
+    ///
+    /// 每行在其引用的行之后计算, 因此可以引用下方的行; 互相引用的行报告为循环引用。
+    /// 与上次评估相比, 只重新计算输入改动的行以及直接或间接依赖它们的行。
+    /// 共享未知数的多个方程作为线性方程组一起求解。
+    pub fn evaluate_sheet(&mut self, inputs: &[String]) -> Vec<Result<String, EvalError>> {
+        let systems = self.find_systems(inputs);
+        let cache = self.cache
+            .take()
+            .filter(|cache| cache.inputs.len() == inputs.len() && same_systems(&self.systems, &systems));
+        if cache.is_none() {
+            self.systems = systems;
+        }
+        let dependencies: Vec<Dependencies> = (0..inputs.len())
+            .map(|row| self.dependencies(inputs, row))
+            .collect();
+
+        let mut dirty: Vec<bool> = match &cache {
+            Some(cache) =>
+                inputs
+                    .iter()
+                    .zip(&cache.inputs)
+                    .map(|(input, cached)| input != cached)
+                    .collect(),
+            None => vec![true; inputs.len()],
+        };
+        let mut dependents = vec![Vec::new(); inputs.len()];
+        for (row, dependency) in dependencies.iter().enumerate() {
+            for (target, _) in &dependency.rows {
+                dependents[*target].push(row);
+            }
+        }
+        let mut queue: Vec<usize> = (0..inputs.len()).filter(|row| dirty[*row]).collect();
+        while let Some(row) = queue.pop() {
+            for &dependent in &dependents[row] {
+                if !dirty[dependent] {
+                    dirty[dependent] = true;
+                    queue.push(dependent);
+                }
+            }
+        }
+        for system in &mut self.systems {
+            if system.rows.iter().any(|row| dirty[*row]) {
+                system.solution = None;
+            }
+        }
+
+        let mut results = match cache {
+            Some(cache) => cache.results,
+            None => vec![Ok(String::new()); inputs.len()],
+        };
+        let mut visits: Vec<Visit> = dirty
+            .iter()
+            .map(|dirty| if *dirty { Visit::Pending } else { Visit::Done })
+            .collect();
+        let mut stack = Vec::new();
+        for row in 0..inputs.len() {
+            self.visit(row, inputs, &dependencies, &mut visits, &mut stack, &mut results);
+        }
+        self.update_sum(inputs.len());
+        self.cache = Some(SheetCache { inputs: inputs.to_vec(), results: results.clone() });
+        results
+    }
+
+    /// 深度优先计算一行及其依赖的行, 遇到正在计算的行即为循环引用
+    fn visit(
+        &mut self,
+        row: usize,
+        inputs: &[String],
+        dependencies: &[Dependencies],
+        visits: &mut [Visit],
+        stack: &mut Vec<usize>,
+        results: &mut [Result<String, EvalError>]
+    ) {
+        match visits[row] {
+            Visit::Done => {
+                return;
+            }
+            Visit::Active => {
+                let start = stack
+                    .iter()
+                    .position(|active| *active == row)
+                    .unwrap_or(0);
+                let cycle = stack[start..].to_vec();
+                for (k, &member) in cycle.iter().enumerate() {
+                    let path = cycle[k..]
+                        .iter()
+                        .chain(&cycle[..k])
+                        .chain(std::iter::once(&member))
+                        .map(|row| row_label(*row))
+                        .collect::<Vec<_>>()
+                        .join(" → ");
+                    let next = cycle[(k + 1) % cycle.len()];
+                    let span = dependencies[member].rows
+                        .iter()
+                        .find(|(target, _)| *target == next)
+                        .and_then(|(_, span)| span.clone());
+                    results[member] = Err(EvalError {
+                        message: format!("Circular reference: {}", path),
+                        span,
+                    });
+                    self.variables.remove(&row_label(member));
+                    visits[member] = Visit::Done;
+                }
+                return;
+            }
+            Visit::Pending => {}
+        }
+
+        visits[row] = Visit::Active;
+        stack.push(row);
+        for (target, _) in &dependencies[row].rows {
+            self.visit(*target, inputs, dependencies, visits, stack, results);
+        }
+        stack.pop();
+        // 已作为循环引用的一部分报告
+        if visits[row] == Visit::Done {
+            return;
+        }
+        if dependencies[row].aggregates {
+            self.update_sum(inputs.len());
+        }
+        results[row] = self.evaluate_one(&inputs[row], row);
+        visits[row] = Visit::Done;
+    }
+
+    /// 一行依赖的行, 方程组中的行还依赖方程组其他行所依赖的行
+    fn dependencies(&self, inputs: &[String], row: usize) -> Dependencies {
+        let mut dependencies = self.direct_dependencies(inputs, row);
+        if let Some(system) = self.systems.iter().find(|system| system.rows.contains(&row)) {
+            for other in system.rows.iter().filter(|other| **other != row) {
+                let other = self.direct_dependencies(inputs, *other);
+                dependencies.aggregates |= other.aggregates;
+                dependencies.rows.extend(
+                    other.rows
+                        .into_iter()
+                        .filter(|(target, _)| *target != row)
+                        .map(|(target, _)| (target, None))
+                );
+            }
+        }
+        dependencies
+    }
+
+    fn direct_dependencies(&self, inputs: &[String], row: usize) -> Dependencies {
+        let mut dependencies = Dependencies::default();
+        let statement = match self.parse(&inputs[row]) {
+            Ok(statement) => statement,
+            Err(_) => {
+                return dependencies;
+            }
+        };
+        // 与 solve_equation 一致: 只有一个名称的方程不代入已求解的变量
+        let substitute = match &statement {
+            Statement::Equation { .. } => statement.unknowns().len() > 1,
+            _ => true,
+        };
+        let region = self.sum_rows.start..self.sum_rows.end.min(inputs.len());
+        statement.walk(
+            &mut (|expr| {
+                let span = Some(expr.span.clone());
+                match &expr.kind {
+                    ExprKind::Row(index) if *index < inputs.len() => {
+                        dependencies.rows.push((*index, span));
+                    }
+                    ExprKind::Range(rows) => {
+                        dependencies.rows.extend(
+                            rows
+                                .clone()
+                                .filter(|index| *index < inputs.len())
+                                .map(|index| (index, span.clone()))
+                        );
+                    }
+                    ExprKind::Aggregate(_) if !self.sum_rows.contains(&row) => {
+                        dependencies.aggregates = true;
+                        dependencies.rows.extend(region.clone().map(|index| (index, span.clone())));
+                    }
+                    ExprKind::Name(name) if substitute => {
+                        let system = self.systems.iter().find(|system| {
+                            system.rows.last().is_some_and(|last| *last < row) &&
+                                system.unknowns.contains(name)
+                        });
+                        if let Some(system) = system {
+                            dependencies.rows.extend(system.rows.iter().map(|index| (*index, span.clone())));
+                        }
+                    }
+                    _ => {}
+                }
+            })
+        );
+        dependencies
+    }
+
+    /// 按求和区域各行导出的变量值更新聚合值, 多根方程只计入选中的根
+    fn update_sum(&mut self, len: usize) {
+        let values: Vec<String> = (0..len.min(self.sum_rows.end))
+            .map(|i| self.variables.get(&row_label(i)).cloned().unwrap_or_default())
+            .collect();
+        self.calculate_sum_and_count(&values);
+    }
+
+    /// 记录求和区域中的有效数值, 并返回其总和和数量
+    ///
+    /// 区域中有带单位的数值时只合计带单位的行, 数值换算为第一行的单位, 量纲不同时记录错误。
+    pub fn calculate_sum_and_count(&mut self, results: &[String]) -> (f64, usize) {
+        self.region_values = region_values(results, self.sum_rows.clone());
+        self.region_texts = results
+            .iter()
+            .take(self.sum_rows.end)
+            .skip(self.sum_rows.start)
+            .map(|result| remove_thousands_separator(result))
+            .filter(|text| parse_number(text).is_some() || parse_complex(text).is_some() || has_unit(text))
+            .collect();
+        self.region_error = None;
+        if self.region_has_units() {
+            self.region_texts.retain(|text| has_unit(text));
+            let quantities: Vec<Quantity> = self.region_texts
+                .iter()
+                .filter_map(|text| parse_quantity(text))
+                .collect();
+            let first = &quantities[0];
+            let factor = first.unit.as_ref().map_or(1.0, |unit| unit.factor);
+            self.region_values = quantities
+                .iter()
+                .map(|quantity| quantity.value / factor)
+                .collect();
+            if let Some(other) = quantities.iter().find(|quantity| quantity.dims != first.dims) {
+                self.region_error = Some(
+                    format!("Incompatible units: {} and {}", first.unit_name(), other.unit_name())
+                );
+                self.region_values.clear();
+            }
+        }
+        (self.sum(), self.count())
+    }
+
+    /// 该行所在的方程组还缺少几个方程, 不属于方程组时返回 0
+    pub fn missing_equations(&self, row: usize) -> usize {
+        self.systems
+            .iter()
+            .find(|system| system.rows.contains(&row))
+            .map_or(0, |system| system.unknowns.len().saturating_sub(system.rows.len()))
+    }
+
+    /// 评估和求解输入中提供的数学表达式或方程
+    pub fn evaluate_and_solve(&self, input: &str, current_row: usize) -> Result<String, EvalError> {
+        self.solve(input, current_row).map(|solution| self.locale.localize(&solution.display))
+    }
+
+    fn solve(&self, input: &str, current_row: usize) -> Result<Solution, EvalError> {
+        // Direct command handling
+        if input.starts_with("fc:") {
+            return Ok("Import from cfg file".to_string().into());
+        }
+
+        if input.to_lowercase().starts_with("s:") {
+            return Ok("Qalculate!".to_string().into());
+        }
+
+        if let Some(system) = self.systems.iter().find(|system| system.rows.contains(&current_row)) {
+            return self.system_solution(system, current_row);
+        }
+
+        match self.parse(input)? {
+            Statement::Empty => Ok(String::new().into()),
+            Statement::Expression(expr, directive) => {
+                let format = directive.clone().or_else(|| self.format.clone());
+                let scope = RowScope { engine: self, row: current_row, unknowns: &[], values: &[], solved: true };
+                // 只有行内的 `@` 指令使日期按天数显示, 默认格式不适用
+                let kind = self.value_kind(&expr)?;
+                if kind != ValueKind::Number && directive.is_none() {
+                    let value: f64 = evaluate(&expr, &scope)?;
+                    if !value.is_finite() {
+                        return Err(EvalError::new("Division by zero or overflow"));
+                    }
+                    return Ok(
+                        if kind == ValueKind::Date {
+                            format_date(value).into()
+                        } else {
+                            Solution { display: format_duration(value), value: self.number_solution(value).value }
+                        }
+                    );
+                }
+                if self.needs_matrix(&expr) {
+                    let value: Matrix = evaluate(&expr, &scope)?;
+                    return self.matrix_solution(value, directive.as_ref(), format.as_ref());
+                }
+                if self.arithmetic == Arithmetic::Complex || self.needs_complex(&expr) {
+                    let value: Complex64 = evaluate(&expr, &scope)?;
+                    let solution = self.complex_solution(value)?;
+                    return match format {
+                        None => Ok(solution),
+                        Some(format) if clean_complex(value).im == 0.0 => self.formatted_solution(value.re, &format),
+                        Some(format) => Err(EvalError::new(format!("`{}` needs a real result", format.name()))),
+                    };
+                }
+                if self.needs_units(&expr) {
+                    let value: Quantity = evaluate(&expr, &scope)?;
+                    return match format {
+                        Some(format) => self.formatted_quantity(value, &format),
+                        None => self.quantity_solution(value),
+                    };
+                }
+                if self.arithmetic == Arithmetic::Decimal {
+                    let value: Decimal = evaluate(&expr, &scope)?;
+                    return match format {
+                        Some(format) => self.formatted_decimal(value, &format),
+                        None => Ok(self.format_decimal(value).into()),
+                    };
+                }
+                if self.arithmetic == Arithmetic::Integer {
+                    let value: i128 = evaluate(&expr, &scope)?;
+                    return self.integer_solution(value, format.as_ref());
+                }
+                // 结果不是有理数 (如 `sqrt(2)`) 或超出范围时按浮点数计算
+                if self.arithmetic == Arithmetic::Fraction {
+                    if let Ok(value) = evaluate::<Fraction>(&expr, &scope) {
+                        let display = match &format {
+                            Some(format) => self.apply_format(Number::to_f64(value), format)?,
+                            None => self.format_fraction(&value),
+                        };
+                        return Ok(Solution { display, value: fraction_text(&value) });
+                    }
+                }
+                let value: f64 = evaluate(&expr, &scope)?;
+                self.real_solution(value, format.as_ref())
+            }
+            Statement::Equation { lhs, rhs, hint } => self.solve_equation(&lhs, &rhs, hint, current_row),
+        }
+    }
+
+    /// 求解只有一个未知数的方程
+    ///
+    /// 方程只有一个名称时不代入已求解的值, 这样之后的行仍然可以单独求解 `x`。
+    fn solve_equation(
+        &self,
+        lhs: &Expr,
+        rhs: &Expr,
+        hint: Option<RootHint>,
+        current_row: usize
+    ) -> Result<Solution, EvalError> {
+        let mut unknowns = lhs.names();
+        for name in rhs.names() {
+            if !unknowns.contains(&name) {
+                unknowns.push(name);
+            }
+        }
+        let solved = unknowns.len() > 1;
+        if solved {
+            unknowns.retain(|name| self.solved_value(name, current_row).is_none());
+        }
+        if unknowns.len() > 1 {
+            return Err(
+                EvalError::new(
+                    format!(
+                        "Several unknowns ({}), write one equation per unknown in other rows",
+                        unknowns.join(", ")
+                    )
+                )
+            );
+        }
+        let unknown = unknowns.first().map_or("x", String::as_str);
+
+        // 两边只解析一次, 求解时反复代入不同的值
+        let names = [unknown.to_string()];
+        let f = |x: f64| self.residual::<f64>(lhs, rhs, current_row, &names, &[x], solved);
+
+        let coefficients = polynomial_coefficients(f)?;
+        let is_polynomial = coefficients.is_some();
+        // 分数模式下线性方程精确求解, `3*x = 1` 得到 1/3
+        let exact_root = match &coefficients {
+            Some(coefficients) if coefficients.len() == 2 && self.arithmetic == Arithmetic::Fraction => {
+                let residual = |x: f64| self.residual::<Fraction>(lhs, rhs, current_row, &names, &[x], solved).ok();
+                residual(0.0)
+                    .zip(residual(1.0))
+                    .and_then(|(r0, r1)| {
+                        Fraction::binary(BinaryOp::Sub, r1, r0)
+                            .and_then(|slope| Fraction::binary(BinaryOp::Div, -r0, slope))
+                            .ok()
+                    })
+            }
+            _ => None,
+        };
+        // 复数模式下多项式方程同时给出非实数根
+        let mut nonreal_roots = Vec::new();
+        let roots = match coefficients {
+            Some(coefficients) => {
+                if coefficients.len() == 1 {
+                    return Err(
+                        EvalError::new(format!("Invalid equation: coefficient of {} is zero", unknown))
+                    );
+                }
+                if self.arithmetic == Arithmetic::Complex && matches!(hint, None | Some(RootHint::Index(_))) {
+                    nonreal_roots = complex_roots(&coefficients);
+                }
+                let roots = real_roots(&coefficients);
+                match hint {
+                    Some(RootHint::Interval(a, b)) => {
+                        let (a, b) = (a.min(b), a.max(b));
+                        roots
+                            .into_iter()
+                            .filter(|root| (a..=b).contains(root))
+                            .collect()
+                    }
+                    Some(RootHint::Guess(guess)) =>
+                        roots
+                            .into_iter()
+                            .min_by(|a, b| (a - guess).abs().total_cmp(&(b - guess).abs()))
+                            .into_iter()
+                            .collect(),
+                    _ => roots,
+                }
+            }
+            // 不是多项式时数值求解
+            None => {
+                let g = |x: f64| f(x).ok().filter(|value| value.is_finite());
+                match hint {
+                    Some(RootHint::Interval(a, b)) => roots_in_interval(&g, a, b),
+                    Some(RootHint::Guess(guess)) => root_near(&g, guess).into_iter().collect(),
+                    _ => root_near(&g, 0.0).into_iter().collect(),
+                }
+            }
+        };
+
+        if roots.is_empty() && nonreal_roots.is_empty() {
+            return Err(
+                EvalError::new(match hint {
+                    Some(RootHint::Interval(a, b)) => format!("No root found in {}..{}", a, b),
+                    _ if is_polynomial => "No real roots".to_string(),
+                    _ => "No convergence, try an interval such as `@ 0..10`".to_string(),
+                })
+            );
+        }
+        let root_index = match hint {
+            Some(RootHint::Index(index)) => Some(index),
+            _ => None,
+        };
+        if let (Some(root), [_], None | Some(1)) = (exact_root, roots.as_slice(), root_index) {
+            return Ok(Solution { display: self.format_fraction(&root), value: fraction_text(&root) });
+        }
+        let mut solutions: Vec<Solution> = roots
+            .iter()
+            .map(|root| self.number_solution(*root))
+            .collect();
+        for root in nonreal_roots {
+            solutions.push(self.complex_solution(root)?);
+        }
+        self.pick_root(solutions, root_index)
+    }
+
+    /// 方程左边减右边的值, `unknowns` 取 `values` 中的值
+    fn residual<N: Number>(
+        &self,
+        lhs: &Expr,
+        rhs: &Expr,
+        row: usize,
+        unknowns: &[String],
+        values: &[f64],
+        solved: bool
+    ) -> Result<N, EvalError> {
+        let scope = RowScope { engine: self, row, unknowns, values, solved };
+        N::binary(BinaryOp::Sub, evaluate(lhs, &scope)?, evaluate(rhs, &scope)?).map_err(EvalError::new)
+    }
+
+    /// 找出工作表中的线性方程组
+    ///
+    /// 连续的方程行中, 含有两个以上未知数的方程与共享未知数的方程组成一个方程组,
+    /// 空行或表达式行将方程组隔开。方程组结束后其未知数成为已知量, 之后的行可以直接引用。
+    fn find_systems(&self, inputs: &[String]) -> Vec<LinearSystem> {
+        let mut systems = Vec::new();
+        let mut solved: Vec<String> = Vec::new();
+        let mut run: Vec<(usize, Vec<String>)> = Vec::new();
+
+        for (row, input) in inputs.iter().enumerate() {
+            let unknowns = self.parse(input).ok().and_then(|statement| {
+                let mut unknowns = statement.unknowns();
+                // 只有一个未知数时单独求解, 否则已求解的名称视为已知量 (全部已求解时重新求解)
+                if unknowns.iter().any(|name| !solved.contains(name)) {
+                    unknowns.retain(|name| !solved.contains(name));
+                }
+                if unknowns.is_empty() { None } else { Some(unknowns) }
+            });
+            match unknowns {
+                Some(unknowns) => run.push((row, unknowns)),
+                None => {
+                    systems_in_run(&std::mem::take(&mut run), &mut systems, &mut solved, inputs);
+                }
+            }
+        }
+        systems_in_run(&run, &mut systems, &mut solved, inputs);
+        systems
+    }
+
+    /// 用高斯消元法求解方程组, 每个方程都必须是未知数的线性函数
+    fn solve_system(&self, system: &LinearSystem) -> Result<Vec<f64>, EvalError> {
+        let n = system.unknowns.len();
+        if system.rows.len() != n {
+            return Err(
+                EvalError::new(
+                    format!(
+                        "{} unknowns ({}) need {} equations, found {}",
+                        n,
+                        system.unknowns.join(", "),
+                        n,
+                        system.rows.len()
+                    )
+                )
+            );
+        }
+
+        let zero = vec![0.0; n];
+        let probe: Vec<f64> = (0..n).map(|j| 1.5 + 0.75 * (j as f64)).collect();
+        let mut matrix = Vec::with_capacity(n);
+        let mut constants = Vec::with_capacity(n);
+        for (row, input) in system.rows.iter().zip(&system.inputs) {
+            let (lhs, rhs) = match self.parse(input)? {
+                Statement::Equation { lhs, rhs, .. } => (lhs, rhs),
+                _ => {
+                    return Err(EvalError::new("Invalid equation."));
+                }
+            };
+            let residual = |values: &[f64]| {
+                self.residual::<f64>(&lhs, &rhs, *row, &system.unknowns, values, true)
+            };
+
+            let base = residual(&zero)?;
+            let mut coefficients = Vec::with_capacity(n);
+            for j in 0..n {
+                let mut unit = zero.clone();
+                unit[j] = 1.0;
+                coefficients.push(residual(&unit)? - base);
+            }
+
+            // 在另一点验证线性
+            let expected =
+                base +
+                coefficients
+                    .iter()
+                    .zip(&probe)
+                    .map(|(a, t)| a * t)
+                    .sum::<f64>();
+            let actual = residual(&probe)?;
+            let scale = 1.0 + expected.abs().max(actual.abs());
+            if !actual.is_finite() || (actual - expected).abs() > scale * 1e-9 {
+                return Err(
+                    EvalError::new(format!("Equations in {} are not linear", system.unknowns.join(", ")))
+                );
+            }
+            matrix.push(coefficients);
+            constants.push(-base);
+        }
+
+        solve_linear_system(matrix, constants).ok_or_else(|| {
+            EvalError::new("System has no unique solution")
+        })
+    }
+
+    /// 方程组中第 k 行显示并导出第 k 个未知数
+    fn system_solution(
+        &self,
+        system: &LinearSystem,
+        current_row: usize
+    ) -> Result<Solution, EvalError> {
+        let values = match &system.solution {
+            Some(solution) => solution.clone()?,
+            None => self.solve_system(system)?,
+        };
+        let index = system.rows
+            .iter()
+            .position(|row| *row == current_row)
+            .unwrap_or(0);
+        let solution = self.number_solution(values[index]);
+        Ok(Solution {
+            display: format!("{} = {}", system.unknowns[index], solution.display),
+            value: solution.value,
+        })
+    }
+
+    /// 该行之前已求解的方程组中名称的值
+    fn solved_value(&self, name: &str, current_row: usize) -> Option<f64> {
+        self.systems
+            .iter()
+            .filter(|system| system.rows.last().is_some_and(|last| *last < current_row))
+            .find_map(|system| {
+                let values = system.solution.as_ref()?.as_ref().ok()?;
+                let index = system.unknowns.iter().position(|unknown| unknown == name)?;
+                values.get(index).copied()
+            })
+    }
+
+    /// 显示方程的全部根并导出第 `root_index` 个 (从 1 开始, 默认最小的实根)
+    ///
+    /// 全部的根超出结果列宽度时只显示导出的根及其序号, 例如 `2 (1/3)`, 仍然超出时只显示导出的根。
+    fn pick_root(&self, mut roots: Vec<Solution>, root_index: Option<usize>) -> Result<Solution, EvalError> {
+        let index = root_index.unwrap_or(1);
+        if index == 0 || index > roots.len() {
+            let kind = if self.arithmetic == Arithmetic::Complex { "" } else { "real " };
+            return Err(
+                EvalError::new(
+                    format!("Root {} does not exist, the equation has {} {}roots", index, roots.len(), kind)
+                )
+            );
+        }
+
+        let display = roots
+            .iter()
+            .map(|root| root.display.as_str())
+            .collect::<Vec<_>>()
+            .join("; ");
+        let count = roots.len();
+        let root = roots.swap_remove(index - 1);
+        let display = if display_width(&display) <= self.max_result_len {
+            display
+        } else {
+            let numbered = format!("{} ({}/{})", root.display, index, count);
+            if display_width(&numbered) <= self.max_result_len { numbered } else { root.display }
+        };
+        Ok(Solution { display, value: root.value })
+    }
+}
+
+/// 行变量或求和区域中的文本是否为带单位的数值, 例如 `117.72 N`
+fn has_unit(text: &str) -> bool {
+    parse_quantity(text).is_some_and(|value| !value.is_dimensionless())
+}
+
+/// 去掉相对于模可以忽略的实部或虚部, 使 `(1i)^2` 显示为 -1
+fn clean_complex(value: Complex64) -> Complex64 {
+    let tolerance = value.norm() * 1e-12;
+    let clean = |part: f64| if part.abs() <= tolerance { 0.0 } else { part };
+    Complex64::new(clean(value.re), clean(value.im))
+}
+
+/// 两次找出的方程组是否相同 (行、未知数和输入都一致)
+fn same_systems(old: &[LinearSystem], new: &[LinearSystem]) -> bool {
+    old.len() == new.len() &&
+        old
+            .iter()
+            .zip(new)
+            .all(|(old, new)| old.rows == new.rows && old.unknowns == new.unknowns && old.inputs == new.inputs)
+}
+
+/// 将一段连续的方程行按共享的未知数分组, 含有多个未知数的组成为方程组
+fn systems_in_run(
+    run: &[(usize, Vec<String>)],
+    systems: &mut Vec<LinearSystem>,
+    solved: &mut Vec<String>,
+    inputs: &[String]
+) {
+    let mut groups: Vec<(Vec<usize>, Vec<String>)> = Vec::new();
+    for (row, unknowns) in run {
+        // 合并所有与当前行共享未知数的组
+        let mut group = (vec![*row], unknowns.clone());
+        let mut i = 0;
+        while i < groups.len() {
+            if groups[i].1.iter().any(|name| group.1.contains(name)) {
+                let (rows, names) = groups.remove(i);
+                group.0.extend(rows);
+                for name in names {
+                    if !group.1.contains(&name) {
+                        group.1.push(name);
+                    }
+                }
+            } else {
+                i += 1;
+            }
+        }
+        groups.push(group);
+    }
+
+    for (mut rows, unknowns) in groups {
+        let is_system = run
+            .iter()
+            .any(|(row, names)| rows.contains(row) && names.len() > 1);
+        if !is_system {
+            continue;
+        }
+        rows.sort_unstable();
+        // 未知数按行的顺序排列
+        let mut ordered: Vec<String> = Vec::new();
+        for (row, names) in run {
+            if rows.contains(row) {
+                for name in names {
+                    if unknowns.contains(name) && !ordered.contains(name) {
+                        ordered.push(name.clone());
+                    }
+                }
+            }
+        }
+        solved.extend(ordered.iter().cloned());
+        systems.push(LinearSystem {
+            inputs: rows
+                .iter()
+                .map(|row| inputs[*row].clone())
+                .collect(),
+            rows,
+            unknowns: ordered,
+            solution: None,
+        });
+    }
+}
+
+/// 提取结果列表中指定行的有效数值
+pub fn region_values(results: &[String], rows: Range<usize>) -> Vec<f64> {
+    results
+        .iter()
+        .take(rows.end)
+        .skip(rows.start)
+        .filter_map(|result| parse_number(result))
+        .collect()
+}
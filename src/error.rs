@@ -0,0 +1,47 @@
+use std::fmt;
+use std::ops::Range;
+
+/// 评估错误, `span` 为出错部分在输入中的字符位置 (从 0 开始, 不含结束位置)
+#[derive(Debug, Clone, PartialEq)]
+pub struct EvalError {
+    pub message: String,
+    pub span: Option<Range<usize>>,
+}
+
+impl EvalError {
+    pub fn new(message: impl Into<String>) -> Self {
+        Self { message: message.into(), span: None }
+    }
+
+    pub fn at(message: impl Into<String>, span: Range<usize>) -> Self {
+        Self { message: message.into(), span: Some(span) }
+    }
+
+    /// 出错的列号 (从 1 开始)
+    pub fn column(&self) -> Option<usize> {
+        self.span.as_ref().map(|span| span.start + 1)
+    }
+}
+
+impl fmt::Display for EvalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.column() {
+            Some(column) => write!(f, "{} (column {})", self.message, column),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+impl std::error::Error for EvalError {}
+
+impl From<String> for EvalError {
+    fn from(message: String) -> Self {
+        Self::new(message)
+    }
+}
+
+impl From<&str> for EvalError {
+    fn from(message: &str) -> Self {
+        Self::new(message)
+    }
+}
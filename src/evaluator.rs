@@ -0,0 +1,594 @@
+use crate::aggregate::Aggregate;
+use crate::dates::{ self, call_date, is_date_function, Calendar };
+use crate::error::EvalError;
+use crate::finance::{ self, call_finance, is_finance_function };
+use crate::stats::{ self, call_stats, is_paired, is_stats_function };
+use crate::format::parse_number;
+use crate::functions::UserFunction;
+use crate::integer::{ bitwise_f64, not_f64, WordSize };
+use crate::matrix::{ self, is_matrix_function };
+use crate::parser::{ BinaryOp, BitOp, Expr, ExprKind };
+use crate::units::Unit;
+
+/// 表达式求值使用的数值类型
+///
+/// 默认以 `f64` 计算, 十进制模式使用 [`rust_decimal::Decimal`], 分数模式使用 [`crate::fraction::Fraction`],
+/// 含有虚数的表达式使用 [`num_complex::Complex64`], 带单位的表达式使用 [`crate::units::Quantity`]。
+pub trait Number: Clone {
+    /// 由数值字面量的写法得到数值
+    fn literal(value: f64, digits: &str) -> Result<Self, String>;
+    /// 虚数字面量 `4i`, 只有复数支持
+    fn imaginary(_value: f64) -> Result<Self, String> {
+        Err(COMPLEX_ONLY.to_string())
+    }
+    /// 数值后的单位 `kg`, 只有带单位的数值支持
+    fn unit(_unit: &Unit) -> Result<Self, String> {
+        Err(UNITS_ONLY.to_string())
+    }
+    /// `A to km`
+    fn convert(self, _unit: &Unit) -> Result<Self, String> {
+        Err(UNITS_ONLY.to_string())
+    }
+    /// 解析行变量中保存的数值 (已去掉千位分隔符)
+    fn parse(text: &str) -> Option<Self>;
+    fn from_f64(value: f64) -> Option<Self>;
+    fn to_f64(self) -> f64;
+    fn binary(op: BinaryOp, lhs: Self, rhs: Self) -> Result<Self, String>;
+    /// 按位运算, 默认借助 64 位整数计算
+    fn bitwise(op: BitOp, lhs: Self, rhs: Self) -> Result<Self, String> {
+        let value = bitwise_f64(op, lhs.to_f64(), rhs.to_f64())?;
+        Self::from_f64(value).ok_or_else(|| "Bitwise result is out of range".to_string())
+    }
+    /// 按位取反 `~x`
+    fn not(self) -> Result<Self, String> {
+        Self::from_f64(not_f64(self.to_f64())?).ok_or_else(|| "Bitwise result is out of range".to_string())
+    }
+    /// 按程序员模式的整数宽度回绕, 只有整数需要
+    fn wrap(self, _word: WordSize) -> Self {
+        self
+    }
+    fn neg(self) -> Self;
+    /// `50%` 即 0.5
+    fn percent(self) -> Self;
+    /// 调用内置函数, 参数个数已在解析时检查
+    fn call(name: &str, args: &[Self]) -> Result<Self, String>;
+    /// 矩阵字面量 `[1, 2; 3, 4]`, 只有矩阵支持
+    fn matrix(_rows: Vec<Vec<Self>>) -> Result<Self, String> {
+        Err(MATRIX_ONLY.to_string())
+    }
+    /// `det`、`inverse` 等矩阵函数, 只有矩阵支持
+    fn call_matrix(_name: &str, _args: &[Self]) -> Result<Self, String> {
+        Err(MATRIX_ONLY.to_string())
+    }
+
+    /// 求和区域的聚合值, 默认借助浮点数计算
+    fn aggregate(aggregate: Aggregate, values: &[Self]) -> Option<Self> {
+        let values: Vec<f64> = values
+            .iter()
+            .map(|value| value.clone().to_f64())
+            .collect();
+        Self::from_f64(aggregate.apply(&values))
+    }
+
+    /// 调用财务和统计函数, 每个参数为一个数值或行区间中各行的数值, 默认借助浮点数计算
+    fn call_lists(name: &str, args: &[Vec<Self>]) -> Result<Self, String> {
+        let args: Vec<Vec<f64>> = args
+            .iter()
+            .map(|values| values.iter().map(|value| value.clone().to_f64()).collect())
+            .collect();
+        Self::from_f64(call_list_function(name, &args)?).ok_or_else(|| format!("`{}` is out of range", name))
+    }
+}
+
+const COMPLEX_ONLY: &str = "Complex numbers can only be used in expressions";
+const UNITS_ONLY: &str = "Units can only be used in real-valued expressions";
+const MATRIX_ONLY: &str = "Matrices can only be used in real-valued expressions";
+
+/// 浮点数除以零等得到无穷大或 NaN, 由调用方决定如何处理
+impl Number for f64 {
+    fn literal(value: f64, _digits: &str) -> Result<Self, String> {
+        Ok(value)
+    }
+
+    fn parse(text: &str) -> Option<Self> {
+        parse_number(text)
+    }
+
+    fn from_f64(value: f64) -> Option<Self> {
+        Some(value)
+    }
+
+    fn to_f64(self) -> f64 {
+        self
+    }
+
+    fn binary(op: BinaryOp, lhs: Self, rhs: Self) -> Result<Self, String> {
+        Ok(match op {
+            BinaryOp::Add => lhs + rhs,
+            BinaryOp::Sub => lhs - rhs,
+            BinaryOp::Mul => lhs * rhs,
+            BinaryOp::Div => lhs / rhs,
+            BinaryOp::Rem => lhs % rhs,
+            BinaryOp::Pow => lhs.powf(rhs),
+        })
+    }
+
+    fn neg(self) -> Self {
+        -self
+    }
+
+    fn percent(self) -> Self {
+        self / 100.0
+    }
+
+    fn call(name: &str, args: &[Self]) -> Result<Self, String> {
+        if name == "polar" {
+            return Err(COMPLEX_ONLY.to_string());
+        }
+        Ok(call_function(name, args))
+    }
+}
+
+/// 表达式中名称的取值来源
+pub trait Scope<N> {
+    /// 行变量的值
+    fn row(&self, index: usize) -> Result<N, String>;
+    /// 聚合函数参数中的行, 空行、出错或不是数值的行返回 None, 与求和区域一致
+    fn optional_row(&self, index: usize) -> Result<Option<N>, String>;
+    /// 未知数或已求解变量的值, 未定义时返回 None
+    fn name(&self, name: &str) -> Option<N>;
+    /// 求和区域的聚合值
+    fn aggregate(&self, aggregate: Aggregate) -> Result<N, String>;
+    /// `today()` 使用的时区以及 `workdays` 跳过的节假日
+    fn calendar(&self) -> &Calendar;
+    /// 程序员模式的整数宽度
+    fn word_size(&self) -> WordSize;
+    /// [functions] 中定义的函数
+    fn function(&self, _name: &str) -> Option<&UserFunction> {
+        None
+    }
+}
+
+/// 用户函数体的求值范围, 参数按值绑定, 其余都交给调用处的范围
+struct FunctionScope<'a, N> {
+    parent: &'a dyn Scope<N>,
+    function: &'a UserFunction,
+    values: Vec<N>,
+}
+
+impl<N: Number> Scope<N> for FunctionScope<'_, N> {
+    fn row(&self, index: usize) -> Result<N, String> {
+        self.parent.row(index)
+    }
+
+    fn optional_row(&self, index: usize) -> Result<Option<N>, String> {
+        self.parent.optional_row(index)
+    }
+
+    fn name(&self, name: &str) -> Option<N> {
+        match self.function.params.iter().position(|param| param == name) {
+            Some(index) => Some(self.values[index].clone()),
+            None => self.parent.name(name),
+        }
+    }
+
+    fn aggregate(&self, aggregate: Aggregate) -> Result<N, String> {
+        self.parent.aggregate(aggregate)
+    }
+
+    fn calendar(&self) -> &Calendar {
+        self.parent.calendar()
+    }
+
+    fn word_size(&self) -> WordSize {
+        self.parent.word_size()
+    }
+
+    fn function(&self, name: &str) -> Option<&UserFunction> {
+        self.parent.function(name)
+    }
+}
+
+/// 内置函数及其参数个数范围
+const FUNCTIONS: [(&str, usize, usize); 32] = [
+    ("sin", 1, 1),
+    ("cos", 1, 1),
+    ("tan", 1, 1),
+    ("asin", 1, 1),
+    ("acos", 1, 1),
+    ("atan", 1, 1),
+    ("sinh", 1, 1),
+    ("cosh", 1, 1),
+    ("tanh", 1, 1),
+    ("asinh", 1, 1),
+    ("acosh", 1, 1),
+    ("atanh", 1, 1),
+    ("ln", 1, 1),
+    // `log(x)` 为常用对数, `log(x, b)` 以 b 为底
+    ("log", 1, 2),
+    ("log2", 1, 1),
+    ("log10", 1, 1),
+    ("exp", 1, 1),
+    ("exp2", 1, 1),
+    ("sqrt", 1, 1),
+    ("cbrt", 1, 1),
+    ("abs", 1, 1),
+    ("floor", 1, 1),
+    ("ceil", 1, 1),
+    ("round", 1, 1),
+    ("hypot", 2, 2),
+    ("atan2", 2, 2),
+    ("pow", 2, 2),
+    // 复数的辐角 (度)、实部、虚部、共轭以及 `polar(5, 53.13)` 即 `5∠53.13`
+    ("arg", 1, 1),
+    ("re", 1, 1),
+    ("im", 1, 1),
+    ("conj", 1, 1),
+    ("polar", 2, 2),
+];
+
+/// 聚合函数之外可以用行区间 `A:F` 作为参数的函数
+const RANGE_FUNCTIONS: [&str; 5] = ["sumproduct", "npv", "xnpv", "irr", "xirr"];
+
+/// 聚合函数 `sum(A:E)`、`avg(B, D, F)`, 名称与求和区域的聚合值相同, 也接受 `average` 等别名
+pub fn aggregate_function(name: &str) -> Option<Aggregate> {
+    if name == "z" {
+        return None;
+    }
+    Aggregate::from_name(name)
+}
+
+/// 函数的参数个数范围, 不是内置函数时返回 None
+pub fn function_arity(name: &str) -> Option<(usize, usize)> {
+    if aggregate_function(name).is_some() || name == "sumproduct" {
+        return Some((1, usize::MAX));
+    }
+    FUNCTIONS.iter()
+        .chain(finance::FUNCTIONS.iter())
+        .chain(stats::FUNCTIONS.iter())
+        .chain(dates::FUNCTIONS.iter())
+        .chain(matrix::FUNCTIONS.iter())
+        .find(|(function, _, _)| *function == name)
+        .map(|(_, min, max)| (*min, *max))
+}
+
+/// 函数是否接受行区间参数
+pub fn accepts_range(name: &str) -> bool {
+    aggregate_function(name).is_some() || is_stats_function(name) || RANGE_FUNCTIONS.contains(&name)
+}
+
+/// 以浮点数调用财务或统计函数
+pub fn call_list_function(name: &str, args: &[Vec<f64>]) -> Result<f64, String> {
+    if is_stats_function(name) { call_stats(name, args) } else { call_finance(name, args) }
+}
+
+/// 计算表达式, 出错时返回出错节点的位置
+pub fn evaluate<N: Number>(expr: &Expr, scope: &dyn Scope<N>) -> Result<N, EvalError> {
+    let at = |message: String| EvalError::at(message, expr.span.clone());
+    let value = match &expr.kind {
+        ExprKind::Number(value, digits) => N::literal(*value, digits).map_err(at)?,
+        ExprKind::Imaginary(value) => N::imaginary(*value).map_err(at)?,
+        ExprKind::Unit(unit) => N::unit(unit).map_err(at)?,
+        ExprKind::Date(value) | ExprKind::Duration(value) => from_f64(*value).map_err(at)?,
+        ExprKind::Convert(operand, unit) => evaluate(operand, scope)?.convert(unit).map_err(at)?,
+        ExprKind::Row(index) => scope.row(*index).map_err(at)?,
+        ExprKind::Name(name) => {
+            match scope.name(name) {
+                Some(value) => value,
+                None => {
+                    return Err(at(format!("Unknown variable `{}`", name)));
+                }
+            }
+        }
+        ExprKind::Aggregate(aggregate) => scope.aggregate(*aggregate).map_err(at)?,
+        ExprKind::Percent(operand) => evaluate(operand, scope)?.percent(),
+        ExprKind::Neg(operand) => evaluate(operand, scope)?.neg(),
+        ExprKind::Binary(op, lhs, rhs) => {
+            let (lhs, rhs) = (evaluate(lhs, scope)?, evaluate(rhs, scope)?);
+            N::binary(*op, lhs, rhs).map_err(at)?
+        }
+        ExprKind::Bitwise(op, lhs, rhs) => {
+            let (lhs, rhs) = (evaluate(lhs, scope)?, evaluate(rhs, scope)?);
+            N::bitwise(*op, lhs, rhs).map_err(at)?
+        }
+        ExprKind::BitNot(operand) => evaluate(operand, scope)?.not().map_err(at)?,
+        ExprKind::Call(name, args) if is_finance_function(name) => {
+            let args = args
+                .iter()
+                .map(|arg| evaluate_argument(arg, scope))
+                .collect::<Result<Vec<_>, _>>()?;
+            N::call_lists(name, &args).map_err(at)?
+        }
+        ExprKind::Call(name, args) if is_stats_function(name) => {
+            let columns = args
+                .iter()
+                .map(|arg| evaluate_cells(arg, scope))
+                .collect::<Result<Vec<_>, _>>()?;
+            let args = if is_paired(name) {
+                paired_cells(name, columns).map_err(at)?
+            } else {
+                columns
+                    .into_iter()
+                    .map(|column| column.into_iter().flatten().collect())
+                    .collect()
+            };
+            N::call_lists(name, &args).map_err(at)?
+        }
+        ExprKind::Call(name, args) if is_date_function(name) => {
+            let args = args
+                .iter()
+                .map(|arg| evaluate(arg, scope).map(N::to_f64))
+                .collect::<Result<Vec<_>, _>>()?;
+            from_f64(call_date(name, &args, scope.calendar()).map_err(&at)?).map_err(at)?
+        }
+        ExprKind::Call(name, args) if is_matrix_function(name) => {
+            let args = args
+                .iter()
+                .map(|arg| evaluate(arg, scope))
+                .collect::<Result<Vec<_>, _>>()?;
+            N::call_matrix(name, &args).map_err(at)?
+        }
+        ExprKind::Call(name, args) if name == "sumproduct" => sum_product(expr, args, scope)?,
+        ExprKind::Call(name, args) => {
+            match aggregate_function(name) {
+                Some(aggregate) => {
+                    let mut values = Vec::new();
+                    for arg in args {
+                        values.extend(evaluate_cells(arg, scope)?.into_iter().flatten());
+                    }
+                    N::aggregate(aggregate, &values).ok_or_else(|| {
+                        at(format!("`{}` is undefined for these values", name))
+                    })?
+                }
+                None => {
+                    let args = args
+                        .iter()
+                        .map(|arg| evaluate(arg, scope))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    match scope.function(name) {
+                        Some(function) => call_user_function(function, args, scope).map_err(|err| at(err.message))?,
+                        None => N::call(name, &args).map_err(at)?,
+                    }
+                }
+            }
+        }
+        ExprKind::Matrix(rows) => {
+            let rows = rows
+                .iter()
+                .map(|row| row.iter().map(|element| evaluate(element, scope)).collect::<Result<Vec<_>, _>>())
+                .collect::<Result<Vec<_>, _>>()?;
+            N::matrix(rows).map_err(at)?
+        }
+        ExprKind::Range(_) => {
+            return Err(at("Row ranges can only be used as function arguments".to_string()));
+        }
+    };
+    Ok(value.wrap(scope.word_size()))
+}
+
+/// 调用用户函数, 函数体中的错误附带函数名
+fn call_user_function<N: Number>(function: &UserFunction, values: Vec<N>, scope: &dyn Scope<N>) -> Result<N, EvalError> {
+    let local = FunctionScope { parent: scope, function, values };
+    evaluate(&function.body, &local).map_err(|err| EvalError::new(format!("{} in `{}`", err.message, function.name)))
+}
+
+/// 日期等以浮点数表示的值转换为计算使用的数值类型
+fn from_f64<N: Number>(value: f64) -> Result<N, String> {
+    N::from_f64(value).ok_or_else(|| format!("{} cannot be represented in this mode", value))
+}
+
+/// 计算函数参数, 行区间得到其中各行的值
+fn evaluate_argument<N: Number>(expr: &Expr, scope: &dyn Scope<N>) -> Result<Vec<N>, EvalError> {
+    match &expr.kind {
+        ExprKind::Range(rows) =>
+            rows
+                .clone()
+                .map(|index| scope.row(index).map_err(|message| EvalError::at(message, expr.span.clone())))
+                .collect(),
+        _ => Ok(vec![evaluate(expr, scope)?]),
+    }
+}
+
+/// 计算聚合函数的参数, 引用的行为空或出错时得到 None, 其他表达式出错时返回错误
+fn evaluate_cells<N: Number>(expr: &Expr, scope: &dyn Scope<N>) -> Result<Vec<Option<N>>, EvalError> {
+    let at = |message: String| EvalError::at(message, expr.span.clone());
+    match &expr.kind {
+        ExprKind::Range(rows) =>
+            rows
+                .clone()
+                .map(|index| scope.optional_row(index).map_err(at))
+                .collect(),
+        ExprKind::Row(index) => Ok(vec![scope.optional_row(*index).map_err(at)?]),
+        _ => Ok(vec![Some(evaluate(expr, scope)?)]),
+    }
+}
+
+/// 按行对应的多个区间, 长度必须相同, 其中一行为空或出错时跳过这一组
+fn paired_cells<N: Number>(name: &str, columns: Vec<Vec<Option<N>>>) -> Result<Vec<Vec<N>>, String> {
+    let len = columns[0].len();
+    if columns.iter().any(|column| column.len() != len) {
+        return Err(format!("`{}` needs ranges of the same length", name));
+    }
+    let mut paired = vec![Vec::new(); columns.len()];
+    for index in 0..len {
+        if columns.iter().all(|column| column[index].is_some()) {
+            for (values, column) in paired.iter_mut().zip(&columns) {
+                values.extend(column[index].clone());
+            }
+        }
+    }
+    Ok(paired)
+}
+
+/// `sumproduct(A:C, D:F)`: 各参数对应行的乘积之和
+fn sum_product<N: Number>(expr: &Expr, args: &[Expr], scope: &dyn Scope<N>) -> Result<N, EvalError> {
+    let at = |message: String| EvalError::at(message, expr.span.clone());
+    let columns = args
+        .iter()
+        .map(|arg| evaluate_cells(arg, scope))
+        .collect::<Result<Vec<_>, _>>()?;
+    let columns = paired_cells("sumproduct", columns).map_err(at)?;
+    let mut total: Option<N> = None;
+    for index in 0..columns[0].len() {
+        let mut product = columns[0][index].clone();
+        for column in &columns[1..] {
+            product = N::binary(BinaryOp::Mul, product, column[index].clone()).map_err(at)?;
+        }
+        total = Some(match total {
+            Some(total) => N::binary(BinaryOp::Add, total, product).map_err(at)?,
+            None => product,
+        });
+    }
+    match total {
+        Some(total) => Ok(total),
+        None => N::from_f64(0.0).ok_or_else(|| at("`sumproduct` is out of range".to_string())),
+    }
+}
+
+/// 以浮点数调用内置函数, 参数个数已在解析时检查
+pub fn call_function(name: &str, args: &[f64]) -> f64 {
+    let x = args[0];
+    match name {
+        "sin" => x.sin(),
+        "cos" => x.cos(),
+        "tan" => x.tan(),
+        "asin" => x.asin(),
+        "acos" => x.acos(),
+        "atan" => x.atan(),
+        "sinh" => x.sinh(),
+        "cosh" => x.cosh(),
+        "tanh" => x.tanh(),
+        "asinh" => x.asinh(),
+        "acosh" => x.acosh(),
+        "atanh" => x.atanh(),
+        "ln" => x.ln(),
+        "log" => {
+            match args.get(1) {
+                Some(base) => x.log(*base),
+                None => x.log10(),
+            }
+        }
+        "log2" => x.log2(),
+        "log10" => x.log10(),
+        "exp" => x.exp(),
+        "exp2" => x.exp2(),
+        "sqrt" => x.sqrt(),
+        "cbrt" => x.cbrt(),
+        "abs" => x.abs(),
+        "floor" => x.floor(),
+        "ceil" => x.ceil(),
+        "round" => x.round(),
+        "min" => args.iter().copied().fold(f64::INFINITY, f64::min),
+        "max" => args.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+        "hypot" => x.hypot(args[1]),
+        "atan2" => x.atan2(args[1]),
+        "pow" => x.powf(args[1]),
+        "arg" => if x < 0.0 { 180.0 } else { 0.0 },
+        "re" | "conj" => x,
+        "im" => 0.0,
+        _ => f64::NAN,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::row_label;
+    use crate::parser::{ parse, Statement };
+
+    /// 只有行变量的求值范围, 未定义的行为空行
+    struct TestScope {
+        rows: Vec<Option<f64>>,
+        calendar: Calendar,
+    }
+
+    impl TestScope {
+        fn new(rows: &[Option<f64>]) -> Self {
+            Self { rows: rows.to_vec(), calendar: Calendar::default() }
+        }
+    }
+
+    impl Scope<f64> for TestScope {
+        fn row(&self, index: usize) -> Result<f64, String> {
+            match self.rows.get(index).copied().flatten() {
+                Some(value) => Ok(value),
+                None => Err(format!("Row {} is empty", row_label(index))),
+            }
+        }
+
+        fn optional_row(&self, index: usize) -> Result<Option<f64>, String> {
+            Ok(self.rows.get(index).copied().flatten())
+        }
+
+        fn name(&self, _name: &str) -> Option<f64> {
+            None
+        }
+
+        fn aggregate(&self, aggregate: Aggregate) -> Result<f64, String> {
+            Err(format!("`{}` is not available", aggregate.name()))
+        }
+
+        fn calendar(&self) -> &Calendar {
+            &self.calendar
+        }
+
+        fn word_size(&self) -> WordSize {
+            WordSize::default()
+        }
+    }
+
+    fn eval_in(input: &str, scope: &TestScope) -> Result<f64, EvalError> {
+        match parse(input)? {
+            Statement::Expression(expr, _) => evaluate(&expr, scope),
+            other => panic!("{} is not an expression: {:?}", input, other),
+        }
+    }
+
+    fn eval(input: &str) -> f64 {
+        eval_in(input, &TestScope::new(&[])).unwrap()
+    }
+
+    #[test]
+    fn precedence() {
+        assert_eq!(eval("1 + 2 * 3"), 7.0);
+        assert_eq!(eval("(1 + 2) * 3"), 9.0);
+        assert_eq!(eval("-2^2"), -4.0);
+        assert_eq!(eval("2^3^2"), 512.0);
+        assert_eq!(eval("10 - 4 - 3"), 3.0);
+    }
+
+    #[test]
+    fn percent_and_remainder() {
+        assert_eq!(eval("50% * 200"), 100.0);
+        assert_eq!(eval("200 * 15%"), 30.0);
+        assert_eq!(eval("10 % 3"), 1.0);
+    }
+
+    #[test]
+    fn rows_and_functions() {
+        let scope = TestScope::new(&[Some(2.0), Some(5.0), None, Some(1.0)]);
+        assert_eq!(eval_in("a * 2 + B", &scope).unwrap(), 9.0);
+        assert_eq!(eval_in("max(A, b, 3)", &scope).unwrap(), 5.0);
+        // 区间参数跳过空行
+        assert_eq!(eval_in("min(A:D)", &scope).unwrap(), 1.0);
+        assert_eq!(eval("log(8, 2)"), 3.0);
+    }
+
+    #[test]
+    fn error_columns() {
+        let scope = TestScope::new(&[Some(2.0)]);
+        let error = eval_in("A + C", &scope).unwrap_err();
+        assert_eq!(error.message, "Row C is empty");
+        assert_eq!(error.column(), Some(5));
+
+        let error = eval_in("1 + 2 * x", &scope).unwrap_err();
+        assert_eq!(error.message, "Unknown variable `x`");
+        assert_eq!(error.column(), Some(9));
+    }
+
+    #[test]
+    fn date_arithmetic() {
+        assert_eq!(eval("2026-10-17 - 2026-10-01"), 16.0);
+        assert_eq!(eval("2026-10-17T18:00 - 2026-10-17"), 0.75);
+    }
+}
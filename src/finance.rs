@@ -0,0 +1,243 @@
+use crate::solver::root_near;
+
+/// 财务函数及其参数个数范围, 参数顺序与电子表格相同
+///
+/// `type` 为 0 表示期末付款, 为 1 表示期初付款; 现金流出为负数, 流入为正数。
+pub const FUNCTIONS: [(&str, usize, usize); 11] = [
+    // pmt(rate, nper, pv, [fv], [type])
+    ("pmt", 3, 5),
+    // ipmt(rate, per, nper, pv, [fv], [type])
+    ("ipmt", 4, 6),
+    ("ppmt", 4, 6),
+    // fv(rate, nper, pmt, [pv], [type])
+    ("fv", 3, 5),
+    // pv(rate, nper, pmt, [fv], [type])
+    ("pv", 3, 5),
+    // nper(rate, pmt, pv, [fv], [type])
+    ("nper", 3, 5),
+    // rate(nper, pmt, pv, [fv], [type], [guess])
+    ("rate", 3, 6),
+    // npv(rate, value1, value2, ...), 第一笔现金流在第一期末
+    ("npv", 2, usize::MAX),
+    // xnpv(rate, values, dates), 日期为天数
+    ("xnpv", 3, 3),
+    // irr(values, [guess])
+    ("irr", 1, 2),
+    // xirr(values, dates, [guess])
+    ("xirr", 2, 3),
+];
+
+/// 是否为财务函数
+pub fn is_finance_function(name: &str) -> bool {
+    FUNCTIONS.iter().any(|(function, _, _)| *function == name)
+}
+
+/// 调用财务函数, 每个参数为一个数值或行区间 `A:F` 中各行的数值
+pub fn call_finance(name: &str, args: &[Vec<f64>]) -> Result<f64, String> {
+    if name == "npv" {
+        let values: Vec<f64> = args[1..].concat();
+        return Ok(npv(scalar(name, args, 0, 0.0)?, &values));
+    }
+    if matches!(name, "xnpv" | "irr" | "xirr") {
+        return cash_flows(name, args);
+    }
+
+    let arg = |index: usize, default: f64| scalar(name, args, index, default);
+    let value = match name {
+        "pmt" => pmt(arg(0, 0.0)?, arg(1, 0.0)?, arg(2, 0.0)?, arg(3, 0.0)?, payment_type(arg(4, 0.0)?)),
+        "ipmt" | "ppmt" => {
+            let (rate, per, nper) = (arg(0, 0.0)?, arg(1, 0.0)?, arg(2, 0.0)?);
+            if per < 1.0 || per > nper {
+                return Err(format!("`{}` period must be between 1 and {}", name, nper));
+            }
+            let (pv, fv, kind) = (arg(3, 0.0)?, arg(4, 0.0)?, payment_type(arg(5, 0.0)?));
+            let interest = ipmt(rate, per, nper, pv, fv, kind);
+            if name == "ipmt" { interest } else { pmt(rate, nper, pv, fv, kind) - interest }
+        }
+        "fv" => fv(arg(0, 0.0)?, arg(1, 0.0)?, arg(2, 0.0)?, arg(3, 0.0)?, payment_type(arg(4, 0.0)?)),
+        "pv" => pv(arg(0, 0.0)?, arg(1, 0.0)?, arg(2, 0.0)?, arg(3, 0.0)?, payment_type(arg(4, 0.0)?)),
+        "nper" => nper(arg(0, 0.0)?, arg(1, 0.0)?, arg(2, 0.0)?, arg(3, 0.0)?, payment_type(arg(4, 0.0)?)),
+        "rate" => {
+            let (periods, payment, present) = (arg(0, 0.0)?, arg(1, 0.0)?, arg(2, 0.0)?);
+            let (future, kind, guess) = (arg(3, 0.0)?, payment_type(arg(4, 0.0)?), arg(5, 0.1)?);
+            // 没有任何现金流或期数为零且现值与终值抵消时, 任何利率都满足方程
+            if (payment == 0.0 && present == 0.0 && future == 0.0) || (periods == 0.0 && present + future == 0.0) {
+                return Err("`rate` is undefined for these arguments".to_string());
+            }
+            let f = |rate: f64| {
+                if rate <= -1.0 {
+                    return None;
+                }
+                Some(fv(rate, periods, payment, present, kind) - future)
+            };
+            root_near(&f, guess).ok_or_else(|| "`rate` did not converge, try another guess".to_string())?
+        }
+        _ => {
+            return Err(format!("Unknown function `{}`", name));
+        }
+    };
+    if value.is_finite() { Ok(value) } else { Err(format!("`{}` is undefined for these arguments", name)) }
+}
+
+/// 第 index 个参数, 省略时取默认值, 不能是多行的区间
+fn scalar(name: &str, args: &[Vec<f64>], index: usize, default: f64) -> Result<f64, String> {
+    match args.get(index).map(Vec::as_slice) {
+        None => Ok(default),
+        Some([value]) => Ok(*value),
+        Some(_) => Err(format!("`{}` argument {} must be a single value", name, index + 1)),
+    }
+}
+
+/// 非零的 `type` 都按期初付款处理
+fn payment_type(value: f64) -> f64 {
+    if value == 0.0 { 0.0 } else { 1.0 }
+}
+
+/// XNPV、IRR、XIRR: 现金流及日期各为一个区间
+fn cash_flows(name: &str, args: &[Vec<f64>]) -> Result<f64, String> {
+    let (values, dates, guess) = match name {
+        "xnpv" => (&args[1], Some(&args[2]), None),
+        "irr" => (&args[0], None, Some(scalar(name, args, 1, 0.1)?)),
+        _ => (&args[0], Some(&args[1]), Some(scalar(name, args, 2, 0.1)?)),
+    };
+    if let Some(dates) = dates {
+        if dates.len() != values.len() {
+            return Err(format!("`{}` needs one date for each of the {} cash flows, found {}", name, values.len(), dates.len()));
+        }
+    }
+    let times: Vec<f64> = match dates {
+        Some(dates) => dates.iter().map(|date| (date - dates[0]) / 365.0).collect(),
+        None => (0..values.len()).map(|period| period as f64).collect(),
+    };
+    let present_value = |rate: f64| -> Option<f64> {
+        if rate <= -1.0 {
+            return None;
+        }
+        Some(
+            values
+                .iter()
+                .zip(&times)
+                .map(|(value, time)| value / (1.0 + rate).powf(*time))
+                .sum()
+        )
+    };
+
+    let guess = match guess {
+        Some(guess) => guess,
+        None => {
+            return present_value(scalar(name, args, 0, 0.0)?).ok_or_else(|| format!("`{}` rate must be greater than -1", name));
+        }
+    };
+    if !values.iter().any(|value| *value > 0.0) || !values.iter().any(|value| *value < 0.0) {
+        return Err(format!("`{}` needs at least one positive and one negative cash flow", name));
+    }
+    root_near(&present_value, guess).ok_or_else(|| format!("`{}` did not converge, try another guess", name))
+}
+
+/// 各期现金流的净现值, 第一笔在第一期末
+fn npv(rate: f64, values: &[f64]) -> f64 {
+    values
+        .iter()
+        .enumerate()
+        .map(|(period, value)| value / (1.0 + rate).powi((period as i32) + 1))
+        .sum()
+}
+
+/// 年金终值系数, 利率为零时为期数
+fn annuity_factor(rate: f64, nper: f64) -> f64 {
+    if rate == 0.0 { nper } else { ((1.0 + rate).powf(nper) - 1.0) / rate }
+}
+
+fn fv(rate: f64, nper: f64, pmt: f64, pv: f64, kind: f64) -> f64 {
+    -(pv * (1.0 + rate).powf(nper) + pmt * (1.0 + rate * kind) * annuity_factor(rate, nper))
+}
+
+fn pv(rate: f64, nper: f64, pmt: f64, fv: f64, kind: f64) -> f64 {
+    -(fv + pmt * (1.0 + rate * kind) * annuity_factor(rate, nper)) / (1.0 + rate).powf(nper)
+}
+
+fn pmt(rate: f64, nper: f64, pv: f64, fv: f64, kind: f64) -> f64 {
+    -(pv * (1.0 + rate).powf(nper) + fv) / ((1.0 + rate * kind) * annuity_factor(rate, nper))
+}
+
+fn nper(rate: f64, pmt: f64, pv: f64, fv: f64, kind: f64) -> f64 {
+    if rate == 0.0 {
+        return -(pv + fv) / pmt;
+    }
+    let payment = pmt * (1.0 + rate * kind);
+    ((payment - fv * rate) / (payment + pv * rate)).ln() / (1.0 + rate).ln()
+}
+
+/// 第 per 期付款中的利息部分
+fn ipmt(rate: f64, per: f64, nper: f64, pv: f64, fv: f64, kind: f64) -> f64 {
+    let payment = pmt(rate, nper, pv, fv, kind);
+    let balance = if per == 1.0 {
+        if kind == 1.0 { 0.0 } else { -pv }
+    } else if kind == 1.0 {
+        self::fv(rate, per - 2.0, payment, pv, 1.0) - payment
+    } else {
+        self::fv(rate, per - 1.0, payment, pv, 0.0)
+    };
+    balance * rate
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call(name: &str, args: &[f64]) -> Result<f64, String> {
+        let args: Vec<Vec<f64>> = args
+            .iter()
+            .map(|arg| vec![*arg])
+            .collect();
+        call_finance(name, &args)
+    }
+
+    fn assert_close(actual: f64, expected: f64, tolerance: f64) {
+        assert!((actual - expected).abs() <= tolerance, "{} != {}", actual, expected);
+    }
+
+    // 参考值取自电子表格函数文档中的示例
+
+    #[test]
+    fn annuities() {
+        assert_close(call("pmt", &[0.08 / 12.0, 10.0, 10000.0]).unwrap(), -1037.03, 0.005);
+        assert_close(call("pmt", &[0.08 / 12.0, 10.0, 10000.0, 0.0, 1.0]).unwrap(), -1030.16, 0.005);
+        assert_close(call("fv", &[0.06 / 12.0, 10.0, -200.0, -500.0, 1.0]).unwrap(), 2581.40, 0.005);
+        assert_close(call("pv", &[0.08 / 12.0, 240.0, 500.0]).unwrap(), -59777.15, 0.005);
+        assert_close(call("nper", &[0.01, -100.0, -1000.0, 10000.0, 1.0]).unwrap(), 59.6738657, 1e-6);
+        assert_close(call("nper", &[0.01, -100.0, -1000.0]).unwrap(), -9.57859404, 1e-6);
+    }
+
+    #[test]
+    fn interest_and_principal() {
+        assert_close(call("ipmt", &[0.1 / 12.0, 1.0, 36.0, 8000.0]).unwrap(), -66.67, 0.005);
+        assert_close(call("ipmt", &[0.1, 3.0, 3.0, 8000.0]).unwrap(), -292.45, 0.005);
+        assert_close(call("ppmt", &[0.1 / 12.0, 1.0, 24.0, 2000.0]).unwrap(), -75.62, 0.005);
+        assert_close(call("ppmt", &[0.08, 10.0, 10.0, 200000.0]).unwrap(), -27598.05, 0.005);
+        assert!(call("ipmt", &[0.1, 4.0, 3.0, 8000.0]).is_err());
+    }
+
+    #[test]
+    fn rate() {
+        assert_close(call("rate", &[48.0, -200.0, 8000.0]).unwrap(), 0.00770147, 1e-8);
+        assert_eq!(call("rate", &[0.0, 0.0, 0.0]), Err("`rate` is undefined for these arguments".to_string()));
+        assert_eq!(call("rate", &[10.0, 0.0, 0.0]), Err("`rate` is undefined for these arguments".to_string()));
+        assert!(call("rate", &[10.0, 100.0, 800.0]).is_err());
+    }
+
+    #[test]
+    fn cash_flows() {
+        assert_close(call("npv", &[0.1, -10000.0, 3000.0, 4200.0, 6800.0]).unwrap(), 1188.44, 0.005);
+        let flows = vec![-70000.0, 12000.0, 15000.0, 18000.0, 21000.0, 26000.0];
+        assert_close(call_finance("irr", &[flows[..5].to_vec()]).unwrap(), -0.021245, 1e-6);
+        assert_close(call_finance("irr", &[flows]).unwrap(), 0.086630, 1e-6);
+
+        // 2008-01-01, 2008-03-01, 2008-10-30, 2009-02-15, 2009-04-01
+        let values = vec![-10000.0, 2750.0, 4250.0, 3250.0, 2750.0];
+        let dates = vec![39448.0, 39508.0, 39751.0, 39859.0, 39904.0];
+        assert_close(call_finance("xnpv", &[vec![0.09], values.clone(), dates.clone()]).unwrap(), 2086.65, 0.005);
+        assert_close(call_finance("xirr", &[values, dates]).unwrap(), 0.373362535, 1e-6);
+        assert!(call_finance("irr", &[vec![100.0, 200.0]]).is_err());
+    }
+}
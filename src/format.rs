@@ -0,0 +1,360 @@
+use rust_decimal::Decimal;
+
+/// 格式化数值, 在数值中添加千位分隔符以提高可读性
+pub fn format_with_thousands_separator(value: f64, decimal_places: usize) -> String {
+    // 判断是否为整数，若是整数则不保留小数位
+    if value.fract() == 0.0 {
+        // 如果是整数，直接返回整数部分, 超出 i64 范围的整数也按原值显示
+        let formatted_int = format!("{:.0}", if value == 0.0 { 0.0 } else { value });
+        return group_thousands(&formatted_int);
+    }
+
+    // 如果不是整数，根据 decimal_places 先将小数部分转为字符串
+    let formatted_value = format!("{:.1$}", value, decimal_places);
+    let parts: Vec<&str> = formatted_value.split('.').collect();
+    let int_part = parts[0];
+    let dec_part = parts.get(1).unwrap_or(&"");
+
+    // 移除小数部分末尾的多余零
+    let dec_part_trimmed = dec_part.trim_end_matches('0');
+
+    // 如果没有小数位，返回整数部分
+    if dec_part_trimmed.is_empty() {
+        return group_thousands(int_part);
+    }
+
+    // 如果有小数位，返回整数部分加上处理后的小数部分
+    format!("{}.{}", group_thousands(int_part), dec_part_trimmed)
+}
+
+/// 格式化十进制数, 去掉小数部分末尾的零并添加千位分隔符
+pub fn format_decimal(value: Decimal) -> String {
+    let text = value.normalize().to_string();
+    match text.split_once('.') {
+        Some((int_part, dec_part)) => format!("{}.{}", group_thousands(int_part), dec_part),
+        None => group_thousands(&text),
+    }
+}
+
+/// 解析行变量中保存的数值, 支持千位分隔符、分数模式导出的 `7/2` 以及 `1.23亿`
+pub fn parse_number(text: &str) -> Option<f64> {
+    let text = remove_thousands_separator(text);
+    let (text, exponent) = split_chinese_unit(&text);
+    let value = match text.split_once('/') {
+        Some((numerator, denominator)) => {
+            let numerator = numerator.trim().parse::<f64>().ok()?;
+            let denominator = denominator.trim().parse::<f64>().ok().filter(|d| *d != 0.0)?;
+            numerator / denominator
+        }
+        None => text.trim().parse::<f64>().ok()?,
+    };
+    Some(if exponent == 0 { value } else { value * 10_f64.powi(exponent) })
+}
+
+/// 去掉数值末尾的 `万` (10^4) 和 `亿` (10^8), 返回其余部分和对应的幂次, `2万亿` 为 10^12
+pub fn split_chinese_unit(text: &str) -> (&str, i32) {
+    let mut rest = text.trim_end();
+    let mut exponent = 0;
+    loop {
+        if let Some(prefix) = rest.strip_suffix('万') {
+            exponent += 4;
+            rest = prefix;
+        } else if let Some(prefix) = rest.strip_suffix('亿') {
+            exponent += 8;
+            rest = prefix;
+        } else {
+            return (rest, exponent);
+        }
+    }
+}
+
+/// 终端中的显示宽度, 中文等全角字符占两列
+pub fn display_width(text: &str) -> usize {
+    text.chars()
+        .map(|c| {
+            match c as u32 {
+                0x1100..=0x115f | 0x2e80..=0xa4cf | 0xac00..=0xd7a3 | 0xf900..=0xfaff | 0xfe30..=0xfe4f | 0xff00..=0xff60 |
+                0xffe0..=0xffe6 => 2,
+                _ => 1,
+            }
+        })
+        .sum()
+}
+
+/// 移除格式化数值中的千位分隔符以便进一步处理
+pub fn remove_thousands_separator(value: &str) -> String {
+    value.replace(",", "")
+}
+
+/// 每三位插入一个逗号, 保留前导负号
+pub fn group_thousands(int_part: &str) -> String {
+    let (sign, digits) = match int_part.strip_prefix('-') {
+        Some(digits) => ("-", digits),
+        None => ("", int_part),
+    };
+    let grouped = digits
+        .chars()
+        .rev()
+        .collect::<Vec<_>>()
+        .chunks(3)
+        .map(|chunk| chunk.iter().collect::<String>())
+        .collect::<Vec<_>>()
+        .join(",")
+        .chars()
+        .rev()
+        .collect::<String>();
+    format!("{}{}", sign, grouped)
+}
+
+/// 行末 `@` 之后的显示格式, 只影响显示, 行变量仍导出完整精度的值
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NumberFormat {
+    /// `@2`: 固定两位小数, 保留末尾的零
+    Fixed(usize),
+    /// `@sci`、`@sci3`: 科学计数法 `1.23e9`, 未指定位数时去掉末尾的零
+    Scientific(Option<usize>),
+    /// `@eng`、`@eng2`: 指数为 3 的倍数 `123.4e6`
+    Engineering(Option<usize>),
+    /// `@%`、`@%1`: 百分比 `12.50%`, 默认两位小数
+    Percent(usize),
+    /// `@hex`: 十六进制整数 `0xFF`
+    Hex,
+    /// `@oct`: 八进制整数 `0o17`
+    Octal,
+    /// `@bin`: 二进制整数 `0b1010`
+    Binary,
+    /// `@currency:CNY`: 货币符号和两位小数, 未指定币种时使用 [format] 中的 `currency`
+    Currency(Option<String>),
+    /// `@cn` 或 `@万`: 以万、亿为单位 `1.23亿`、`4,500万`
+    ChineseUnits(Option<usize>),
+    /// `@cnupper` 或 `@大写`: 大写金额 `壹万贰仟叁佰肆拾伍元陆角柒分`
+    ChineseUpper,
+}
+
+impl NumberFormat {
+    /// 解析 `@` 之后的格式指令, 例如 `2`、`sci`、`%1`、`currency:USD`
+    pub fn from_directive(text: &str) -> Option<NumberFormat> {
+        let text = text.trim().to_lowercase();
+        if let Some(code) = text.strip_prefix("currency") {
+            return match code.strip_prefix(':') {
+                Some(code) if !code.is_empty() && code.chars().all(|c| c.is_ascii_alphabetic()) => {
+                    Some(NumberFormat::Currency(Some(code.to_uppercase())))
+                }
+                Some(_) => None,
+                None if code.is_empty() => Some(NumberFormat::Currency(None)),
+                None => None,
+            };
+        }
+        let split = text.find(|c: char| c.is_ascii_digit()).unwrap_or(text.len());
+        let (name, digits) = text.split_at(split);
+        let places = if digits.is_empty() {
+            None
+        } else {
+            Some(digits.parse::<usize>().ok().filter(|places| *places <= 28)?)
+        };
+        match name {
+            "" => places.map(NumberFormat::Fixed),
+            "sci" | "scientific" => Some(NumberFormat::Scientific(places)),
+            "eng" | "engineering" => Some(NumberFormat::Engineering(places)),
+            "%" | "percent" => Some(NumberFormat::Percent(places.unwrap_or(2))),
+            "hex" if places.is_none() => Some(NumberFormat::Hex),
+            "oct" if places.is_none() => Some(NumberFormat::Octal),
+            "bin" if places.is_none() => Some(NumberFormat::Binary),
+            "cn" | "万" => Some(NumberFormat::ChineseUnits(places)),
+            "cnupper" | "大写" if places.is_none() => Some(NumberFormat::ChineseUpper),
+            _ => None,
+        }
+    }
+
+    /// 指令的写法, 用于错误信息
+    pub fn name(&self) -> String {
+        match self {
+            NumberFormat::Fixed(places) => format!("@{}", places),
+            NumberFormat::Scientific(_) => "@sci".to_string(),
+            NumberFormat::Engineering(_) => "@eng".to_string(),
+            NumberFormat::Percent(_) => "@%".to_string(),
+            NumberFormat::Hex => "@hex".to_string(),
+            NumberFormat::Octal => "@oct".to_string(),
+            NumberFormat::Binary => "@bin".to_string(),
+            NumberFormat::Currency(_) => "@currency".to_string(),
+            NumberFormat::ChineseUnits(_) => "@cn".to_string(),
+            NumberFormat::ChineseUpper => "@cnupper".to_string(),
+        }
+    }
+
+    /// 按格式显示数值, `decimal_places` 为未指定位数时的小数位数, `currency` 为默认币种
+    pub fn apply(&self, value: f64, decimal_places: usize, currency: &str) -> Result<String, String> {
+        Ok(match self {
+            NumberFormat::Fixed(places) => pad_decimals(&format_with_thousands_separator(value, *places), *places),
+            NumberFormat::Scientific(places) => format_scientific(value, *places, decimal_places),
+            NumberFormat::Engineering(places) => format_engineering(value, *places, decimal_places),
+            NumberFormat::Percent(places) =>
+                format!("{}%", pad_decimals(&format_with_thousands_separator(value * 100.0, *places), *places)),
+            NumberFormat::Hex | NumberFormat::Octal | NumberFormat::Binary => {
+                if value.fract() != 0.0 || value.abs() >= 9.2e18 {
+                    return Err(format!("`{}` needs an integer result", self.name()));
+                }
+                let sign = if value < 0.0 { "-" } else { "" };
+                let magnitude = value.abs() as i64;
+                match self {
+                    NumberFormat::Hex => format!("{}0x{:X}", sign, magnitude),
+                    NumberFormat::Octal => format!("{}0o{:o}", sign, magnitude),
+                    _ => format!("{}0b{:b}", sign, magnitude),
+                }
+            }
+            NumberFormat::Currency(code) => {
+                let code = code.as_deref().unwrap_or(currency);
+                let (symbol, places) = currency_symbol(code);
+                let sign = if value < 0.0 { "-" } else { "" };
+                let amount = pad_decimals(&format_with_thousands_separator(value.abs(), places), places);
+                format!("{}{}{}", sign, symbol, amount)
+            }
+            NumberFormat::ChineseUnits(places) => format_chinese_units(value, *places, decimal_places),
+            NumberFormat::ChineseUpper => format_chinese_upper(value)?,
+        })
+    }
+}
+
+/// 在小数部分末尾补零到 `places` 位
+fn pad_decimals(text: &str, places: usize) -> String {
+    if places == 0 {
+        return text.to_string();
+    }
+    let (int_part, dec_part) = text.split_once('.').unwrap_or((text, ""));
+    format!("{}.{:0<2$}", int_part, dec_part, places)
+}
+
+/// 去掉尾数中小数部分末尾的零, `1.2300e9` -> `1.23e9`
+fn trim_mantissa(mantissa: &str) -> &str {
+    if mantissa.contains('.') { mantissa.trim_end_matches('0').trim_end_matches('.') } else { mantissa }
+}
+
+fn format_scientific(value: f64, places: Option<usize>, decimal_places: usize) -> String {
+    let text = format!("{:.1$e}", value, places.unwrap_or(decimal_places));
+    match (places, text.split_once('e')) {
+        (None, Some((mantissa, exponent))) => format!("{}e{}", trim_mantissa(mantissa), exponent),
+        _ => text,
+    }
+}
+
+fn format_engineering(value: f64, places: Option<usize>, decimal_places: usize) -> String {
+    let digits = places.unwrap_or(decimal_places);
+    if value == 0.0 || !value.is_finite() {
+        return format_scientific(value, places, decimal_places);
+    }
+    let mut exponent = (value.abs().log10() / 3.0).floor() as i32 * 3;
+    let mut mantissa = format!("{:.1$}", value / 10_f64.powi(exponent), digits);
+    // 四舍五入后进位到 1000 时使用下一个指数
+    if mantissa.trim_start_matches('-').parse::<f64>().is_ok_and(|m| m >= 1000.0) {
+        exponent += 3;
+        mantissa = format!("{:.1$}", value / 10_f64.powi(exponent), digits);
+    }
+    let mantissa = if places.is_none() { trim_mantissa(&mantissa) } else { &mantissa };
+    format!("{}e{}", mantissa, exponent)
+}
+
+/// 币种的符号和小数位数, 不认识的币种以代码加空格作为前缀
+fn currency_symbol(code: &str) -> (String, usize) {
+    match code {
+        "CNY" | "RMB" => ("¥".to_string(), 2),
+        "JPY" => ("¥".to_string(), 0),
+        "USD" => ("$".to_string(), 2),
+        "EUR" => ("€".to_string(), 2),
+        "GBP" => ("£".to_string(), 2),
+        "KRW" => ("₩".to_string(), 0),
+        "INR" => ("₹".to_string(), 2),
+        "HKD" => ("HK$".to_string(), 2),
+        _ => (format!("{} ", code), 2),
+    }
+}
+
+/// 一万以上以万为单位, 一亿以上以亿为单位, 未指定位数时去掉末尾的零
+fn format_chinese_units(value: f64, places: Option<usize>, decimal_places: usize) -> String {
+    let digits = places.unwrap_or(decimal_places);
+    let format = |scaled: f64| {
+        let text = format_with_thousands_separator(scaled, digits);
+        if places.is_some() { pad_decimals(&text, digits) } else { text }
+    };
+    // 四舍五入后进位到一万时使用更大的单位
+    let rounds_to = |scaled: f64, limit: f64| parse_number(&format(scaled)).is_some_and(|rounded| rounded.abs() >= limit);
+    if value.abs() >= 1e8 || rounds_to(value / 1e4, 1e4) {
+        format!("{}亿", format(value / 1e8))
+    } else if value.abs() >= 1e4 || rounds_to(value, 1e4) {
+        format!("{}万", format(value / 1e4))
+    } else {
+        format(value)
+    }
+}
+
+const UPPER_DIGITS: [char; 10] = ['零', '壹', '贰', '叁', '肆', '伍', '陆', '柒', '捌', '玖'];
+
+/// 大写金额, 四舍五入到分, 整数金额以 `整` 结尾
+fn format_chinese_upper(value: f64) -> Result<String, String> {
+    let cents = (value.abs() * 100.0).round();
+    if cents >= 1e18 {
+        return Err("Amount is too large for `@cnupper`".to_string());
+    }
+    let cents = cents as u64;
+    let (yuan, jiao, fen) = (cents / 100, ((cents / 10) % 10) as usize, (cents % 10) as usize);
+    let mut text = String::from(if value < 0.0 && cents > 0 { "负" } else { "" });
+    if yuan > 0 {
+        text.push_str(&upper_integer(yuan));
+        text.push('元');
+    }
+    match (jiao, fen) {
+        (0, 0) => text.push_str(if yuan > 0 { "整" } else { "零元整" }),
+        (0, _) => {
+            if yuan > 0 {
+                text.push('零');
+            }
+            text.extend([UPPER_DIGITS[fen], '分']);
+        }
+        (_, 0) => text.extend([UPPER_DIGITS[jiao], '角']),
+        (_, _) => text.extend([UPPER_DIGITS[jiao], '角', UPPER_DIGITS[fen], '分']),
+    }
+    Ok(text)
+}
+
+/// 整数部分每四位一节, 节内为仟佰拾, 节之间为万、亿, 连续的零只读一个
+fn upper_integer(value: u64) -> String {
+    const PLACES: [&str; 4] = ["", "拾", "佰", "仟"];
+    const SECTIONS: [&str; 4] = ["", "万", "亿", "万"];
+    let mut sections = Vec::new();
+    let mut rest = value;
+    while rest > 0 {
+        sections.push((rest % 10000) as usize);
+        rest /= 10000;
+    }
+    let mut text = String::new();
+    let mut pending_zero = false;
+    for (index, section) in sections.iter().enumerate().rev() {
+        if *section == 0 {
+            // `壹万亿` 的亿节为零时仍需写出亿
+            if index == 2 && !text.is_empty() {
+                text.push('亿');
+            }
+            pending_zero = !text.is_empty();
+            continue;
+        }
+        if pending_zero || (!text.is_empty() && *section < 1000) {
+            text.push('零');
+        }
+        pending_zero = false;
+        let mut zero = false;
+        for place in (0..4).rev() {
+            let digit = (section / 10_usize.pow(place as u32)) % 10;
+            if digit == 0 {
+                zero = !text.is_empty();
+                continue;
+            }
+            if zero && place < 3 && !text.ends_with('零') {
+                text.push('零');
+            }
+            zero = false;
+            text.push(UPPER_DIGITS[digit]);
+            text.push_str(PLACES[place]);
+        }
+        text.push_str(SECTIONS[index]);
+    }
+    text
+}
@@ -0,0 +1,284 @@
+use num_rational::Ratio;
+use num_traits::{ CheckedAdd, CheckedDiv, CheckedMul, CheckedSub, One, Signed, Zero };
+
+use crate::aggregate::Aggregate;
+use crate::evaluator::Number;
+use crate::format::{ format_with_thousands_separator, group_thousands };
+use crate::parser::BinaryOp;
+
+/// 分数模式使用的有理数
+pub type Fraction = Ratio<i128>;
+
+/// 由浮点数还原分数时允许的最大分母
+const MAX_DENOMINATOR: i128 = 1_000_000;
+
+/// 分数结果的显示方式, 行变量始终导出假分数以保持精确
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FractionStyle {
+    /// `7/2`
+    #[default]
+    Fraction,
+    /// 带分数 `3 1/2`
+    Mixed,
+    /// 按小数位数显示为小数 `3.5`
+    Decimal,
+}
+
+impl FractionStyle {
+    /// 按 .func.toml 中 `fraction` 的取值查找
+    pub fn from_name(name: &str) -> Option<FractionStyle> {
+        match name.trim().to_lowercase().as_str() {
+            "fraction" | "improper" => Some(FractionStyle::Fraction),
+            "mixed" => Some(FractionStyle::Mixed),
+            "decimal" => Some(FractionStyle::Decimal),
+            _ => None,
+        }
+    }
+
+    /// TUI 中依次切换显示方式
+    pub fn next(self) -> FractionStyle {
+        match self {
+            FractionStyle::Fraction => FractionStyle::Mixed,
+            FractionStyle::Mixed => FractionStyle::Decimal,
+            FractionStyle::Decimal => FractionStyle::Fraction,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            FractionStyle::Fraction => "fraction",
+            FractionStyle::Mixed => "mixed",
+            FractionStyle::Decimal => "decimal",
+        }
+    }
+}
+
+/// 解析 `7/2`、`-1/3`、`3 1/2`、`0.25` 或 `1.5e3` 形式的数值
+pub fn parse_fraction(text: &str) -> Option<Fraction> {
+    let text = text.trim();
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(digits) => (true, digits.trim_start()),
+        None => (false, text),
+    };
+    let value = match digits.split_once('/') {
+        Some((numerator, denominator)) => {
+            let denominator = denominator.trim().parse::<i128>().ok().filter(|d| *d > 0)?;
+            match numerator.trim().split_once(' ') {
+                // 带分数
+                Some((whole, numerator)) => {
+                    let whole = Fraction::from_integer(whole.parse::<i128>().ok()?);
+                    let part = Fraction::new(numerator.trim().parse::<i128>().ok()?, denominator);
+                    whole.checked_add(&part)?
+                }
+                None => Fraction::new(numerator.parse::<i128>().ok()?, denominator),
+            }
+        }
+        None => parse_decimal_fraction(digits)?,
+    };
+    Some(if negative { -value } else { value })
+}
+
+/// `12.5` -> 25/2, `1.5e3` -> 1500
+fn parse_decimal_fraction(text: &str) -> Option<Fraction> {
+    let (mantissa, exponent) = match text.split_once(['e', 'E']) {
+        Some((mantissa, exponent)) => (mantissa, exponent.parse::<i32>().ok()?),
+        None => (text, 0),
+    };
+    let (int_part, dec_part) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    if int_part.is_empty() && dec_part.is_empty() {
+        return None;
+    }
+    let numerator = format!("{}{}", int_part, dec_part).parse::<i128>().ok()?;
+    let scale = exponent - (dec_part.len() as i32);
+    let power = 10_i128.checked_pow(scale.unsigned_abs())?;
+    if scale >= 0 {
+        Some(Fraction::from_integer(numerator.checked_mul(power)?))
+    } else {
+        Some(Fraction::new(numerator, power))
+    }
+}
+
+/// 由浮点数还原分母不超过一百万的最简分数, 误差超出浮点精度时返回 None
+///
+/// 使用连分数展开, `0.3333333333333333` 还原为 1/3, 而 `sqrt(2)` 等无理数没有对应的分数。
+pub fn fraction_from_f64(value: f64) -> Option<Fraction> {
+    if !value.is_finite() || value.abs() >= 1e18 {
+        return None;
+    }
+    let tolerance = value.abs().max(1.0) * 1e-13;
+    let (mut h0, mut h1) = (0_i128, 1_i128);
+    let (mut k0, mut k1) = (1_i128, 0_i128);
+    let mut x = value;
+    for _ in 0..64 {
+        let a = x.floor();
+        let a_int = a as i128;
+        let h = a_int.checked_mul(h1)?.checked_add(h0)?;
+        let k = a_int.checked_mul(k1)?.checked_add(k0)?;
+        if k > MAX_DENOMINATOR {
+            return None;
+        }
+        if ((h as f64) / (k as f64) - value).abs() <= tolerance {
+            return Some(Fraction::new(h, k));
+        }
+        (h0, h1, k0, k1) = (h1, h, k1, k);
+        let rest = x - a;
+        if rest == 0.0 {
+            return None;
+        }
+        x = 1.0 / rest;
+    }
+    None
+}
+
+/// 行变量中保存的精确写法, 整数不带分母
+pub fn fraction_text(value: &Fraction) -> String {
+    if value.is_integer() {
+        value.numer().to_string()
+    } else {
+        format!("{}/{}", value.numer(), value.denom())
+    }
+}
+
+/// 按显示方式格式化分数, 整数部分添加千位分隔符
+pub fn format_fraction(value: &Fraction, style: FractionStyle, decimal_places: usize) -> String {
+    if value.is_integer() {
+        return group_thousands(&value.numer().to_string());
+    }
+    match style {
+        FractionStyle::Fraction => fraction_text(value),
+        FractionStyle::Mixed => {
+            let whole = value.trunc();
+            if whole.is_zero() {
+                return fraction_text(value);
+            }
+            let part = (value - whole).abs();
+            format!("{} {}/{}", group_thousands(&whole.numer().to_string()), part.numer(), part.denom())
+        }
+        FractionStyle::Decimal => format_with_thousands_separator(Number::to_f64(*value), decimal_places),
+    }
+}
+
+/// 整数次幂, 溢出时返回 None
+fn checked_powi(base: Fraction, exponent: i128) -> Option<Fraction> {
+    if exponent < 0 {
+        if base.is_zero() {
+            return None;
+        }
+        return checked_powi(base.recip(), -exponent);
+    }
+    let mut result = Fraction::one();
+    for _ in 0..exponent.min(4096) {
+        result = result.checked_mul(&base)?;
+    }
+    Some(result)
+}
+
+/// 分子分母都是完全平方数时的精确平方根
+fn exact_sqrt(value: &Fraction) -> Option<Fraction> {
+    if value.is_negative() {
+        return None;
+    }
+    let root = |n: i128| {
+        let r = (n as f64).sqrt().round() as i128;
+        (r.checked_mul(r)? == n).then_some(r)
+    };
+    Some(Fraction::new(root(*value.numer())?, root(*value.denom())?))
+}
+
+/// 分数运算保持精确, 溢出、除以零或结果不是有理数时报错
+impl Number for Fraction {
+    fn literal(_value: f64, digits: &str) -> Result<Self, String> {
+        parse_fraction(digits).ok_or_else(|| format!("Number `{}` is too large for a fraction", digits))
+    }
+
+    fn parse(text: &str) -> Option<Self> {
+        parse_fraction(text)
+    }
+
+    fn from_f64(value: f64) -> Option<Self> {
+        fraction_from_f64(value)
+    }
+
+    fn to_f64(self) -> f64 {
+        (*self.numer() as f64) / (*self.denom() as f64)
+    }
+
+    fn binary(op: BinaryOp, lhs: Self, rhs: Self) -> Result<Self, String> {
+        if matches!(op, BinaryOp::Div | BinaryOp::Rem) && rhs.is_zero() {
+            return Err("Division by zero".to_string());
+        }
+        let value = match op {
+            BinaryOp::Add => lhs.checked_add(&rhs),
+            BinaryOp::Sub => lhs.checked_sub(&rhs),
+            BinaryOp::Mul => lhs.checked_mul(&rhs),
+            BinaryOp::Div => lhs.checked_div(&rhs),
+            BinaryOp::Rem =>
+                lhs
+                    .checked_div(&rhs)
+                    .and_then(|quotient| rhs.checked_mul(&quotient.trunc()))
+                    .and_then(|multiple| lhs.checked_sub(&multiple)),
+            BinaryOp::Pow => {
+                if !rhs.is_integer() {
+                    return Err("Powers with a fractional exponent are not exact".to_string());
+                }
+                checked_powi(lhs, *rhs.numer())
+            }
+        };
+        value.ok_or_else(|| "Overflow".to_string())
+    }
+
+    fn neg(self) -> Self {
+        -self
+    }
+
+    fn percent(self) -> Self {
+        self / Fraction::from_integer(100)
+    }
+
+    fn call(name: &str, args: &[Self]) -> Result<Self, String> {
+        let x = args[0];
+        match name {
+            "abs" => Ok(x.abs()),
+            "floor" => Ok(x.floor()),
+            "ceil" => Ok(x.ceil()),
+            "round" => Ok(x.round()),
+            "min" => Ok(args.iter().copied().fold(x, |min, value| min.min(value))),
+            "max" => Ok(args.iter().copied().fold(x, |max, value| max.max(value))),
+            "sqrt" => exact_sqrt(&x).ok_or_else(|| "`sqrt` of this value is not a fraction".to_string()),
+            _ => Err(format!("`{}` has no exact fraction result, use float or decimal arithmetic", name)),
+        }
+    }
+
+    fn call_lists(name: &str, _args: &[Vec<Self>]) -> Result<Self, String> {
+        Err(format!("`{}` has no exact fraction result, use float or decimal arithmetic", name))
+    }
+
+    fn aggregate(aggregate: Aggregate, values: &[Self]) -> Option<Self> {
+        aggregate.apply_fraction(values)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fraction(text: &str) -> Fraction {
+        parse_fraction(text).unwrap()
+    }
+
+    #[test]
+    fn exact_arithmetic() {
+        let sum = Fraction::binary(BinaryOp::Add, fraction("1/3"), fraction("1/6")).unwrap();
+        assert_eq!(fraction_text(&sum), "1/2");
+        assert!(Fraction::binary(BinaryOp::Div, fraction("1"), fraction("0")).is_err());
+    }
+
+    #[test]
+    fn display_styles() {
+        let value = fraction("7/2");
+        assert_eq!(format_fraction(&value, FractionStyle::Fraction, 2), "7/2");
+        assert_eq!(format_fraction(&value, FractionStyle::Mixed, 2), "3 1/2");
+        assert_eq!(format_fraction(&value, FractionStyle::Decimal, 2), "3.5");
+        assert_eq!(fraction_from_f64(0.75), Some(fraction("3/4")));
+    }
+}
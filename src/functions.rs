@@ -0,0 +1,131 @@
+use std::collections::HashMap;
+
+use crate::evaluator::function_arity;
+use crate::parser::{ parse_function_body, Expr, ExprKind };
+
+/// .func.toml 的 [functions] 中定义的函数, 例如 `bmi(w, h) = "w / h^2"`
+#[derive(Debug, Clone)]
+pub struct UserFunction {
+    pub name: String,
+    pub params: Vec<String>,
+    pub body: Expr,
+}
+
+/// 用户定义的函数表, 函数体只能使用参数、数字、内置函数以及其他用户函数
+#[derive(Debug, Clone, Default)]
+pub struct UserFunctions {
+    functions: HashMap<String, UserFunction>,
+}
+
+impl UserFunctions {
+    /// 由 `(函数头, 函数体)` 构造, 函数之间可以互相调用但不能递归
+    ///
+    /// 出错时返回出错定义的序号和错误信息。
+    pub fn new(definitions: &[(String, String)]) -> Result<UserFunctions, (usize, String)> {
+        let mut table = UserFunctions::default();
+        for (index, (signature, _)) in definitions.iter().enumerate() {
+            let (name, params) = parse_signature(signature).map_err(|message| (index, message))?;
+            if table.functions.contains_key(&name) {
+                return Err((index, format!("`{}` is defined more than once", name)));
+            }
+            // 函数体解析时只用到参数个数
+            let body = Expr { kind: ExprKind::Number(0.0, "0".to_string()), span: 0..0 };
+            table.functions.insert(name.clone(), UserFunction { name, params, body });
+        }
+        let mut bodies = Vec::new();
+        for (index, (signature, text)) in definitions.iter().enumerate() {
+            let name = signature_name(signature);
+            let params = &table.functions[&name].params;
+            let body = parse_function_body(text, params, &table).map_err(|err| {
+                (index, format!("`{}`: {}", name, err))
+            })?;
+            bodies.push((name, body));
+        }
+        for (name, body) in bodies {
+            if let Some(function) = table.functions.get_mut(&name) {
+                function.body = body;
+            }
+        }
+        for (index, (signature, _)) in definitions.iter().enumerate() {
+            let name = signature_name(signature);
+            if table.calls(&name, &name, &mut Vec::new()) {
+                return Err((index, format!("`{}` calls itself", name)));
+            }
+        }
+        Ok(table)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&UserFunction> {
+        self.functions.get(name)
+    }
+
+    /// 参数个数, 不是用户函数时返回 None
+    pub fn arity(&self, name: &str) -> Option<usize> {
+        self.functions.get(name).map(|function| function.params.len())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.functions.is_empty()
+    }
+
+    /// `caller` 是否直接或间接调用 `target`
+    fn calls(&self, caller: &str, target: &str, visited: &mut Vec<String>) -> bool {
+        if visited.iter().any(|name| name == caller) {
+            return false;
+        }
+        visited.push(caller.to_string());
+        let mut callees = Vec::new();
+        if let Some(function) = self.functions.get(caller) {
+            function.body.walk(
+                &mut (|expr| {
+                    if let ExprKind::Call(name, _) = &expr.kind {
+                        if self.functions.contains_key(name) {
+                            callees.push(name.clone());
+                        }
+                    }
+                })
+            );
+        }
+        callees.iter().any(|callee| callee == target || self.calls(callee, target, visited))
+    }
+}
+
+/// 解析函数头 `bmi(w, h)`, 名称和参数不区分大小写
+fn parse_signature(signature: &str) -> Result<(String, Vec<String>), String> {
+    let invalid = || format!("Invalid function definition `{}`, expected e.g. `f(x, y)`", signature.trim());
+    let (name, rest) = signature.trim().split_once('(').ok_or_else(invalid)?;
+    let params = rest.trim_end().strip_suffix(')').ok_or_else(invalid)?;
+    let name = name.trim().to_lowercase();
+    if !is_identifier(&name) {
+        return Err(invalid());
+    }
+    if function_arity(&name).is_some() {
+        return Err(format!("`{}` is a built-in function", name));
+    }
+    let params: Vec<String> = if params.trim().is_empty() {
+        Vec::new()
+    } else {
+        params
+            .split(',')
+            .map(|param| param.trim().to_lowercase())
+            .collect()
+    };
+    for (index, param) in params.iter().enumerate() {
+        if !is_identifier(param) {
+            return Err(format!("Invalid parameter `{}` in `{}`", param, name));
+        }
+        if params[..index].contains(param) {
+            return Err(format!("Duplicate parameter `{}` in `{}`", param, name));
+        }
+    }
+    Ok((name, params))
+}
+
+/// 已通过检查的函数头中的名称
+fn signature_name(signature: &str) -> String {
+    signature.split('(').next().unwrap_or_default().trim().to_lowercase()
+}
+
+fn is_identifier(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic()) && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
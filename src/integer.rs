@@ -0,0 +1,288 @@
+use crate::aggregate::Aggregate;
+use crate::evaluator::Number;
+use crate::parser::{ BinaryOp, BitOp };
+
+/// 程序员模式的整数宽度和有无符号, 每步运算的结果按宽度回绕
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WordSize {
+    pub bits: u32,
+    pub signed: bool,
+}
+
+impl Default for WordSize {
+    fn default() -> Self {
+        Self { bits: 64, signed: true }
+    }
+}
+
+impl WordSize {
+    /// 按 .func.toml 中 `word_size` 的取值查找: `i8`、`u16`、`int32`、`uint64`, 只写位数时为有符号
+    pub fn from_name(name: &str) -> Option<WordSize> {
+        let name = name.trim().to_lowercase();
+        let (signed, bits) = if let Some(bits) = name.strip_prefix("uint").or_else(|| name.strip_prefix('u')) {
+            (false, bits)
+        } else {
+            (true, name.strip_prefix("int").or_else(|| name.strip_prefix('i')).unwrap_or(&name))
+        };
+        match bits.parse::<u32>() {
+            Ok(bits @ (8 | 16 | 32 | 64)) => Some(WordSize { bits, signed }),
+            _ => None,
+        }
+    }
+
+    /// `i32`、`u8` 形式的名称
+    pub fn name(self) -> String {
+        format!("{}{}", if self.signed { 'i' } else { 'u' }, self.bits)
+    }
+
+    /// 按宽度回绕, 有符号时最高位为符号位
+    pub fn wrap(self, value: i128) -> i128 {
+        let modulus = 1i128 << self.bits;
+        let value = value.rem_euclid(modulus);
+        if self.signed && value >= modulus / 2 { value - modulus } else { value }
+    }
+
+    /// 以补码表示的位模式, 用于十六进制、八进制和二进制显示
+    fn pattern(self, value: i128) -> u128 {
+        (value as u128) & ((1u128 << self.bits) - 1)
+    }
+}
+
+/// 程序员模式下结果列的显示进制
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Base {
+    #[default]
+    Decimal,
+    Hex,
+    Octal,
+    Binary,
+}
+
+impl Base {
+    /// 按 .func.toml 中 `base` 的取值查找
+    pub fn from_name(name: &str) -> Option<Base> {
+        match name.trim().to_lowercase().as_str() {
+            "dec" | "decimal" | "10" => Some(Base::Decimal),
+            "hex" | "hexadecimal" | "16" => Some(Base::Hex),
+            "oct" | "octal" | "8" => Some(Base::Octal),
+            "bin" | "binary" | "2" => Some(Base::Binary),
+            _ => None,
+        }
+    }
+
+    pub fn radix(self) -> u32 {
+        match self {
+            Base::Decimal => 10,
+            Base::Hex => 16,
+            Base::Octal => 8,
+            Base::Binary => 2,
+        }
+    }
+}
+
+/// 解析 `0xFF`、`0o17`、`0b1010` 形式的整数, 数字之间可以有 `_`
+pub fn parse_radix(text: &str) -> Option<u128> {
+    let text = text.trim();
+    let base = match text.get(..2)?.to_lowercase().as_str() {
+        "0x" => Base::Hex,
+        "0o" => Base::Octal,
+        "0b" => Base::Binary,
+        _ => {
+            return None;
+        }
+    };
+    let digits: String = text[2..].chars().filter(|c| *c != '_').collect();
+    if digits.is_empty() || text.ends_with('_') {
+        return None;
+    }
+    u128::from_str_radix(&digits, base.radix()).ok()
+}
+
+/// 按进制显示整数, 十进制带符号, 其他进制显示按宽度截取的补码, 例如 i8 的 -1 为 `0xFF`
+pub fn format_integer(value: i128, base: Base, word: WordSize) -> String {
+    let pattern = word.pattern(value);
+    match base {
+        Base::Decimal => value.to_string(),
+        Base::Hex => format!("0x{:X}", pattern),
+        Base::Octal => format!("0o{:o}", pattern),
+        Base::Binary => format!("0b{:b}", pattern),
+    }
+}
+
+/// 补足到整个宽度的二进制位, 每四位以空格分隔, 用于 TUI 中的进制面板
+pub fn binary_digits(value: i128, word: WordSize) -> String {
+    let digits = format!("{:0width$b}", word.pattern(value), width = word.bits as usize);
+    digits
+        .as_bytes()
+        .chunks(4)
+        .map(|chunk| String::from_utf8_lossy(chunk).into_owned())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// 各进制的表示, 依次为十六进制、十进制、八进制和二进制
+pub fn format_bases(value: i128, word: WordSize) -> [String; 4] {
+    [Base::Hex, Base::Decimal, Base::Octal, Base::Binary].map(|base| format_integer(value, base, word))
+}
+
+/// 以 64 位整数进行按位运算, 供非程序员模式使用, 操作数必须是整数
+pub fn bitwise_f64(op: BitOp, lhs: f64, rhs: f64) -> Result<f64, String> {
+    let (lhs, rhs) = (whole(lhs)?, whole(rhs)?);
+    Ok(bitwise(op, lhs, rhs)? as f64)
+}
+
+/// `~x`, 供非程序员模式使用
+pub fn not_f64(value: f64) -> Result<f64, String> {
+    Ok(!whole(value)? as f64)
+}
+
+fn whole(value: f64) -> Result<i128, String> {
+    if value.fract() != 0.0 || value.abs() >= 9.2e18 {
+        return Err("Bitwise operations need integers".to_string());
+    }
+    Ok(value as i128)
+}
+
+fn bitwise(op: BitOp, lhs: i128, rhs: i128) -> Result<i128, String> {
+    Ok(match op {
+        BitOp::And => lhs & rhs,
+        BitOp::Or => lhs | rhs,
+        BitOp::Xor => lhs ^ rhs,
+        BitOp::Shl | BitOp::Shr => {
+            if !(0..=64).contains(&rhs) {
+                return Err("Shift amount must be between 0 and 64".to_string());
+            }
+            if op == BitOp::Shl { lhs << rhs } else { lhs >> rhs }
+        }
+    })
+}
+
+/// 程序员模式的整数运算, 除法向零取整, 结果由求值器按 [`WordSize`] 回绕
+impl Number for i128 {
+    fn literal(value: f64, digits: &str) -> Result<Self, String> {
+        if let Ok(value) = digits.parse::<i128>() {
+            return Ok(value);
+        }
+        <i128 as Number>::from_f64(value).ok_or_else(|| format!("Programmer mode needs integers, found `{}`", digits))
+    }
+
+    fn parse(text: &str) -> Option<Self> {
+        let text = text.trim();
+        text.parse::<i128>().ok().or_else(|| <i128 as Number>::from_f64(text.parse::<f64>().ok()?))
+    }
+
+    fn from_f64(value: f64) -> Option<Self> {
+        if value.fract() == 0.0 && value.abs() < 1.7e38 { Some(value as i128) } else { None }
+    }
+
+    fn to_f64(self) -> f64 {
+        self as f64
+    }
+
+    fn binary(op: BinaryOp, lhs: Self, rhs: Self) -> Result<Self, String> {
+        if matches!(op, BinaryOp::Div | BinaryOp::Rem) && rhs == 0 {
+            return Err("Division by zero".to_string());
+        }
+        Ok(match op {
+            BinaryOp::Add => lhs.wrapping_add(rhs),
+            BinaryOp::Sub => lhs.wrapping_sub(rhs),
+            BinaryOp::Mul => lhs.wrapping_mul(rhs),
+            BinaryOp::Div => lhs.wrapping_div(rhs),
+            BinaryOp::Rem => lhs.wrapping_rem(rhs),
+            BinaryOp::Pow => {
+                let exponent = match u32::try_from(rhs) {
+                    Ok(exponent) => exponent,
+                    Err(_) => {
+                        return Err("Programmer mode needs a non-negative exponent".to_string());
+                    }
+                };
+                lhs.wrapping_pow(exponent)
+            }
+        })
+    }
+
+    fn bitwise(op: BitOp, lhs: Self, rhs: Self) -> Result<Self, String> {
+        bitwise(op, lhs, rhs)
+    }
+
+    fn not(self) -> Result<Self, String> {
+        Ok(!self)
+    }
+
+    fn wrap(self, word: WordSize) -> Self {
+        word.wrap(self)
+    }
+
+    fn neg(self) -> Self {
+        self.wrapping_neg()
+    }
+
+    fn percent(self) -> Self {
+        self / 100
+    }
+
+    fn call(name: &str, args: &[Self]) -> Result<Self, String> {
+        let x = args[0];
+        match name {
+            "abs" => Ok(x.wrapping_abs()),
+            "min" => Ok(args.iter().copied().fold(x, i128::min)),
+            "max" => Ok(args.iter().copied().fold(x, i128::max)),
+            "pow" => Self::binary(BinaryOp::Pow, x, args[1]),
+            _ => Err(format!("`{}` is not available in programmer mode", name)),
+        }
+    }
+
+    /// 平均值和中位数向零取整
+    fn aggregate(aggregate: Aggregate, values: &[Self]) -> Option<Self> {
+        if values.is_empty() {
+            return Some(0);
+        }
+        let sum = || values.iter().fold(0i128, |sum, value| sum.wrapping_add(*value));
+        match aggregate {
+            Aggregate::Sum => Some(sum()),
+            Aggregate::Average => Some(sum() / (values.len() as i128)),
+            Aggregate::Min => values.iter().copied().min(),
+            Aggregate::Max => values.iter().copied().max(),
+            Aggregate::Median => {
+                let mut sorted = values.to_vec();
+                sorted.sort();
+                let mid = sorted.len() / 2;
+                Some(if sorted.len().is_multiple_of(2) { (sorted[mid - 1] + sorted[mid]) / 2 } else { sorted[mid] })
+            }
+            Aggregate::Count => Some(values.len() as i128),
+            Aggregate::Product => Some(values.iter().fold(1i128, |product, value| product.wrapping_mul(*value))),
+            Aggregate::Stdev => {
+                let values: Vec<f64> = values
+                    .iter()
+                    .map(|value| *value as f64)
+                    .collect();
+                Some(Aggregate::Stdev.apply(&values).trunc() as i128)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wraps_to_word_size() {
+        let u8 = WordSize::from_name("u8").unwrap();
+        let i8 = WordSize::from_name("int8").unwrap();
+        assert_eq!(u8.wrap(256), 0);
+        assert_eq!(u8.wrap(-1), 255);
+        assert_eq!(i8.wrap(128), -128);
+        assert_eq!(WordSize::from_name("u12"), None);
+    }
+
+    #[test]
+    fn formats_twos_complement() {
+        let i8 = WordSize::from_name("i8").unwrap();
+        assert_eq!(format_integer(-1, Base::Hex, i8), "0xFF");
+        assert_eq!(format_integer(-1, Base::Decimal, i8), "-1");
+        assert_eq!(format_integer(5, Base::Binary, i8), "0b101");
+        assert_eq!(parse_radix("0xff"), Some(255));
+        assert_eq!(parse_radix("0b1010"), Some(10));
+    }
+}
@@ -0,0 +1,59 @@
+//! RS Mathematical Tools 的计算核心
+//!
+//! 提供与 TUI 相同的工作表语义: A ~ T (可扩展到 AA ~ ZZ) 行、`x` 方程、百分比以及
+//! `z` (求和区域的总和, 默认 A - Q), 求和区域的其他聚合值可以用 `avg`、`max` 等名称引用。
+//! 所有状态都保存在 [`Engine`] 实例中, 可以在其他程序中嵌入使用。
+
+pub mod aggregate;
+pub mod complex;
+pub mod dates;
+pub mod decimal;
+pub mod engine;
+pub mod error;
+pub mod evaluator;
+pub mod finance;
+pub mod format;
+pub mod fraction;
+pub mod functions;
+pub mod integer;
+pub mod locale;
+pub mod matrix;
+pub mod parser;
+pub mod report;
+pub mod solver;
+pub mod stats;
+pub mod units;
+
+pub use aggregate::{ parse_aggregates, Aggregate };
+pub use complex::{ complex_text, format_complex, parse_complex, ComplexForm };
+pub use dates::{ format_date, format_duration, parse_date, Calendar };
+pub use decimal::{ Arithmetic, Rounding };
+pub use engine::{
+    parse_row_range,
+    range_label,
+    region_values,
+    row_index,
+    row_label,
+    Engine,
+    MAX_ROWS,
+    ROW_COUNT,
+    SUM_ROWS,
+};
+pub use error::EvalError;
+pub use format::{
+    display_width,
+    format_decimal,
+    format_with_thousands_separator,
+    parse_number,
+    remove_thousands_separator,
+    NumberFormat,
+};
+pub use fraction::{ format_fraction, fraction_text, parse_fraction, Fraction, FractionStyle };
+pub use functions::{ UserFunction, UserFunctions };
+pub use integer::{ binary_digits, format_bases, format_integer, parse_radix, Base, WordSize };
+pub use locale::{ Grouping, Locale };
+pub use matrix::{ format_matrix, matrix_lines, matrix_text, parse_matrix, Matrix };
+pub use parser::{ parse, parse_localized, Statement };
+pub use report::{ AggregateReport, RowReport, SheetReport };
+pub use stats::Summary;
+pub use units::{ format_quantity, parse_quantity, parse_unit, Quantity, Unit };
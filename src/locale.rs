@@ -0,0 +1,177 @@
+/// 整数部分的分组方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Grouping {
+    /// 每三位一组 `1,234,567`
+    #[default]
+    Thousands,
+    /// 印度计数法, 最后三位之前每两位一组 `12,34,567`
+    Indian,
+    /// 不分组
+    None,
+}
+
+impl Grouping {
+    /// 按 .func.toml 中 `grouping` 的取值查找
+    pub fn from_name(name: &str) -> Option<Grouping> {
+        match name.trim().to_lowercase().as_str() {
+            "3" | "thousands" => Some(Grouping::Thousands),
+            "indian" | "lakh" | "2" => Some(Grouping::Indian),
+            "none" | "0" => Some(Grouping::None),
+            _ => None,
+        }
+    }
+}
+
+/// 数值的输入和显示习惯: 小数点、分组符号和分组方式
+///
+/// 计算时行变量始终保存为 `1234.5` 这样的标准写法, 只在解析输入和显示结果时按区域转换。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Locale {
+    pub decimal: char,
+    pub group: char,
+    pub grouping: Grouping,
+}
+
+impl Default for Locale {
+    fn default() -> Self {
+        Self { decimal: '.', group: ',', grouping: Grouping::Thousands }
+    }
+}
+
+impl Locale {
+    /// 预设的区域: `en`、`de`、`fr`、`ch`、`in`
+    pub fn from_name(name: &str) -> Option<Locale> {
+        let (decimal, group, grouping) = match name.trim().to_lowercase().replace('_', "-").as_str() {
+            "en" | "en-us" | "en-gb" | "zh" | "zh-cn" | "ja" => ('.', ',', Grouping::Thousands),
+            "de" | "de-de" | "it" | "es" | "nl" | "pt-br" | "id" => (',', '.', Grouping::Thousands),
+            "fr" | "fr-fr" | "ru" | "pl" | "sv" | "cs" => (',', ' ', Grouping::Thousands),
+            "ch" | "de-ch" => ('.', '\'', Grouping::Thousands),
+            "in" | "en-in" | "hi" => ('.', ',', Grouping::Indian),
+            _ => {
+                return None;
+            }
+        };
+        Some(Locale { decimal, group, grouping })
+    }
+
+    /// 小数点为逗号时函数参数以 `;` 分隔
+    pub fn argument_separator(&self) -> char {
+        if self.decimal == ',' { ';' } else { ',' }
+    }
+
+    /// 分组符号之后的一组数字是否有效, Indian 分组还接受两位一组
+    pub fn group_size_ok(&self, digits: usize) -> bool {
+        match self.grouping {
+            Grouping::Thousands => digits == 3,
+            Grouping::Indian => digits == 2 || digits == 3,
+            Grouping::None => false,
+        }
+    }
+
+    /// 输入中数值的写法转换为标准写法 `1234.5`
+    pub fn canonical(&self, text: &str) -> String {
+        text.chars()
+            .filter(|c| *c != self.group)
+            .map(|c| if c == self.decimal { '.' } else { c })
+            .collect()
+    }
+
+    /// 按区域解析一个数值, 例如 PageUp/PageDown 调整的行
+    pub fn parse_number(&self, text: &str) -> Option<f64> {
+        self.canonical(text.trim()).parse::<f64>().ok()
+    }
+
+    /// 将标准格式的显示文本转换为区域格式
+    ///
+    /// 只改动数值中的分隔符: 带千位分隔符的整数部分重新分组, 小数点替换为区域的小数点,
+    /// 因此 `0xFF`、`2026` 这样没有分隔符的数字保持不变。
+    pub fn localize(&self, text: &str) -> String {
+        if *self == Locale::default() {
+            return text.to_string();
+        }
+        let chars: Vec<char> = text.chars().collect();
+        let is_digit = |index: usize| chars.get(index).is_some_and(char::is_ascii_digit);
+        let mut output = String::new();
+        let mut i = 0;
+        while i < chars.len() {
+            if !is_digit(i) {
+                output.push(chars[i]);
+                i += 1;
+                continue;
+            }
+            let mut digits = String::new();
+            let mut grouped = false;
+            while is_digit(i) || (chars.get(i) == Some(&',') && is_digit(i + 1) && !digits.is_empty()) {
+                if chars[i] == ',' {
+                    grouped = true;
+                } else {
+                    digits.push(chars[i]);
+                }
+                i += 1;
+            }
+            output.push_str(&if grouped { self.group_digits(&digits) } else { digits });
+            if chars.get(i) == Some(&'.') && is_digit(i + 1) {
+                output.push(self.decimal);
+                i += 1;
+                while is_digit(i) {
+                    output.push(chars[i]);
+                    i += 1;
+                }
+            }
+        }
+        output
+    }
+
+    /// 按区域的分组方式分组整数部分的数字
+    fn group_digits(&self, digits: &str) -> String {
+        let sizes: &[usize] = match self.grouping {
+            Grouping::Thousands => &[3],
+            Grouping::Indian => &[3, 2],
+            Grouping::None => {
+                return digits.to_string();
+            }
+        };
+        let mut groups = Vec::new();
+        let mut end = digits.len();
+        let mut index = 0;
+        while end > 0 {
+            let size = sizes[index.min(sizes.len() - 1)];
+            let start = end.saturating_sub(size);
+            groups.push(&digits[start..end]);
+            end = start;
+            index += 1;
+        }
+        groups.reverse();
+        groups.join(&self.group.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_and_localizes_numbers() {
+        let de = Locale::from_name("de").unwrap();
+        assert_eq!(de.parse_number("1.234,56"), Some(1234.56));
+        assert_eq!(de.localize("1,234.56"), "1.234,56");
+        assert_eq!(de.argument_separator(), ';');
+
+        let ch = Locale::from_name("de-CH").unwrap();
+        assert_eq!(ch.localize("1,234,567.5"), "1'234'567.5");
+    }
+
+    #[test]
+    fn indian_grouping() {
+        let india = Locale::from_name("in").unwrap();
+        assert_eq!(india.localize("1,234,567.5"), "12,34,567.5");
+        assert_eq!(india.parse_number("12,34,567.5"), Some(1234567.5));
+    }
+
+    #[test]
+    fn keeps_numbers_without_separators() {
+        let fr = Locale::from_name("fr").unwrap();
+        assert_eq!(fr.localize("0xFF + 2026"), "0xFF + 2026");
+        assert_eq!(fr.localize("1.5"), "1,5");
+    }
+}
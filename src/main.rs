@@ -1,4 +1,4 @@
-use clap::Parser;
+use clap::{ Parser, Subcommand, ValueEnum };
 use crossterm::{
     cursor,
     event::{
@@ -17,7 +17,15 @@ use crossterm::{
     },
     execute,
     queue,
-    style::{ Attribute, Color, Print, ResetColor, SetAttribute, SetForegroundColor },
+    style::{
+        Attribute,
+        Color,
+        Print,
+        ResetColor,
+        SetAttribute,
+        SetBackgroundColor,
+        SetForegroundColor,
+    },
     terminal::{
         disable_raw_mode,
         enable_raw_mode,
@@ -28,8 +36,36 @@ use crossterm::{
         LeaveAlternateScreen,
     },
 };
-use evalexpr::eval;
-use regex::Regex;
+use regex::{ Captures, Regex };
+use rs_mathematical_tools::{
+    binary_digits,
+    display_width,
+    format_bases,
+    matrix_lines,
+    parse_aggregates,
+    parse_date,
+    parse_matrix,
+    parse_row_range,
+    range_label,
+    row_index,
+    row_label,
+    AggregateReport,
+    Arithmetic,
+    Base,
+    Calendar,
+    ComplexForm,
+    Engine,
+    FractionStyle,
+    Grouping,
+    Locale,
+    NumberFormat,
+    Rounding,
+    SheetReport,
+    UserFunctions,
+    WordSize,
+    MAX_ROWS,
+    ROW_COUNT,
+};
 use std::collections::HashMap;
 use std::env;
 use std::fs;
@@ -42,20 +78,43 @@ use toml::Value;
 #[cfg(target_os = "windows")]
 use arboard::Clipboard;
 
-#[macro_use]
-extern crate lazy_static;
-use std::sync::Mutex;
-
-lazy_static! {
-    static ref GLOBAL_SUM: Mutex<f64> = Mutex::new(0.0);
-    static ref GLOBAL_COUNT: Mutex<usize> = Mutex::new(0);
-    static ref DECIMAL_PLACES: Mutex<usize> = Mutex::new(4); // 设置默认保留四位小数, 全局参数, 可改3或4
-}
-
 #[derive(Parser, Debug)]
-#[command(author, version, about, long_about = None)]
+#[command(author, version, about, long_about = None, args_conflicts_with_subcommands = true)]
 struct Args {
     filename: Option<String>,
+
+    /// Read one expression per line from stdin instead of starting the TUI
+    #[arg(long)]
+    repl: bool,
+
+    #[command(subcommand)]
+    command: Option<Commands>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Commands {
+    /// Evaluate a section and print the results without starting the TUI
+    Eval {
+        /// File containing the section, defaults to .func.toml next to the executable
+        #[arg(short, long)]
+        file: Option<String>,
+
+        /// Name of the section to evaluate
+        #[arg(short, long, default_value = "home")]
+        section: String,
+
+        /// Output format
+        #[arg(long, value_enum, default_value_t = OutputFormat::Table)]
+        format: OutputFormat,
+    },
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum OutputFormat {
+    Table,
+    Json,
+    Csv,
+    Markdown,
 }
 
 struct TerminalManager;
@@ -77,83 +136,108 @@ impl Drop for TerminalManager {
     }
 }
 
+/// 重新载入 .func.toml 并填入 section, 载入或 [functions] 中的错误记录在 `config_error` 中
 fn handle_fc_command(
     command: &str,
     inputs: &mut Vec<String>,
     func_map: &mut HashMap<String, HashMap<String, String>>,
-    func_toml_path: &Path
+    engine: &mut Engine,
+    func_toml_path: &Path,
+    config_error: &mut Option<String>
 ) -> bool {
     let key = &command[3..];
-    if let Ok((new_func_map, _, _, _, _)) = load_func_commands_from_file(func_toml_path) {
-        *func_map = new_func_map;
-    } else {
-        eprintln!("Failed to reload .func.toml");
-        return false;
+    match load_func_commands_from_file(func_toml_path) {
+        Ok(config) => {
+            *func_map = config.func_map;
+            // 用户函数随 .func.toml 一起重新载入
+            engine.set_functions(config.functions);
+            *config_error = config.function_error;
+        }
+        Err(err) => {
+            *config_error = Some(format!("Failed to reload .func.toml: {}", err));
+            return false;
+        }
     }
 
     if let Some(commands) = func_map.get(key) {
-        for input in inputs.iter_mut().take(20) {
-            // 修改此处为20
-            input.clear();
-        }
-        for (input_key, input_value) in commands {
-            let index = match input_key.as_str() {
-                "A" => 0,
-                "B" => 1,
-                "C" => 2,
-                "D" => 3,
-                "E" => 4,
-                "F" => 5,
-                "G" => 6,
-                "H" => 7,
-                "I" => 8,
-                "J" => 9,
-                "K" => 10,
-                "L" => 11,
-                "M" => 12,
-                "N" => 13,
-                "O" => 14,
-                "P" => 15,
-                "Q" => 16,
-                "R" => 17,
-                "S" => 18,
-                "T" => 19,
-                _ => {
-                    continue;
-                }
-            };
-            inputs[index] = input_value.to_string();
-        }
+        fill_inputs_from_section(inputs, commands);
         return true;
     }
     false
 }
 
+/// 清空输入框并按行标签填入 section 的内容
+///
+/// 行数至少为 20 (A ~ T), section 中存在 AA 等更多的行时自动扩展。
+fn fill_inputs_from_section(inputs: &mut Vec<String>, commands: &HashMap<String, String>) {
+    let rows = commands
+        .keys()
+        .filter_map(|key| row_index(key))
+        .map(|index| index + 1)
+        .max()
+        .unwrap_or(0)
+        .max(ROW_COUNT);
+    inputs.clear();
+    inputs.resize(rows, String::new());
+    for (input_key, input_value) in commands {
+        if let Some(index) = row_index(input_key) {
+            inputs[index] = input_value.to_string();
+        }
+    }
+}
+
 fn handle_const_command(
     command: &str,
-    inputs: &mut Vec<String>,
+    inputs: &mut [String],
     const_map: &HashMap<String, String>,
     current_row: usize
 ) -> bool {
-    if let Some(value) = const_map.get(command) {
+    let key = command.strip_prefix("cst.").unwrap_or(command);
+    if let Some(value) = const_map.get(key) {
         inputs[current_row] = value.to_string();
         return true;
     }
     false
 }
 
-fn load_func_commands_from_file(
-    filename: &Path
-) -> Result<
-    (
-        HashMap<String, HashMap<String, String>>,
-        HashMap<String, String>,
-        Option<String>,
-        Option<String>,
-        f64,
-    ),
-    io::Error
-> {
+/// .func.toml 的解析结果
+struct FuncConfig {
+    func_map: HashMap<String, HashMap<String, String>>,
+    const_map: HashMap<String, String>,
+    custom_color: Option<String>,
+    custom_attribute: Option<String>,
+    step: f64,
+    sum_rows: Option<String>,
+    aggregates: Option<String>,
+    arithmetic: Option<String>,
+    precision: Option<String>,
+    rounding: Option<String>,
+    fraction: Option<String>,
+    complex: Option<String>,
+    /// 程序员模式的整数宽度和显示进制
+    word_size: Option<String>,
+    base: Option<String>,
+    /// [format] 中的默认显示格式和币种
+    format: Option<String>,
+    currency: Option<String>,
+    /// [TUI] 中的 `locale` 及单独设置的小数点、分组符号和分组方式
+    locale: Option<String>,
+    decimal_separator: Option<String>,
+    group_separator: Option<String>,
+    grouping: Option<String>,
+    /// [TUI] 中的 `stats`, 启动时是否显示统计面板
+    stats: bool,
+    /// [TUI] 中的 `utc_offset`, `today()` 使用的时区
+    utc_offset: Option<String>,
+    /// [holidays] 中的节假日, `2026-10-01` 或 `2026-10-01..2026-10-07`
+    holidays: Vec<String>,
+    /// [functions] 中的用户函数, `"bmi(w, h)" = "w / h^2"`
+    functions: UserFunctions,
+    /// [functions] 中出错的定义, 此时不载入任何用户函数, 其余设置照常载入
+    function_error: Option<String>,
+}
+
+fn load_func_commands_from_file(filename: &Path) -> Result<FuncConfig, io::Error> {
     if !filename.exists() {
         let initial_content =
             r#"
@@ -222,6 +306,25 @@ step = "0.1"
     let mut custom_color = None;
     let mut custom_attribute = None;
     let mut step = 0.1;
+    let mut sum_rows = None;
+    let mut aggregates = None;
+    let mut arithmetic = None;
+    let mut precision = None;
+    let mut rounding = None;
+    let mut fraction = None;
+    let mut complex = None;
+    let mut word_size = None;
+    let mut base = None;
+    let mut format = None;
+    let mut currency = None;
+    let mut locale = None;
+    let mut decimal_separator = None;
+    let mut group_separator = None;
+    let mut grouping = None;
+    let mut stats = false;
+    let mut utc_offset = None;
+    let mut holidays = Vec::new();
+    let mut definitions = Vec::new();
 
     if let Value::Table(table) = value {
         for (key, value) in table {
@@ -248,6 +351,75 @@ step = "0.1"
                     {
                         step = step_value;
                     }
+                    sum_rows = tui_table
+                        .get("sum_rows")
+                        .and_then(|v| v.as_str().map(String::from));
+                    aggregates = tui_table
+                        .get("aggregates")
+                        .and_then(|v| v.as_str().map(String::from));
+                    arithmetic = tui_table
+                        .get("arithmetic")
+                        .and_then(|v| v.as_str().map(String::from));
+                    precision = tui_table
+                        .get("precision")
+                        .and_then(|v| v.as_str().map(String::from));
+                    rounding = tui_table
+                        .get("rounding")
+                        .and_then(|v| v.as_str().map(String::from));
+                    fraction = tui_table
+                        .get("fraction")
+                        .and_then(|v| v.as_str().map(String::from));
+                    complex = tui_table
+                        .get("complex")
+                        .and_then(|v| v.as_str().map(String::from));
+                    word_size = tui_table
+                        .get("word_size")
+                        .and_then(|v| v.as_str().map(String::from));
+                    base = tui_table
+                        .get("base")
+                        .and_then(|v| v.as_str().map(String::from));
+                    locale = tui_table
+                        .get("locale")
+                        .and_then(|v| v.as_str().map(String::from));
+                    decimal_separator = tui_table
+                        .get("decimal_separator")
+                        .and_then(|v| v.as_str().map(String::from));
+                    group_separator = tui_table
+                        .get("group_separator")
+                        .and_then(|v| v.as_str().map(String::from));
+                    grouping = tui_table
+                        .get("grouping")
+                        .and_then(|v| v.as_str().map(String::from));
+                    stats = tui_table
+                        .get("stats")
+                        .and_then(|v| v.as_str())
+                        .is_some_and(|v| matches!(v.trim().to_lowercase().as_str(), "true" | "on" | "yes"));
+                    utc_offset = tui_table
+                        .get("utc_offset")
+                        .and_then(|v| v.as_str().map(String::from));
+                }
+            } else if key == "format" {
+                if let Value::Table(format_table) = value {
+                    format = format_table
+                        .get("default")
+                        .and_then(|v| v.as_str().map(String::from));
+                    currency = format_table
+                        .get("currency")
+                        .and_then(|v| v.as_str().map(String::from));
+                }
+            } else if key == "holidays" {
+                if let Value::Table(holiday_table) = value {
+                    holidays = holiday_table
+                        .values()
+                        .filter_map(|v| v.as_str().map(String::from))
+                        .collect();
+                }
+            } else if key == "functions" {
+                if let Value::Table(function_table) = value {
+                    definitions = function_table
+                        .into_iter()
+                        .filter_map(|(signature, v)| v.as_str().map(|body| (signature, body.to_string())))
+                        .collect();
                 }
             } else if let Value::Table(command_table) = value {
                 let mut commands = HashMap::new();
@@ -268,14 +440,253 @@ step = "0.1"
         );
     }
 
-    Ok((func_map, const_map, custom_color, custom_attribute, step))
+    let (functions, function_error) = match UserFunctions::new(&definitions) {
+        Ok(functions) => (functions, None),
+        Err((index, message)) => {
+            let line = match definition_line(&content, &definitions[index].0) {
+                Some(line) => format!(" line {}", line),
+                None => String::new(),
+            };
+            let file = filename.file_name().unwrap_or(filename.as_os_str());
+            (
+                UserFunctions::default(),
+                Some(format!("Invalid function in {}{}: {}", Path::new(file).display(), line, message)),
+            )
+        }
+    };
+
+    Ok(FuncConfig {
+        func_map,
+        const_map,
+        custom_color,
+        custom_attribute,
+        step,
+        sum_rows,
+        aggregates,
+        arithmetic,
+        precision,
+        rounding,
+        fraction,
+        complex,
+        word_size,
+        base,
+        format,
+        currency,
+        locale,
+        decimal_separator,
+        group_separator,
+        grouping,
+        stats,
+        utc_offset,
+        holidays,
+        functions,
+        function_error,
+    })
 }
 
-fn main() -> io::Result<()> {
-    let _terminal_manager = TerminalManager::new()?;
+/// [functions] 中某个定义所在的行号 (从 1 开始), 用于报告定义中的错误
+fn definition_line(content: &str, signature: &str) -> Option<usize> {
+    let mut in_functions = false;
+    for (index, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.starts_with('[') {
+            in_functions = line.trim_matches(|c| c == '[' || c == ']').trim() == "functions";
+        } else if in_functions {
+            let key = line
+                .split('=')
+                .next()
+                .unwrap_or_default()
+                .trim()
+                .trim_matches(|c| c == '"' || c == '\'');
+            if key == signature {
+                return Some(index + 1);
+            }
+        }
+    }
+    None
+}
+
+/// 按 .func.toml 的 [TUI] 设置创建计算引擎
+fn new_engine(config: &FuncConfig) -> Engine {
+    let mut engine = Engine::new();
+    if let Some(sum_rows) = config.sum_rows.as_deref().and_then(parse_row_range) {
+        engine.set_sum_rows(sum_rows);
+    }
+    if let Some(aggregates) = config.aggregates.as_deref().and_then(parse_aggregates) {
+        engine.set_aggregates(aggregates);
+    }
+    if let Some(arithmetic) = config.arithmetic.as_deref().and_then(Arithmetic::from_name) {
+        engine.set_arithmetic(arithmetic);
+    }
+    if let Some(precision) = config.precision.as_deref().and_then(parse_precision) {
+        engine.set_decimal_places(precision);
+    }
+    if let Some(rounding) = config.rounding.as_deref().and_then(Rounding::from_name) {
+        engine.set_rounding(rounding);
+    }
+    if let Some(style) = config.fraction.as_deref().and_then(FractionStyle::from_name) {
+        engine.set_fraction_style(style);
+    }
+    if let Some(form) = config.complex.as_deref().and_then(ComplexForm::from_name) {
+        engine.set_complex_form(form);
+    }
+    if let Some(word_size) = config.word_size.as_deref().and_then(WordSize::from_name) {
+        engine.set_word_size(word_size);
+    }
+    if let Some(base) = config.base.as_deref().and_then(Base::from_name) {
+        engine.set_base(base);
+    }
+    if let Some(format) = config.format.as_deref().and_then(parse_format) {
+        engine.set_format(Some(format));
+    }
+    if let Some(currency) = &config.currency {
+        engine.set_currency(currency);
+    }
+    engine.set_locale(config_locale(config));
+    engine.set_calendar(config_calendar(config));
+    engine.set_functions(config.functions.clone());
+    engine
+}
+
+/// 由 `utc_offset` 和 [holidays] 得到日历, 无效的节假日被忽略
+fn config_calendar(config: &FuncConfig) -> Calendar {
+    let mut holidays = Vec::new();
+    for holiday in &config.holidays {
+        let (start, end) = holiday.split_once("..").unwrap_or((holiday, holiday));
+        if let (Some(start), Some(end)) = (parse_date(start), parse_date(end)) {
+            holidays.extend((start as i64)..=(end as i64));
+        }
+    }
+    holidays.sort_unstable();
+    holidays.dedup();
+    let utc_offset = config.utc_offset
+        .as_deref()
+        .and_then(|offset| offset.trim().trim_start_matches('+').parse::<f64>().ok())
+        .filter(|offset| (-14.0..=14.0).contains(offset))
+        .unwrap_or(0.0);
+    Calendar { utc_offset, holidays }
+}
+
+/// 由 `locale` 预设和单独设置的 `decimal_separator`、`group_separator`、`grouping` 得到区域,
+/// 小数点与分组符号相同时忽略单独的设置
+fn config_locale(config: &FuncConfig) -> Locale {
+    let mut locale = config.locale
+        .as_deref()
+        .and_then(Locale::from_name)
+        .unwrap_or_default();
+    let single_char = |value: &Option<String>| {
+        let mut chars = value.as_deref()?.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => Some(c),
+            _ => None,
+        }
+    };
+    let custom = Locale {
+        decimal: single_char(&config.decimal_separator).unwrap_or(locale.decimal),
+        group: single_char(&config.group_separator).unwrap_or(locale.group),
+        grouping: config.grouping.as_deref().and_then(Grouping::from_name).unwrap_or(locale.grouping),
+    };
+    if custom.decimal != custom.group && matches!(custom.decimal, '.' | ',') {
+        locale = custom;
+    }
+    locale
+}
+
+/// `@2`、`sci` 等格式指令, 开头的 `@` 可以省略
+fn parse_format(value: &str) -> Option<NumberFormat> {
+    let value = value.trim();
+    NumberFormat::from_directive(value.strip_prefix('@').unwrap_or(value))
+}
+
+/// 小数位数, 最多 28 位
+fn parse_precision(value: &str) -> Option<usize> {
+    value
+        .trim()
+        .parse::<usize>()
+        .ok()
+        .filter(|precision| *precision <= 28)
+}
+
+/// 应用 section 中的 `sum_rows`、`aggregates`、`arithmetic`、`precision`、`rounding`、`fraction`、`complex`、
+/// `word_size`、`base` 和 `format` 设置,
+/// 未设置时使用 [TUI] 和 [format] 中的默认值
+fn apply_section_settings(
+    engine: &mut Engine,
+    defaults: &Engine,
+    commands: Option<&HashMap<String, String>>
+) {
+    let setting = |key: &str| commands.and_then(|commands| commands.get(key)).map(String::as_str);
+    engine.set_sum_rows(
+        setting("sum_rows")
+            .and_then(parse_row_range)
+            .unwrap_or_else(|| defaults.sum_rows())
+    );
+    engine.set_aggregates(
+        setting("aggregates")
+            .and_then(parse_aggregates)
+            .unwrap_or_else(|| defaults.aggregates().to_vec())
+    );
+    engine.set_arithmetic(
+        setting("arithmetic")
+            .and_then(Arithmetic::from_name)
+            .unwrap_or_else(|| defaults.arithmetic())
+    );
+    engine.set_decimal_places(
+        setting("precision")
+            .and_then(parse_precision)
+            .unwrap_or_else(|| defaults.decimal_places())
+    );
+    engine.set_rounding(
+        setting("rounding")
+            .and_then(Rounding::from_name)
+            .unwrap_or_else(|| defaults.rounding())
+    );
+    engine.set_fraction_style(
+        setting("fraction")
+            .and_then(FractionStyle::from_name)
+            .unwrap_or_else(|| defaults.fraction_style())
+    );
+    engine.set_complex_form(
+        setting("complex")
+            .and_then(ComplexForm::from_name)
+            .unwrap_or_else(|| defaults.complex_form())
+    );
+    engine.set_word_size(
+        setting("word_size")
+            .and_then(WordSize::from_name)
+            .unwrap_or_else(|| defaults.word_size())
+    );
+    engine.set_base(
+        setting("base")
+            .and_then(Base::from_name)
+            .unwrap_or_else(|| defaults.base())
+    );
+    engine.set_format(
+        setting("format")
+            .and_then(parse_format)
+            .or_else(|| defaults.format().cloned())
+    );
+}
 
+fn main() -> io::Result<()> {
+    let args = Args::parse();
     let exe_path = env::current_exe()?;
     let exe_dir = exe_path.parent().unwrap();
+
+    if let Some(Commands::Eval { file, section, format }) = args.command {
+        let filename = file.map(PathBuf::from).unwrap_or_else(|| exe_dir.join(".func.toml"));
+        if let Err(e) = run_eval(&filename, &section, format) {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    if args.repl {
+        return run_repl(&exe_dir.join(".func.toml"));
+    }
+
+    let _terminal_manager = TerminalManager::new()?;
     let func_toml_path = exe_dir.join(".func.toml");
     let backup_toml_path = exe_dir.join(".func.toml.bak");
 
@@ -323,28 +734,28 @@ step = "0.1"
         fs::copy(&func_toml_path, &backup_toml_path)?;
     }
 
-    let (mut func_map, const_map, custom_color, custom_attribute, step) = match
-        load_func_commands_from_file(&func_toml_path)
-    {
-        Ok(result) => result,
+    let config = match load_func_commands_from_file(&func_toml_path) {
+        Ok(config) => config,
         Err(e) => {
             eprintln!("{}", e);
             wait_for_keypress("- Press any key to exit ...");
             return Err(e);
         }
     };
+    let engine = new_engine(&config);
+    let FuncConfig { mut func_map, const_map, custom_color, custom_attribute, step, stats, function_error, .. } =
+        config;
 
-    let args = Args::parse();
     let filename = args.filename.map(PathBuf::from).unwrap_or_else(|| exe_dir.join(".func.toml"));
     let (mut inputs, additional_lines) = read_inputs_from_file(&filename).unwrap_or_else(|_| {
-        (vec!["".to_string(); 20], vec![]) // 修改此处为20
+        (vec!["".to_string(); ROW_COUNT], vec![])
     });
     let lock_state = Arc::new(RwLock::new(false));
     let current_section = Arc::new(RwLock::new("home".to_string()));
 
     let undo_stack = Arc::new(RwLock::new(Vec::new()));
 
-    let result = run_app(
+    run_app(
         &filename,
         &mut inputs,
         &additional_lines,
@@ -355,10 +766,185 @@ step = "0.1"
         custom_color,
         custom_attribute,
         step, // 传递step值
+        stats,
         &func_toml_path,
-        Arc::clone(&undo_stack)
-    );
-    result
+        Arc::clone(&undo_stack),
+        engine,
+        function_error
+    )
+}
+
+/// 无界面评估指定 section, 将结果和求和区域的合计按指定格式输出到标准输出
+fn run_eval(filename: &Path, section: &str, format: OutputFormat) -> io::Result<()> {
+    if !filename.exists() {
+        return Err(
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{} does not exist", filename.display())
+            )
+        );
+    }
+
+    let config = load_func_commands_from_file(filename)?;
+    if let Some(err) = &config.function_error {
+        eprintln!("{}", err);
+    }
+    let commands = config.func_map.get(&section.to_lowercase()).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("Section [{}] not found in {}", section, filename.display())
+        )
+    })?;
+
+    let mut inputs = Vec::new();
+    fill_inputs_from_section(&mut inputs, commands);
+
+    let defaults = new_engine(&config);
+    let mut engine = defaults.clone();
+    apply_section_settings(&mut engine, &defaults, Some(commands));
+    let results = engine.evaluate_sheet(&inputs);
+
+    let report = SheetReport::new(section, &inputs, &results, &engine);
+    let output = match format {
+        OutputFormat::Table => report.to_table(),
+        OutputFormat::Json => report.to_json(),
+        OutputFormat::Csv => report.to_csv(),
+        OutputFormat::Markdown => report.to_markdown(),
+    };
+    io::stdout().write_all(output.as_bytes())
+}
+
+/// 行模式 REPL, 不进入 raw mode 和备用屏幕
+///
+/// 每行输入依次赋给 A, B, C ... 出错时保留当前标签以便重新输入。
+fn run_repl(func_toml_path: &Path) -> io::Result<()> {
+    let mut config = load_func_commands_from_file(func_toml_path)?;
+    let defaults = new_engine(&config);
+    let mut engine = defaults.clone();
+    let mut inputs = vec!["".to_string(); ROW_COUNT];
+    let mut current_row = 0;
+    let mut current_section = "home".to_string();
+
+    let stdin = io::stdin();
+    let mut stdout = io::stdout();
+    writeln!(stdout, "RS Mathematical Tools REPL, type `list` to show the sheet or `exit` to quit.")?;
+    if let Some(err) = &config.function_error {
+        writeln!(stdout, "{}", err)?;
+    }
+
+    loop {
+        write!(stdout, "{}> ", row_label(current_row))?;
+        stdout.flush()?;
+
+        let mut line = String::new();
+        if stdin.read_line(&mut line)? == 0 {
+            writeln!(stdout)?;
+            break;
+        }
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let input_command = line.to_lowercase();
+        if input_command == "exit" || input_command == "quit" {
+            break;
+        } else if input_command == "list" {
+            let results = engine.evaluate_sheet(&inputs);
+            let report = SheetReport::new(&current_section, &inputs, &results, &engine);
+            write!(stdout, "{}", report.to_table())?;
+        } else if input_command == "clear" || input_command == "cls" {
+            for input in inputs.iter_mut() {
+                input.clear();
+            }
+            engine.clear_variables();
+            current_row = 0;
+        } else if input_command == "rate" {
+            let output = execute_rate_command().unwrap_or_else(|err| err);
+            writeln!(stdout, "{}", output)?;
+        } else if let Some(command) = input_command.strip_prefix("s:") {
+            let output = execute_qalc_command(command.trim()).unwrap_or_else(|err| err);
+            writeln!(stdout, "{}", output)?;
+        } else if let Some(section) = input_command.strip_prefix("fc:") {
+            let mut config_error = None;
+            let found = handle_fc_command(
+                &input_command,
+                &mut inputs,
+                &mut config.func_map,
+                &mut engine,
+                func_toml_path,
+                &mut config_error
+            );
+            if let Some(err) = config_error {
+                writeln!(stdout, "{}", err)?;
+            }
+            if !found {
+                writeln!(stdout, "Section [{}] not found.", section)?;
+                continue;
+            }
+            current_section = section.to_string();
+            apply_section_settings(&mut engine, &defaults, config.func_map.get(section));
+            engine.clear_variables();
+            let results = engine.evaluate_sheet(&inputs);
+            let report = SheetReport::new(&current_section, &inputs, &results, &engine);
+            write!(stdout, "{}", report.to_table())?;
+            current_row = match inputs.iter().rposition(|input| !input.trim().is_empty()) {
+                Some(last) => next_row(&mut inputs, last),
+                None => 0,
+            };
+        } else {
+            if !handle_const_command(&input_command, &mut inputs, &config.const_map, current_row) {
+                inputs[current_row] = line.to_string();
+            }
+            let label = row_label(current_row);
+            match engine.evaluate_sheet(&inputs).swap_remove(current_row) {
+                Ok(result) => {
+                    writeln!(stdout, "{} = {}", label, result)?;
+                    current_row = next_row(&mut inputs, current_row);
+                }
+                // 方程组的方程还不够时保留该行, 继续输入下一个方程
+                Err(_) if engine.missing_equations(current_row) > 0 => {
+                    writeln!(
+                        stdout,
+                        "{} = ... ({} more equation(s) needed)",
+                        label,
+                        engine.missing_equations(current_row)
+                    )?;
+                    current_row = next_row(&mut inputs, current_row);
+                }
+                Err(err) => {
+                    writeln!(stdout, "{} = Error: {}", label, err.message)?;
+                    // 在输入下方用 ^ 标出出错的位置
+                    if let Some(span) = &err.span {
+                        let indent = " ".repeat(label.len() + 3);
+                        writeln!(stdout, "{}{}", indent, inputs[current_row])?;
+                        writeln!(
+                            stdout,
+                            "{}{}{}",
+                            indent,
+                            " ".repeat(span.start),
+                            "^".repeat(span.len().max(1))
+                        )?;
+                    }
+                    inputs[current_row].clear();
+                    engine.remove_variable(&label);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// 返回下一行的行号, 已是最后一行时追加新行, 达到上限后回到第一行
+fn next_row(inputs: &mut Vec<String>, current_row: usize) -> usize {
+    if current_row + 1 < inputs.len() {
+        current_row + 1
+    } else if inputs.len() < MAX_ROWS {
+        inputs.push(String::new());
+        current_row + 1
+    } else {
+        0
+    }
 }
 
 fn wait_for_keypress(message: &str) {
@@ -375,10 +961,12 @@ fn wait_for_keypress(message: &str) {
     }
 }
 
+// 事件分支中的 `if` 不能合并为匹配守卫, 否则未命中时会落入后面的字符输入分支
+#[allow(clippy::collapsible_match, clippy::too_many_arguments)]
 fn run_app(
     filename: &Path,
     inputs: &mut Vec<String>,
-    additional_lines: &Vec<String>,
+    additional_lines: &[String],
     lock_state: Arc<RwLock<bool>>,
     current_section: Arc<RwLock<String>>,
     func_map: &mut HashMap<String, HashMap<String, String>>,
@@ -386,15 +974,23 @@ fn run_app(
     custom_color: Option<String>,
     custom_attribute: Option<String>,
     step: f64,
+    mut show_stats: bool,
     func_toml_path: &Path,
-    undo_stack: Arc<RwLock<Vec<Vec<String>>>>
+    undo_stack: Arc<RwLock<Vec<Vec<String>>>>,
+    mut engine: Engine,
+    mut config_error: Option<String>
 ) -> io::Result<()> {
     let mut stdout = io::stdout();
-    let mut variables = HashMap::new();
     let mut current_row = 0;
     let mut current_pos = 0;
-    let input_width = 60;
+    let mut scroll_offset = 0;
+    let mut last_visible_rows = 0;
+    let input_width: usize = 60;
     let output_width = 20;
+    engine.set_max_result_len(output_width - 3);
+    let defaults = engine.clone();
+    // F6 切换的分数显示方式, 优先于 .func.toml 中的设置
+    let mut fraction_style: Option<FractionStyle> = None;
     let title =
         " RS Mathematical Tools                                                             V1.3.0 ";
     let heade =
@@ -459,12 +1055,45 @@ fn run_app(
         ResetColor
     )?;
 
-    let result = loop {
+    loop {
         let is_locked = *lock_state.read().unwrap();
         let current_section_name = current_section.read().unwrap().clone();
-        let (term_width, _) = size()?;
+        let (term_width, term_height) = size()?;
+        apply_section_settings(&mut engine, &defaults, func_map.get(&current_section_name));
+        if let Some(style) = fraction_style {
+            engine.set_fraction_style(style);
+        }
+
+        // 统计面板显示在合计栏下方, 其后的状态栏和菜单栏相应下移
+        let panel_lines = if show_stats { 2 } else { 0 };
+        // 行数超过终端高度时滚动显示, 至少保留原来 20 行的布局
+        let visible_rows = inputs
+            .len()
+            .min(ROW_COUNT.max((term_height as usize).saturating_sub(12 + panel_lines)));
+        if current_row < scroll_offset {
+            scroll_offset = current_row;
+        } else if current_row >= scroll_offset + visible_rows {
+            scroll_offset = current_row + 1 - visible_rows;
+        }
+        scroll_offset = scroll_offset.min(inputs.len() - visible_rows);
+        let label_width = row_label(inputs.len() - 1).len();
+        let input_column = output_width + 8 + label_width;
+        let status_row = (visible_rows + 6 + panel_lines) as u16;
+        let message_row = (visible_rows + 3) as u16;
+        let sum_region = range_label(&engine.sum_rows());
 
         let mut buffer = Vec::new();
+        if visible_rows != last_visible_rows {
+            queue!(
+                buffer,
+                Clear(ClearType::All),
+                SetAttribute(Attribute::Reverse),
+                cursor::MoveTo(0, 0),
+                Print(title),
+                ResetColor
+            )?;
+            last_visible_rows = visible_rows;
+        }
         let section_length = current_section_name.len() as u16;
         queue!(
             buffer,
@@ -477,25 +1106,63 @@ fn run_app(
             ResetColor
         )?;
 
-        let mut results = Vec::new();
-        for (i, input) in inputs.iter().enumerate() {
-            let label = (b'A' + (i as u8)) as char;
-            let result = if input.trim().is_empty() {
-                "".to_string()
-            } else {
-                match evaluate_and_solve(input, &variables, i) {
-                    Ok(res) => {
-                        if res.len() <= output_width - 3 { res } else { "Error".to_string() }
-                    }
-                    Err(_) => {
-                        if input.starts_with("fc:") {
-                            "Import from cfg file".to_string()
-                        } else {
-                            "Error".to_string()
-                        }
-                    }
-                }
-            };
+        let evaluated = engine.evaluate_sheet(inputs);
+        // 当前行的错误在输入中标出位置, 并在消息行显示原因
+        let current_error = evaluated
+            .get(current_row)
+            .and_then(|result| result.as_ref().err())
+            .filter(|_| !inputs[current_row].starts_with("fc:"))
+            .cloned();
+        let results: Vec<String> = evaluated
+            .into_iter()
+            .map(|result| result.unwrap_or_else(|_| "Error".to_string()))
+            .collect();
+        // 第一个聚合值单独一行, 其余的合并显示在第二行, 无法合计时只显示原因
+        let aggregate_lines: Vec<String> = match engine.region_error() {
+            Some(err) => vec![err.to_string()],
+            None =>
+                engine
+                    .aggregates()
+                    .iter()
+                    .map(|aggregate| {
+                        let report = AggregateReport::new(*aggregate, &engine);
+                        format!("{} = {}", report.caption(), report.display)
+                    })
+                    .collect(),
+        };
+        let aggregate_footer = |lines: &[String]| {
+            if lines.is_empty() { String::new() } else { format!("({}) {}", sum_region, lines.join(", ")) }
+        };
+        let (first_aggregate, other_aggregates) = aggregate_lines.split_at(aggregate_lines.len().min(1));
+        let scroll_hint = if inputs.len() > visible_rows {
+            format!(
+                "Rows {} - {} of {}",
+                row_label(scroll_offset),
+                row_label(scroll_offset + visible_rows - 1),
+                inputs.len()
+            )
+        } else {
+            String::new()
+        };
+        queue!(
+            buffer,
+            cursor::MoveTo(62, 1),
+            SetForegroundColor(Color::DarkGrey),
+            Print(format!("{:>26}", scroll_hint)),
+            ResetColor
+        )?;
+
+        for (i, (input, result)) in inputs
+            .iter()
+            .zip(results.iter())
+            .enumerate()
+            .skip(scroll_offset)
+            .take(visible_rows) {
+            let label = row_label(i);
+            let screen_row = (i - scroll_offset + 3) as u16;
+            // `万`、`亿` 等全角字符占两列, 相应减少补齐的空格
+            let output_width = output_width.saturating_sub(display_width(result) - result.chars().count());
+            let input_width = input_width.saturating_sub(display_width(input) - input.chars().count());
 
             if i == current_row {
                 if result == "Error" || (input.starts_with("fc:") && result.is_empty()) {
@@ -508,10 +1175,10 @@ fn run_app(
                                 Color::DarkRed
                             }
                         ),
-                        cursor::MoveTo(0, (i + 3) as u16),
+                        cursor::MoveTo(0, screen_row),
                         Print(
                             format!(
-                                "{}: [{:>width$}] = [{:<input_width$}]",
+                                "{:<label_width$}: [{:>width$}] = [{:<input_width$}]",
                                 label,
                                 if input.starts_with("fc:") {
                                     ""
@@ -531,10 +1198,10 @@ fn run_app(
                             buffer,
                             SetForegroundColor(tui_color),
                             SetAttribute(tui_attribute),
-                            cursor::MoveTo(0, (i + 3) as u16),
+                            cursor::MoveTo(0, screen_row),
                             Print(
                                 format!(
-                                    "{}: [{:>width$}] = [{:<input_width$}]",
+                                    "{:<label_width$}: [{:>width$}] = [{:<input_width$}]",
                                     label,
                                     result,
                                     input,
@@ -547,11 +1214,11 @@ fn run_app(
                     } else {
                         queue!(
                             buffer,
-                            SetForegroundColor(if i >= 17 { Color::Blue } else { tui_color }), // 修改此处为17
-                            cursor::MoveTo(0, (i + 3) as u16),
+                            SetForegroundColor(if engine.sum_rows().contains(&i) { tui_color } else { Color::Blue }),
+                            cursor::MoveTo(0, screen_row),
                             Print(
                                 format!(
-                                    "{}: [{:>width$}] = [{:<input_width$}]",
+                                    "{:<label_width$}: [{:>width$}] = [{:<input_width$}]",
                                     label,
                                     result,
                                     input,
@@ -574,10 +1241,10 @@ fn run_app(
                                 Color::DarkRed
                             }
                         ),
-                        cursor::MoveTo(0, (i + 3) as u16),
+                        cursor::MoveTo(0, screen_row),
                         Print(
                             format!(
-                                "{}: [{:>width$}] = [{:<input_width$}]",
+                                "{:<label_width$}: [{:>width$}] = [{:<input_width$}]",
                                 label,
                                 if input.starts_with("fc:") {
                                     ""
@@ -594,11 +1261,11 @@ fn run_app(
                 } else {
                     queue!(
                         buffer,
-                        SetForegroundColor(if i >= 17 { Color::Blue } else { Color::Reset }), // 修改此处为17
-                        cursor::MoveTo(0, (i + 3) as u16),
+                        SetForegroundColor(if engine.sum_rows().contains(&i) { Color::Reset } else { Color::Blue }),
+                        cursor::MoveTo(0, screen_row),
                         Print(
                             format!(
-                                "{}: [{:>width$}] = [{:<input_width$}]",
+                                "{:<label_width$}: [{:>width$}] = [{:<input_width$}]",
                                 label,
                                 result,
                                 input,
@@ -611,33 +1278,45 @@ fn run_app(
                 }
             }
 
-            if result != "Error" {
-                variables.insert(label.to_string(), result.clone());
-            } else {
-                variables.remove(&label.to_string());
-            }
-            results.push(result);
         }
 
-        let (sum, valid_count) = calculate_sum_and_count(&results);
-        let average = if valid_count > 0 { sum / (valid_count as f64) } else { 0.0 };
+        if let Some(span) = current_error.as_ref().and_then(|err| err.span.clone()) {
+            let input = &inputs[current_row];
+            let start = span.start.min(input_width.saturating_sub(1));
+            let end = span.end.clamp(start + 1, input_width);
+            let marked: String = input
+                .chars()
+                .chain(std::iter::repeat(' '))
+                .skip(start)
+                .take(end - start)
+                .collect();
+            queue!(
+                buffer,
+                cursor::MoveTo((input_column + start) as u16, (current_row - scroll_offset + 3) as u16),
+                SetForegroundColor(Color::White),
+                SetBackgroundColor(Color::DarkRed),
+                Print(marked),
+                ResetColor
+            )?;
+        }
+
         queue!(
             buffer,
-            cursor::MoveTo(0, (inputs.len() + 4) as u16),
+            cursor::MoveTo(0, (visible_rows + 4) as u16),
             Print(" ".repeat(term_width as usize)),
-            cursor::MoveTo(0, (inputs.len() + 5) as u16),
+            cursor::MoveTo(0, (visible_rows + 5) as u16),
             Print(" ".repeat(term_width as usize)),
             SetForegroundColor(Color::Blue),
-            cursor::MoveTo(10, (inputs.len() + 4) as u16),
-            Print(format!("(A - Q) Sum = Z = {}", format_with_thousands_separator(sum))),
-            cursor::MoveTo(10, (inputs.len() + 5) as u16),
-            Print(format!("(A - Q) Average = {}", format_with_thousands_separator(average))),
-            cursor::MoveTo(0, (inputs.len() + 8) as u16),
+            cursor::MoveTo(10, (visible_rows + 4) as u16),
+            Print(aggregate_footer(first_aggregate)),
+            cursor::MoveTo(10, (visible_rows + 5) as u16),
+            Print(aggregate_footer(other_aggregates)),
+            cursor::MoveTo(0, (visible_rows + 8 + panel_lines) as u16),
             ResetColor,
             SetAttribute(Attribute::Reverse),
             Print(foote),
             ResetColor,
-            cursor::MoveTo(19, 26),
+            cursor::MoveTo(19, status_row),
             SetForegroundColor(if is_locked { Color::Red } else { Color::Green }),
             Print(
                 format!("Status = {} (F4 Status Switch & Align #)", if is_locked {
@@ -647,35 +1326,73 @@ fn run_app(
                 })
             ),
             ResetColor,
-            cursor::MoveTo(0, (inputs.len() + 9) as u16),
+            cursor::MoveTo(0, (visible_rows + 9 + panel_lines) as u16),
             ResetColor
         )?;
 
+        if show_stats {
+            // 程序员模式下显示当前行的各进制表示
+            let (panel_label, panel) = if engine.arithmetic() == Arithmetic::Integer {
+                (row_label(current_row), bases_panel(&engine, current_row))
+            } else {
+                (sum_region.clone(), stats_panel(&engine))
+            };
+            for (i, line) in panel
+                .iter()
+                .enumerate()
+                .filter(|(_, line)| !line.is_empty()) {
+                queue!(
+                    buffer,
+                    cursor::MoveTo(0, (visible_rows + 6 + i) as u16),
+                    Print(" ".repeat(term_width as usize)),
+                    SetForegroundColor(Color::DarkCyan),
+                    cursor::MoveTo(10, (visible_rows + 6 + i) as u16),
+                    Print(format!("({}) {}", panel_label, line)),
+                    ResetColor
+                )?;
+            }
+        }
+
         if show_saved_message {
             queue!(
                 buffer,
-                cursor::MoveTo(0, 23),
+                cursor::MoveTo(0, message_row),
                 SetForegroundColor(Color::DarkYellow),
                 Print(format!("{} -> Section: [{}]", saved, current_section_name)),
                 ResetColor
             )?;
             show_saved_message = false;
         } else {
-            queue!(buffer, cursor::MoveTo(0, 23), Print(" ".repeat(term_width as usize)))?;
+            queue!(buffer, cursor::MoveTo(0, message_row), Print(" ".repeat(term_width as usize)))?;
+            // 当前行没有错误时显示 .func.toml 中的错误, 直到重新载入后消除
+            let message = match (&current_error, &config_error) {
+                (Some(err), _) => Some(format!("{}: {}", row_label(current_row), err.message)),
+                (None, Some(err)) => Some(err.clone()),
+                (None, None) => None,
+            };
+            if let Some(message) = message {
+                queue!(
+                    buffer,
+                    cursor::MoveTo(0, message_row),
+                    SetForegroundColor(Color::DarkRed),
+                    Print(message),
+                    ResetColor
+                )?;
+            }
         }
 
         for (i, line) in additional_lines.iter().enumerate() {
-            queue!(buffer, cursor::MoveTo(0, (inputs.len() + 10 + i) as u16), Print(line))?;
+            queue!(buffer, cursor::MoveTo(0, (visible_rows + 10 + panel_lines + i) as u16), Print(line))?;
         }
 
         if is_locked {
             queue!(buffer, cursor::Hide)?;
         } else {
             // 计算光标位置
-            let cursor_position = (output_width + 9 + current_pos) as u16;
+            let cursor_position = (input_column + current_pos) as u16;
             queue!(
                 buffer,
-                cursor::MoveTo(cursor_position, (current_row + 3) as u16),
+                cursor::MoveTo(cursor_position, (current_row - scroll_offset + 3) as u16),
                 cursor::Show
             )?;
         }
@@ -691,6 +1408,8 @@ fn run_app(
             &mut stdout,
             current_row,
             current_pos,
+            ((input_column + current_pos) as u16, (current_row - scroll_offset + 3) as u16),
+            status_row,
             &mut last_row,
             &mut last_pos
         )?;
@@ -715,9 +1434,9 @@ fn run_app(
                                 (input_text.as_str(), "")
                             };
 
-                            if let Ok(current_value) = number_part.trim().parse::<f64>() {
+                            if let Some(current_value) = engine.locale().parse_number(number_part) {
                                 let new_value = current_value + step;
-                                let formatted_value = format!("{:.3}", new_value);
+                                let formatted_value = engine.locale().localize(&format!("{:.3}", new_value));
                                 let spaces = " ".repeat(
                                     number_part.len() - number_part.trim_end().len()
                                 );
@@ -729,7 +1448,7 @@ fn run_app(
                                 );
                             } else if number_part.trim().is_empty() {
                                 // 保留三位小数
-                                inputs[current_row] = format!("{:.3}{}", step, comment_part);
+                                inputs[current_row] = format!("{}{}", engine.locale().localize(&format!("{:.3}", step)), comment_part);
                             }
                             current_pos = inputs[current_row].len();
                         }
@@ -745,10 +1464,10 @@ fn run_app(
                                 (input_text.as_str(), "")
                             };
 
-                            if let Ok(current_value) = number_part.trim().parse::<f64>() {
+                            if let Some(current_value) = engine.locale().parse_number(number_part) {
                                 let new_value = (current_value - step).max(0.0);
                                 // 保留三位小数
-                                let formatted_value = format!("{:.3}", new_value);
+                                let formatted_value = engine.locale().localize(&format!("{:.3}", new_value));
                                 let spaces = " ".repeat(
                                     number_part.len() - number_part.trim_end().len()
                                 );
@@ -759,7 +1478,7 @@ fn run_app(
                                     comment_part
                                 );
                             } else if number_part.trim().is_empty() {
-                                inputs[current_row] = format!("0.000{}", comment_part);
+                                inputs[current_row] = format!("{}{}", engine.locale().localize("0.000"), comment_part);
                             }
                             current_pos = inputs[current_row].len();
                         }
@@ -811,7 +1530,7 @@ fn run_app(
                         modifiers.contains(KeyModifiers::CONTROL)
                     => {
                         if !is_locked {
-                            move_cursor_to_next_word(&inputs, &mut current_row, &mut current_pos);
+                            move_cursor_to_next_word(inputs, &mut current_row, &mut current_pos);
                         }
                     }
 
@@ -821,12 +1540,17 @@ fn run_app(
                     => {
                         jump_to_input_box(
                             &mut stdout,
-                            &inputs,
+                            inputs,
                             &mut current_row,
                             &mut current_pos
                         )?;
                     }
 
+                    (KeyCode::F(2), KeyEventKind::Press) => {
+                        show_matrix_popup(&mut stdout, &engine, current_row)?;
+                        // 弹窗覆盖了工作表, 关闭后重绘整个屏幕
+                        last_visible_rows = 0;
+                    }
                     (KeyCode::F(4), KeyEventKind::Press) => {
                         let mut lock_state_guard = lock_state.write().unwrap();
                         *lock_state_guard = !*lock_state_guard;
@@ -838,6 +1562,14 @@ fn run_app(
                             align_hash_comments(inputs, &mut current_row, &mut current_pos);
                         }
                     }
+                    (KeyCode::F(6), KeyEventKind::Press) => {
+                        fraction_style = Some(engine.fraction_style().next());
+                    }
+                    (KeyCode::F(7), KeyEventKind::Press) => {
+                        show_stats = !show_stats;
+                        // 布局改变, 下一轮重绘整个屏幕
+                        last_visible_rows = 0;
+                    }
                     (KeyCode::F(9), KeyEventKind::Press) => {
                         remove_spaces_before_hash(inputs, &mut current_row, &mut current_pos);
                     }
@@ -845,14 +1577,14 @@ fn run_app(
                         modifiers.contains(KeyModifiers::CONTROL)
                     => {
                         if !is_locked {
-                            push_undo_stack(&undo_stack, &inputs);
-                            for input in inputs.iter_mut().take(17) {
-                                // 修改此处为17
+                            push_undo_stack(&undo_stack, inputs);
+                            // 求和区域可以超出已有的行
+                            let sum_rows = engine.sum_rows();
+                            for input in inputs.iter_mut().take(sum_rows.end).skip(sum_rows.start) {
                                 input.clear();
                             }
-                            for label in (b'A'..=b'T').map(|c| (c as char).to_string()) {
-                                // 修改此处为T
-                                variables.remove(&label);
+                            for i in 0..inputs.len() {
+                                engine.remove_variable(&row_label(i));
                             }
                             current_pos = 0;
                             current_row = 0;
@@ -863,10 +1595,9 @@ fn run_app(
                         modifiers.contains(KeyModifiers::CONTROL)
                     => {
                         if !is_locked {
-                            push_undo_stack(&undo_stack, &inputs);
-                            let label = (b'A' + (current_row as u8)) as char;
+                            push_undo_stack(&undo_stack, inputs);
                             inputs[current_row].clear();
-                            variables.remove(&label.to_string());
+                            engine.remove_variable(&row_label(current_row));
                             current_pos = 0;
                         }
                     }
@@ -898,18 +1629,8 @@ fn run_app(
                         )?;
                         show_saved_message = true;
                         queue!(buffer, Clear(ClearType::All), cursor::MoveTo(0, 0), Print(title))?;
-                        variables.clear();
-                        for (i, input) in inputs.iter().enumerate() {
-                            let label = (b'A' + (i as u8)) as char;
-                            if !input.trim().is_empty() {
-                                match evaluate_and_solve(input, &variables, i) {
-                                    Ok(res) => {
-                                        variables.insert(label.to_string(), res);
-                                    }
-                                    Err(_) => {}
-                                }
-                            }
-                        }
+                        engine.clear_variables();
+                        engine.evaluate_sheet(inputs);
                     }
                     (KeyCode::F(8), KeyEventKind::Press) => {
                         if !is_locked {
@@ -930,11 +1651,9 @@ fn run_app(
                         modifiers.contains(KeyModifiers::CONTROL)
                     => {
                         if current_row < inputs.len() {
-                            let clipboard_value = evaluate_and_solve(
-                                &inputs[current_row],
-                                &variables,
-                                current_row
-                            ).unwrap_or_else(|_| "Error".to_string());
+                            let clipboard_value = engine
+                                .evaluate_and_solve(&inputs[current_row], current_row)
+                                .unwrap_or_else(|_| "Error".to_string());
                             if clipboard_value != "Error" {
                                 let mut clipboard = Clipboard::new().unwrap();
                                 clipboard.set_text(clipboard_value).unwrap();
@@ -943,7 +1662,7 @@ fn run_app(
                                 execute!(stdout, cursor::Hide).unwrap();
                                 execute!(
                                     stdout,
-                                    cursor::MoveTo(29, 23),
+                                    cursor::MoveTo(29, message_row),
                                     SetForegroundColor(Color::DarkYellow),
                                     Print("The results has been copied!"),
                                     ResetColor
@@ -956,7 +1675,7 @@ fn run_app(
                                 // 覆盖第23行内容
                                 execute!(
                                     stdout,
-                                    cursor::MoveTo(0, 23),
+                                    cursor::MoveTo(0, message_row),
                                     Clear(ClearType::CurrentLine)
                                 ).unwrap();
                                 stdout.flush().unwrap();
@@ -978,7 +1697,7 @@ fn run_app(
                             execute!(stdout, cursor::Hide).unwrap();
                             execute!(
                                 stdout,
-                                cursor::MoveTo(29, 23),
+                                cursor::MoveTo(29, message_row),
                                 SetForegroundColor(Color::DarkYellow),
                                 Print("The content has been copied!"),
                                 ResetColor
@@ -991,7 +1710,7 @@ fn run_app(
                             // 覆盖第23行内容
                             execute!(
                                 stdout,
-                                cursor::MoveTo(0, 23),
+                                cursor::MoveTo(0, message_row),
                                 Clear(ClearType::CurrentLine)
                             ).unwrap();
                             stdout.flush().unwrap();
@@ -1048,9 +1767,14 @@ fn run_app(
                         }
                     }
                     (KeyCode::Down, KeyEventKind::Press) => {
-                        if !is_locked && current_row < inputs.len() - 1 {
+                        // 在最后一行按 ↓ 时追加新行
+                        if
+                            !is_locked &&
+                            (current_row < inputs.len() - 1 ||
+                                !inputs[current_row].trim().is_empty())
+                        {
                             inputs[current_row] = format_math_expression(&inputs[current_row]);
-                            current_row += 1;
+                            current_row = next_row(inputs, current_row);
                             current_pos = inputs[current_row].len();
                         }
                     }
@@ -1073,14 +1797,14 @@ fn run_app(
                     }
                     (KeyCode::Backspace, KeyEventKind::Press) => {
                         if !is_locked && current_pos > 0 {
-                            push_undo_stack(&undo_stack, &inputs);
+                            push_undo_stack(&undo_stack, inputs);
                             inputs[current_row].remove(current_pos - 1);
                             current_pos -= 1;
                         }
                     }
                     (KeyCode::Delete, KeyEventKind::Press) => {
                         if !is_locked && current_pos < inputs[current_row].len() {
-                            push_undo_stack(&undo_stack, &inputs);
+                            push_undo_stack(&undo_stack, inputs);
                             inputs[current_row].remove(current_pos);
                         }
                     }
@@ -1153,7 +1877,14 @@ fn run_app(
                                 current_row = 0;
                             } else if
                                 input_command.starts_with("fc:") &&
-                                handle_fc_command(&input_command, inputs, func_map, func_toml_path)
+                                handle_fc_command(
+                                    &input_command,
+                                    inputs,
+                                    func_map,
+                                    &mut engine,
+                                    func_toml_path,
+                                    &mut config_error
+                                )
                             {
                                 current_pos = inputs[current_row].len();
                                 *current_section.write().unwrap() = input_command[3..].to_string();
@@ -1163,7 +1894,7 @@ fn run_app(
                                 current_pos = inputs[current_row].len();
                             } else if input_command == "clear" || input_command == "cls" {
                                 if !is_locked {
-                                    for input in inputs.iter_mut().take(20) {
+                                    for input in inputs.iter_mut() {
                                         input.clear();
                                     }
                                     current_pos = 0;
@@ -1207,30 +1938,12 @@ fn run_app(
 
                                 current_pos = inputs[current_row].len();
                             } else if input_command == "rate" {
-                                inputs[current_row].clear();
-                                let exe_path = env::current_exe().unwrap();
-                                let exe_dir = exe_path.parent().unwrap();
-                                let command_path = if cfg!(target_os = "windows") {
-                                    exe_dir.join("rate.exe")
-                                } else {
-                                    exe_dir.join("./rate")
-                                };
-                                let output = std::process::Command::new(command_path).output();
-                                match output {
-                                    Ok(output) => {
-                                        let result = String::from_utf8_lossy(&output.stdout);
-                                        let trimmed_result = result.trim();
-                                        inputs[current_row].push_str(trimmed_result);
-                                    }
-                                    Err(_) => {
-                                        inputs[current_row].push_str(
-                                            "The rate command was not found!"
-                                        );
-                                    }
-                                }
+                                inputs[current_row] = execute_rate_command().unwrap_or_else(
+                                    |err| err
+                                );
                                 current_pos = inputs[current_row].len();
-                            } else if input_command.starts_with("s:") {
-                                let command = &input_command[2..].trim();
+                            } else if let Some(command) = input_command.strip_prefix("s:") {
+                                let command = command.trim();
                                 match execute_qalc_command(command) {
                                     Ok(result) => {
                                         inputs[current_row] = result;
@@ -1248,19 +1961,11 @@ fn run_app(
                     }
 
                     (KeyCode::Char(c), KeyEventKind::Press) if !is_locked && c.is_ascii() => {
+                        // 求和区域中引用 `z` 时由求值报错, 不影响 `Hz`、`oz` 等单位
                         if inputs[current_row].len() < input_width {
-                            if (c == 'Z' || c == 'z') && current_row <= 16 {
-                                // 修改此处为16
-                                inputs[current_row].clear();
-                                inputs[current_row].push_str(
-                                    "# Global variable Z is limited to the R ~ T area only"
-                                );
-                                // 修改此处为Q-T
-                            } else {
-                                push_undo_stack(&undo_stack, &inputs);
-                                inputs[current_row].insert(current_pos, c);
-                                current_pos += 1;
-                            }
+                            push_undo_stack(&undo_stack, inputs);
+                            inputs[current_row].insert(current_pos, c);
+                            current_pos += 1;
                         }
                     }
                     _ => {}
@@ -1268,41 +1973,25 @@ fn run_app(
             Event::Mouse(MouseEvent { kind, column, row, .. }) =>
                 match kind {
                     MouseEventKind::Down(MouseButton::Left) => {
-                        if !is_locked && (3..inputs.len() + 3).contains(&(row as usize)) {
+                        if !is_locked && (3..visible_rows + 3).contains(&(row as usize)) {
                             inputs[current_row] = format_math_expression(&inputs[current_row]);
-                            let clicked_row = (row as usize) - 3;
+                            let clicked_row = (row as usize) - 3 + scroll_offset;
                             current_row = clicked_row;
-                            current_pos = (column as usize) - (output_width + 9);
+                            current_pos = (column as usize).saturating_sub(input_column);
                             if current_pos > inputs[current_row].len() {
                                 current_pos = inputs[current_row].len();
                             }
-                            let label = (b'A' + (current_row as u8)) as char;
-                            let result = if inputs[current_row].trim().is_empty() {
-                                "".to_string()
-                            } else {
-                                match
-                                    evaluate_and_solve(
-                                        &inputs[current_row],
-                                        &variables,
-                                        current_row
-                                    )
-                                {
-                                    Ok(res) => {
-                                        if res.len() <= output_width - 3 {
-                                            res
-                                        } else {
-                                            "Error".to_string()
-                                        }
-                                    }
-                                    Err(_) => "Error".to_string(),
-                                }
-                            };
+                            let label = row_label(current_row);
+                            let result = engine
+                                .evaluate_row(&inputs[current_row], current_row)
+                                .unwrap_or_else(|_| "Error".to_string());
+                            let output_width = output_width.saturating_sub(display_width(&result) - result.chars().count());
                             queue!(
                                 buffer,
-                                cursor::MoveTo(0, (current_row + 3) as u16),
+                                cursor::MoveTo(0, row),
                                 Print(
                                     format!(
-                                        "{}: [{:>width$}] = [{:<input_width$}]",
+                                        "{:<label_width$}: [{:>width$}] = [{:<input_width$}]",
                                         label,
                                         result,
                                         inputs[current_row],
@@ -1337,7 +2026,89 @@ fn run_app(
 
     disable_raw_mode()?;
     execute!(stdout, LeaveAlternateScreen, DisableMouseCapture)?;
-    Ok(result)
+    Ok(())
+}
+
+/// 统计面板的两行: 求和区域的个数、中位数、众数、标准差、方差以及四分位数和极值
+fn stats_panel(engine: &Engine) -> [String; 2] {
+    let show = |value: f64| engine.locale().localize(&engine.format_number(value));
+    match engine.summary() {
+        Some(summary) =>
+            [
+                format!(
+                    "Count = {}, Median = {}, Mode = {}, Stdev = {}, Variance = {}",
+                    summary.count,
+                    show(summary.median),
+                    summary.mode.map_or_else(|| "-".to_string(), show),
+                    show(summary.stdev),
+                    show(summary.variance)
+                ),
+                format!(
+                    "Q1 = {}, Q3 = {}, IQR = {}, Min = {}, Max = {}",
+                    show(summary.q1),
+                    show(summary.q3),
+                    show(summary.q3 - summary.q1),
+                    show(summary.min),
+                    show(summary.max)
+                ),
+            ],
+        None => ["Statistics: no numbers".to_string(), String::new()],
+    }
+}
+
+/// 程序员模式的面板: 当前行结果的十六进制、十进制、八进制以及按宽度补齐的二进制
+fn bases_panel(engine: &Engine, row: usize) -> [String; 2] {
+    let word = engine.word_size();
+    match engine.variables().get(&row_label(row)).and_then(|value| value.parse::<i128>().ok()) {
+        Some(value) => {
+            let [hex, dec, oct, _] = format_bases(value, word);
+            [
+                format!("HEX = {}, DEC = {}, OCT = {} ({})", hex, dec, oct, word.name()),
+                format!("BIN = {}", binary_digits(value, word)),
+            ]
+        }
+        None => [format!("Bases: no integer ({})", word.name()), String::new()],
+    }
+}
+
+/// F2 弹窗: 在工作表上方显示当前行的完整矩阵, 按任意键关闭
+fn show_matrix_popup(stdout: &mut io::Stdout, engine: &Engine, row: usize) -> io::Result<()> {
+    let label = row_label(row);
+    let mut lines = match engine.variables().get(&label).and_then(|value| parse_matrix(value)) {
+        Some(matrix) => {
+            let mut lines = vec![format!("{} = {} matrix", label, matrix.shape())];
+            lines.extend(matrix_lines(&matrix, engine.decimal_places()).iter().map(|line| engine.locale().localize(line)));
+            lines
+        }
+        None => vec![format!("Row {} is not a matrix", label)],
+    };
+    lines.push("- Press any key to close".to_string());
+    let width = lines
+        .iter()
+        .map(|line| display_width(line))
+        .max()
+        .unwrap_or(0);
+    let (left, top) = (4, 3);
+    queue!(stdout, cursor::MoveTo(left, top), Print(format!("┌{}┐", "─".repeat(width + 2))))?;
+    for (index, line) in lines.iter().enumerate() {
+        let padding = " ".repeat(width - display_width(line));
+        queue!(stdout, cursor::MoveTo(left, top + 1 + (index as u16)), Print(format!("│ {}{} │", line, padding)))?;
+    }
+    queue!(
+        stdout,
+        cursor::MoveTo(left, top + 1 + (lines.len() as u16)),
+        Print(format!("└{}┘", "─".repeat(width + 2)))
+    )?;
+    stdout.flush()?;
+
+    loop {
+        if let Event::Key(key_event) = read()? {
+            if key_event.kind == KeyEventKind::Press {
+                break;
+            }
+        }
+    }
+    Ok(())
 }
 
 // 定义 display_current_position 函数
@@ -1345,18 +2116,22 @@ fn display_current_position(
     stdout: &mut io::Stdout,
     current_row: usize,
     current_pos: usize,
+    (cursor_column, cursor_row): (u16, u16),
+    status_row: u16,
     last_row: &mut Option<usize>,
     last_pos: &mut Option<usize>
 ) -> io::Result<()> {
     // 只有当行号或列号发生变化时才更新显示
     if Some(current_row) != *last_row || Some(current_pos) != *last_pos {
-        let cursor_position = (20 + 9 + current_pos) as u16; // 20 是 output_width，9 是额外的偏移
         let mut x_position = 83; // 默认位置
 
         // 根据 current_row 和 current_pos 动态调整 x_position
         if current_row >= 9 {
             x_position -= 1; // 减少 1
         }
+        if current_row >= 99 {
+            x_position -= 1;
+        }
         if current_pos >= 9 {
             x_position -= 1; // 再减少 1
         }
@@ -1364,9 +2139,9 @@ fn display_current_position(
         // 清除原来的行
         execute!(
             stdout,
-            cursor::MoveTo(x_position, 26),
+            cursor::MoveTo(x_position, status_row),
             Print(format!("  [{},{}]", current_row + 1, current_pos + 1)),
-            cursor::MoveTo(cursor_position, (current_row + 3) as u16) // 将光标移回输入框
+            cursor::MoveTo(cursor_column, cursor_row) // 将光标移回输入框
         )?;
 
         // 更新最后显示的位置
@@ -1408,13 +2183,13 @@ R0 = ""
 "#;
 
         fs::write(filename, initial_content)?;
-        return Ok((vec!["".to_string(); 20], vec![])); // 修改此处为20
+        return Ok((vec!["".to_string(); ROW_COUNT], vec![]));
     }
 
     let mut content = fs::read_to_string(filename)?;
     let mut value: Value = toml
         ::from_str(&content)
-        .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?;
+        .map_err(|e| io::Error::other(e.to_string()))?;
 
     if let Value::Table(ref mut table) = value {
         if !table.contains_key("home") {
@@ -1443,49 +2218,24 @@ R0 = ""
             table.insert("home".to_string(), Value::Table(initial_0_section));
             content = toml
                 ::to_string(&value)
-                .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?;
+                .map_err(|e| io::Error::other(e.to_string()))?;
             fs::write(filename, content.clone())?;
         }
     }
 
     let value: Value = toml
         ::from_str(&content)
-        .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?;
-    let mut inputs = vec!["".to_string(); 20]; // 修改此处为20
+        .map_err(|e| io::Error::other(e.to_string()))?;
+    let mut inputs = vec!["".to_string(); ROW_COUNT];
     let mut additional_lines = vec![];
 
     if let Value::Table(table) = value {
         if let Some(Value::Table(input_table)) = table.get("home") {
-            for (key, value) in input_table {
-                if let Value::String(input_string) = value {
-                    let index = match key.as_str() {
-                        "A" => 0,
-                        "B" => 1,
-                        "C" => 2,
-                        "D" => 3,
-                        "E" => 4,
-                        "F" => 5,
-                        "G" => 6,
-                        "H" => 7,
-                        "I" => 8,
-                        "J" => 9,
-                        "K" => 10,
-                        "L" => 11,
-                        "M" => 12,
-                        "N" => 13,
-                        "O" => 14,
-                        "P" => 15,
-                        "Q" => 16,
-                        "R" => 17,
-                        "S" => 18,
-                        "T" => 19,
-                        _ => {
-                            continue;
-                        }
-                    };
-                    inputs[index] = input_string.clone();
-                }
-            }
+            let commands: HashMap<String, String> = input_table
+                .iter()
+                .filter_map(|(key, value)| value.as_str().map(|v| (key.clone(), v.to_string())))
+                .collect();
+            fill_inputs_from_section(&mut inputs, &commands);
         }
         if let Some(Value::Table(remarks_table)) = table.get("remarks") {
             for (_key, value) in remarks_table {
@@ -1520,276 +2270,27 @@ fn save_inputs_to_file(
             .unwrap_or(Value::Table(toml::map::Map::new()));
         if let Value::Table(ref mut input_table) = input_table {
             for (i, input) in inputs.iter().enumerate() {
-                let label = (b'A' + (i as u8)) as char;
+                let label = row_label(i);
                 if !input.is_empty() {
-                    input_table.insert(label.to_string(), Value::String(input.clone()));
+                    input_table.insert(label, Value::String(input.clone()));
                 } else {
-                    input_table.remove(&label.to_string());
+                    input_table.remove(&label);
                 }
             }
         }
         table.insert(section.to_string(), input_table);
     }
 
-    let toml_string = toml::to_string(&value).map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+    let toml_string = toml::to_string(&value).map_err(io::Error::other)?;
     fs::write(filename, toml_string)?;
     Ok(())
 }
 
-/// 评估和求解输入中提供的数学表达式或方程
-fn evaluate_and_solve(
-    input: &str,
-    variables: &HashMap<String, String>,
-    current_row: usize,
-) -> Result<String, String> {
-    // Special handling for `z` in R-T input rows
-    if (17..=19).contains(&current_row) && input.trim().eq_ignore_ascii_case("z") {
-        let global_sum = GLOBAL_SUM.lock().unwrap();
-        return Ok(format_with_thousands_separator(*global_sum));
-    }
-
-    // Direct command handling
-    if input.starts_with("fc:") {
-        return Ok("Import from cfg file".to_string());
-    }
-
-    if input.to_lowercase().starts_with("s:") {
-        return Ok("Qalculate!".to_string());
-    }
-
-    // Remove thousands separators and strip comments
-    let input_without_commas = remove_thousands_separator(input);
-    let input_without_comment = match input_without_commas.find('#') {
-        Some(pos) => &input_without_commas[..pos],
-        None => &input_without_commas,
-    };
-
-    let parts: Vec<&str> = input_without_comment.split('=').collect();
-
-    if parts.len() == 2 {
-        let lhs = parts[0].replace(" ", "").replace("X", "x");
-        let rhs = parts[1].replace(" ", "").replace("X", "x");
-
-        let lhs_replaced = replace_variables(lhs.clone(), variables).replace("/", "*1.0/");
-        let rhs_replaced = replace_variables(rhs.clone(), variables).replace("/", "*1.0/");
-
-        // Handle `x` in denominator
-        if lhs.contains('/') {
-            let split: Vec<&str> = lhs.split('/').collect();
-            if split.len() == 2 && split[1].contains('x') {
-                let numerator = eval(&replace_percentage(split[0]))
-                    .map_err(|_| "Invalid numerator.".to_string())?
-                    .as_number()
-                    .unwrap_or(0.0);
-
-                let rhs_value = eval(&replace_percentage(&rhs_replaced))
-                    .map_err(|_| "Invalid RHS.".to_string())?
-                    .as_number()
-                    .unwrap_or(0.0);
-
-                if rhs_value == 0.0 {
-                    return Err("Division by zero".to_string());
-                }
-
-                let result = numerator / rhs_value;
-                return Ok(format_with_thousands_separator(result));
-            }
-        }
-
-        // Handle linear equations with `x`
-        let coefficient = eval(&replace_percentage(&lhs_replaced.replace("x", "1.0")))
-            .map_err(|_| "Invalid equation.".to_string())?
-            .as_number()
-            .unwrap_or(0.0);
-        let lhs_value = eval(&replace_percentage(&lhs_replaced.replace("x", "0.0")))
-            .map_err(|_| "Invalid equation.".to_string())?
-            .as_number()
-            .unwrap_or(0.0);
-        let rhs_value = eval(&replace_percentage(&rhs_replaced))
-            .map_err(|_| "Invalid RHS.".to_string())?
-            .as_number()
-            .unwrap_or(0.0);
-
-        if coefficient == 0.0 {
-            return Err("Invalid equation: coefficient of x is zero".to_string());
-        }
-
-        let result = (rhs_value - lhs_value) / coefficient;
-        return Ok(format_with_thousands_separator(result));
-    } else if parts.len() == 1 {
-        let mut expression = replace_variables(parts[0].replace(" ", ""), variables).replace("/", "*1.0/");
-
-        // Handle `z` in expressions
-        if expression.contains("z") {
-            let global_sum = GLOBAL_SUM.lock().unwrap();
-            expression = expression.replace("z", &global_sum.to_string());
-        }
-
-        match eval(&replace_percentage(&expression)) {
-            Ok(result) => {
-                let formatted_result = format_with_thousands_separator(result.as_number().unwrap_or(0.0));
-                Ok(formatted_result)
-            }
-            Err(_) => Err("Invalid mathematical expression.".to_string()),
-        }
-    } else {
-        Err("Invalid input format. Use a linear equation 'a*x + b = c' or a mathematical expression.".to_string())
-    }
-}
-
-/// 将数学表达式中的百分比（例如 `50%`）替换为其小数等价物（例如 `0.5`）
-fn replace_percentage(expression: &str) -> String {
-    let re = Regex::new(r"(\d+(\.\d+)?)%").unwrap();
-    re.replace_all(expression, r"$1 * 0.01").to_string()
-}
-
-/// 将数学表达式中的变量名替换为给定变量映射中的相应值
-fn replace_variables(expression: String, variables: &HashMap<String, String>) -> String {
-    let mut replaced_expression = expression.to_lowercase();
-    let current_row = (b'A' + (variables.len() as u8)) as char;
-    for (var, value) in variables {
-        if var.to_lowercase() == expression.trim().to_lowercase() {
-            if var.to_lowercase().chars().next().unwrap() == current_row {
-                return "Error: Variable self-reference detected".to_string();
-            }
-        }
-        let cleaned_value = remove_thousands_separator(value);
-        let pattern = format!(r"\b{}\b", var.to_lowercase());
-        let regex = Regex::new(&pattern).unwrap();
-        replaced_expression = regex
-            .replace_all(&replaced_expression, cleaned_value.as_str())
-            .to_string();
-    }
-    replaced_expression
-}
-
-/// 计算结果列表中有效数值结果的总和和数量
-fn calculate_sum_and_count(results: &[String]) -> (f64, usize) {
-    let mut sum = 0.0;
-    let mut count = 0;
-    for result in results.iter().take(17) {
-        let cleaned_result = remove_thousands_separator(result);
-        match cleaned_result.parse::<f64>() {
-            Ok(val) => {
-                sum += val;
-                count += 1;
-            }
-            Err(_) => {}
-        }
-    }
-
-    {
-        let mut global_sum = GLOBAL_SUM.lock().unwrap();
-        let mut global_count = GLOBAL_COUNT.lock().unwrap();
-        *global_sum = sum;
-        *global_count = count;
-    }
-
-    (sum, count)
-}
-
-/// 格式化数值, 在数值中添加千位分隔符以提高可读性
-fn format_with_thousands_separator(value: f64) -> String {
-    let decimal_places = *DECIMAL_PLACES.lock().unwrap(); // 获取全局控制的小数位数
-
-    // 判断是否为整数，若是整数则不保留小数位
-    if value.fract() == 0.0 {
-        // 如果是整数，直接返回整数部分
-        let formatted_int = format!("{}", value.trunc() as i64); // 转换为整数形式
-        return formatted_int
-            .chars()
-            .rev()
-            .collect::<Vec<_>>()
-            .chunks(3)
-            .map(|chunk| chunk.iter().collect::<String>())
-            .collect::<Vec<_>>()
-            .join(",")
-            .chars()
-            .rev()
-            .collect::<String>();
-    }
-
-    // 如果不是整数，根据 decimal_places 先将小数部分转为字符串
-    let formatted_value = format!("{:.1$}", value, decimal_places); // 根据全局变量保留小数位
-    let parts: Vec<&str> = formatted_value.split('.').collect();
-    let int_part = parts[0];
-    let dec_part = parts.get(1).unwrap_or(&"");
-
-    // 移除小数部分末尾的多余零
-    let dec_part_trimmed = dec_part.trim_end_matches('0');
-
-    // 如果没有小数位，返回整数部分
-    if dec_part_trimmed.is_empty() {
-        return int_part
-            .chars()
-            .rev()
-            .collect::<Vec<_>>()
-            .chunks(3)
-            .map(|chunk| chunk.iter().collect::<String>())
-            .collect::<Vec<_>>()
-            .join(",")
-            .chars()
-            .rev()
-            .collect::<String>();
-    }
-
-    // 如果有小数位，返回整数部分加上处理后的小数部分
-    let formatted_int = int_part
-        .chars()
-        .rev()
-        .collect::<Vec<_>>()
-        .chunks(3)
-        .map(|chunk| chunk.iter().collect::<String>())
-        .collect::<Vec<_>>()
-        .join(",")
-        .chars()
-        .rev()
-        .collect::<String>();
-
-    format!("{}.{}", formatted_int, dec_part_trimmed)
-}
-
-/// 移除格式化数值中的千位分隔符以便进一步处理
-fn remove_thousands_separator(value: &str) -> String {
-    value.replace(",", "")
-}
-
 /// 循环切换 section
 fn load_section(section: &str, inputs: &mut Vec<String>, func_toml_path: &Path) {
-    if let Ok((func_map, _, _, _, _)) = load_func_commands_from_file(func_toml_path) {
-        if let Some(commands) = func_map.get(section) {
-            for input in inputs.iter_mut().take(20) {
-                // 修改此处为20
-                input.clear();
-            }
-            for (input_key, input_value) in commands {
-                let index = match input_key.as_str() {
-                    "A" => 0,
-                    "B" => 1,
-                    "C" => 2,
-                    "D" => 3,
-                    "E" => 4,
-                    "F" => 5,
-                    "G" => 6,
-                    "H" => 7,
-                    "I" => 8,
-                    "J" => 9,
-                    "K" => 10,
-                    "L" => 11,
-                    "M" => 12,
-                    "N" => 13,
-                    "O" => 14,
-                    "P" => 15,
-                    "Q" => 16,
-                    "R" => 17,
-                    "S" => 18,
-                    "T" => 19,
-                    _ => {
-                        continue;
-                    }
-                };
-                inputs[index] = input_value.to_string();
-            }
+    if let Ok(config) = load_func_commands_from_file(func_toml_path) {
+        if let Some(commands) = config.func_map.get(section) {
+            fill_inputs_from_section(inputs, commands);
         }
     } else {
         eprintln!("Failed to reload .func.toml");
@@ -1835,11 +2336,8 @@ fn handle_page_up(
     current_pos: &mut usize
 ) {
     // 重新加载 .func.toml 文件
-    if
-        let Ok((new_func_map, _new_const_map, _custom_color, _custom_attribute, _)) =
-            load_func_commands_from_file(func_toml_path)
-    {
-        *func_map = new_func_map;
+    if let Ok(config) = load_func_commands_from_file(func_toml_path) {
+        *func_map = config.func_map;
     } else {
         eprintln!("Failed to reload .func.toml");
     }
@@ -1868,11 +2366,8 @@ fn handle_page_down(
     current_pos: &mut usize
 ) {
     // 重新加载 .func.toml 文件
-    if
-        let Ok((new_func_map, _new_const_map, _custom_color, _custom_attribute, _)) =
-            load_func_commands_from_file(func_toml_path)
-    {
-        *func_map = new_func_map;
+    if let Ok(config) = load_func_commands_from_file(func_toml_path) {
+        *func_map = config.func_map;
     } else {
         eprintln!("Failed to reload .func.toml");
     }
@@ -1891,6 +2386,24 @@ fn handle_page_down(
     stdout.flush().unwrap();
 }
 
+/// 执行同目录下的 rate 程序获取汇率
+fn execute_rate_command() -> Result<String, String> {
+    let exe_path = env::current_exe().unwrap();
+    let exe_dir = exe_path.parent().unwrap();
+    let command_path = if cfg!(target_os = "windows") {
+        exe_dir.join("rate.exe")
+    } else {
+        exe_dir.join("./rate")
+    };
+    match std::process::Command::new(command_path).output() {
+        Ok(output) => {
+            let result = String::from_utf8_lossy(&output.stdout);
+            Ok(result.trim().to_string())
+        }
+        Err(_) => Err("The rate command was not found!".to_string()),
+    }
+}
+
 /// 执行 Qalculate! 命令
 fn execute_qalc_command(command: &str) -> Result<String, String> {
     let output = if cfg!(target_os = "windows") {
@@ -1938,6 +2451,9 @@ fn undo(
                 break;
             }
         }
+        // 撤销前后的行数可能不同
+        *current_row = (*current_row).min(inputs.len() - 1);
+        *current_pos = (*current_pos).min(inputs[*current_row].len());
     }
 }
 
@@ -1967,35 +2483,14 @@ fn add_new_section_to_file(section_name: &str, func_toml_path: &Path) -> Result<
     if let Value::Table(ref mut table) = value {
         if !table.contains_key(section_name) {
             let mut new_section = toml::map::Map::new();
-            for key in [
-                "A",
-                "B",
-                "C",
-                "D",
-                "E",
-                "F",
-                "G",
-                "H",
-                "I",
-                "J",
-                "K",
-                "L",
-                "M",
-                "N",
-                "O",
-                "P",
-                "Q",
-                "R",
-                "S",
-                "T",
-            ].iter() {
-                new_section.insert(key.to_string(), Value::String("".to_string()));
+            for key in (0..ROW_COUNT).map(row_label) {
+                new_section.insert(key, Value::String("".to_string()));
             }
             table.insert(section_name.to_string(), Value::Table(new_section));
         }
     }
 
-    let toml_string = toml::to_string(&value).map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+    let toml_string = toml::to_string(&value).map_err(io::Error::other)?;
     fs::write(func_toml_path, toml_string)?;
     Ok(())
 }
@@ -2013,7 +2508,7 @@ fn delete_section_from_file(section_name: &str, func_toml_path: &Path) -> Result
         table.remove(section_name);
     }
 
-    let toml_string = toml::to_string(&value).map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+    let toml_string = toml::to_string(&value).map_err(io::Error::other)?;
     fs::write(func_toml_path, toml_string)?;
     Ok(())
 }
@@ -2037,7 +2532,7 @@ fn clone_section_in_file(
         }
     }
 
-    let toml_string = toml::to_string(&value).map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+    let toml_string = toml::to_string(&value).map_err(io::Error::other)?;
     fs::write(func_toml_path, toml_string)?;
     Ok(())
 }
@@ -2082,38 +2577,43 @@ fn rename_section_in_file(
         }
     }
 
-    let toml_string = toml::to_string(&value).map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+    let toml_string = toml::to_string(&value).map_err(io::Error::other)?;
     fs::write(func_toml_path, toml_string)?;
     Ok(())
 }
 
 /// 格式化数学表达式，在运算符前后添加一个空格，并移除多余的空格
 fn format_math_expression(expression: &str) -> String {
-    let re = Regex::new(r"\s*([+\-*/=])\s*").unwrap();
+    // 日期和时间字面量 `2026-10-17`、`2026-10-17T14:30` 中的 `-` 不是运算符, 原样保留
+    let re = Regex::new(r"\b(\d{4}-\d{1,2}-\d{1,2}(?:T\d{1,2}:\d{2}(?::\d{2})?)?)\b|\s*([+\-*/=])\s*").unwrap();
+    let format = |text: &str| {
+        re.replace_all(text, |caps: &Captures| {
+            match caps.get(1) {
+                Some(date) => date.as_str().to_string(),
+                None => format!(" {} ", &caps[2]),
+            }
+        }).to_string()
+    };
 
     // Split the expression at the first occurrence of '#'
     if let Some((before_comment, comment)) = expression.split_once('#') {
-        let formatted_before_comment = re.replace_all(before_comment, " $1 ").to_string();
+        let formatted_before_comment = format(before_comment);
         // Return combined result with the comment part unchanged
         format!("{}#{}", formatted_before_comment, comment)
     } else {
         // If there's no '#', format the entire expression
-        re.replace_all(expression, " $1 ").to_string()
+        format(expression)
     }
 }
 
 /// 对齐 # 并更新光标位置
-fn align_hash_comments(inputs: &mut Vec<String>, current_row: &mut usize, current_pos: &mut usize) {
+fn align_hash_comments(inputs: &mut [String], current_row: &mut usize, current_pos: &mut usize) {
     // 找到所有输入框中第一个非行头的 `#` 的最大位置
     let max_hash_pos = inputs
         .iter()
         .filter_map(|input| {
             let hash_pos = input.find('#');
-            if let Some(pos) = hash_pos {
-                if pos > 0 { Some(pos) } else { None }
-            } else {
-                None
-            }
+            hash_pos.filter(|&pos| pos > 0)
         })
         .max()
         .unwrap_or(0);
@@ -2137,7 +2637,7 @@ fn align_hash_comments(inputs: &mut Vec<String>, current_row: &mut usize, curren
 
 /// F9 删掉 # 之前的空格一次
 fn remove_spaces_before_hash(
-    inputs: &mut Vec<String>,
+    inputs: &mut [String],
     current_row: &mut usize,
     current_pos: &mut usize
 ) {
@@ -2164,7 +2664,7 @@ fn remove_spaces_before_hash(
 /// Tab 单字跳转
 fn jump_to_input_box(
     stdout: &mut io::Stdout,
-    inputs: &Vec<String>,
+    inputs: &[String],
     current_row: &mut usize,
     current_pos: &mut usize
 ) -> io::Result<()> {
@@ -2172,32 +2672,52 @@ fn jump_to_input_box(
     execute!(stdout, cursor::MoveTo(18, 1), Print("Jump to = "))?;
     stdout.flush()?;
 
-    if
-        let Event::Key(
-            KeyEvent {
-                code: KeyCode::Char(c),
-                modifiers: KeyModifiers::NONE,
-                kind: KeyEventKind::Press,
-                ..
-            },
-        ) = read()?
-    {
-        if c.is_ascii_alphabetic() && c.to_ascii_uppercase() <= 'T' {
-            let target_row = ((c.to_ascii_uppercase() as u8) - b'A') as usize;
-            if target_row < inputs.len() {
-                *current_row = target_row;
-                *current_pos = inputs[*current_row].len();
+    if let Some(c) = read_letter()? {
+        let mut label = c.to_ascii_uppercase().to_string();
+        // 超过 20 行时可以继续输入第二个字母, 例如 AB, 其他按键表示只跳转到单字母行
+        if inputs.len() > ROW_COUNT {
+            execute!(stdout, Print(&label))?;
+            stdout.flush()?;
+            if let Some(c) = read_letter()? {
+                label.push(c.to_ascii_uppercase());
             }
         }
+        if let Some(target_row) = row_index(&label).filter(|&row| row < inputs.len()) {
+            *current_row = target_row;
+            *current_pos = inputs[*current_row].len();
+        }
     }
     //execute!(stdout, cursor::Hide)?;
     execute!(stdout, cursor::Hide, cursor::MoveTo(18, 1), Clear(ClearType::CurrentLine))?;
     Ok(())
 }
 
+/// 读取一个字母按键, 忽略按键释放事件, 其他按键返回 None
+fn read_letter() -> io::Result<Option<char>> {
+    loop {
+        match read()? {
+            Event::Key(KeyEvent { kind: KeyEventKind::Release, .. }) => {
+                continue;
+            }
+            Event::Key(
+                KeyEvent {
+                    code: KeyCode::Char(c),
+                    modifiers: KeyModifiers::NONE | KeyModifiers::SHIFT,
+                    ..
+                },
+            ) if c.is_ascii_alphabetic() => {
+                return Ok(Some(c));
+            }
+            _ => {
+                return Ok(None);
+            }
+        }
+    }
+}
+
 /// 移动光标到下一个单词后
 fn move_cursor_to_next_word(
-    inputs: &Vec<String>,
+    inputs: &[String],
     current_row: &mut usize,
     current_pos: &mut usize
 ) {
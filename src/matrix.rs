@@ -0,0 +1,464 @@
+use crate::aggregate::Aggregate;
+use crate::evaluator::{ call_function, call_list_function, Number };
+use crate::format::{ format_with_thousands_separator, parse_number, remove_thousands_separator };
+use crate::parser::BinaryOp;
+use crate::solver::solve_linear_system;
+
+/// 矩阵函数及其参数个数范围
+pub const FUNCTIONS: [(&str, usize, usize); 8] = [
+    ("transpose", 1, 1),
+    ("inverse", 1, 1),
+    ("det", 1, 1),
+    ("trace", 1, 1),
+    // identity(n): n 阶单位矩阵
+    ("identity", 1, 1),
+    // 两个长度相同的向量
+    ("dot", 2, 2),
+    // 两个三维向量, 结果的方向与第一个向量相同
+    ("cross", 2, 2),
+    // solve(A, b): 线性方程组 A*x = b 的解 x
+    ("solve", 2, 2),
+];
+
+/// 是否为矩阵函数
+pub fn is_matrix_function(name: &str) -> bool {
+    FUNCTIONS.iter().any(|(function, _, _)| *function == name)
+}
+
+/// 按行存储的实数矩阵, 向量为一行或一列的矩阵, 数值为 1×1 矩阵
+#[derive(Debug, Clone, PartialEq)]
+pub struct Matrix {
+    pub rows: usize,
+    pub cols: usize,
+    data: Vec<f64>,
+}
+
+impl Matrix {
+    pub fn scalar(value: f64) -> Matrix {
+        Matrix { rows: 1, cols: 1, data: vec![value] }
+    }
+
+    /// 由各行的元素构造, 各行的元素个数必须相同
+    pub fn from_rows(rows: Vec<Vec<f64>>) -> Result<Matrix, String> {
+        let cols = rows.first().map_or(0, Vec::len);
+        if cols == 0 {
+            return Err("A matrix needs at least one element".to_string());
+        }
+        if rows.iter().any(|row| row.len() != cols) {
+            return Err("Matrix rows must have the same number of elements".to_string());
+        }
+        Ok(Matrix { rows: rows.len(), cols, data: rows.concat() })
+    }
+
+    pub fn identity(n: usize) -> Matrix {
+        let mut data = vec![0.0; n * n];
+        for i in 0..n {
+            data[i * n + i] = 1.0;
+        }
+        Matrix { rows: n, cols: n, data }
+    }
+
+    pub fn get(&self, row: usize, col: usize) -> f64 {
+        self.data[row * self.cols + col]
+    }
+
+    pub fn is_scalar(&self) -> bool {
+        self.data.len() == 1
+    }
+
+    /// 一行或一列
+    pub fn is_vector(&self) -> bool {
+        self.rows == 1 || self.cols == 1
+    }
+
+    pub fn is_square(&self) -> bool {
+        self.rows == self.cols
+    }
+
+    pub fn is_finite(&self) -> bool {
+        self.data.iter().all(|value| value.is_finite())
+    }
+
+    /// `2×3` 形式的大小
+    pub fn shape(&self) -> String {
+        format!("{}×{}", self.rows, self.cols)
+    }
+
+    fn map(&self, f: impl Fn(f64) -> f64) -> Matrix {
+        Matrix { data: self.data.iter().map(|value| f(*value)).collect(), ..self.clone() }
+    }
+
+    pub fn transpose(&self) -> Matrix {
+        let mut data = Vec::with_capacity(self.data.len());
+        for col in 0..self.cols {
+            for row in 0..self.rows {
+                data.push(self.get(row, col));
+            }
+        }
+        Matrix { rows: self.cols, cols: self.rows, data }
+    }
+
+    fn product(&self, other: &Matrix) -> Result<Matrix, String> {
+        if self.cols != other.rows {
+            return Err(format!("Cannot multiply a {} matrix by a {} matrix", self.shape(), other.shape()));
+        }
+        let mut data = Vec::with_capacity(self.rows * other.cols);
+        for row in 0..self.rows {
+            for col in 0..other.cols {
+                data.push((0..self.cols).map(|k| self.get(row, k) * other.get(k, col)).sum());
+            }
+        }
+        Ok(Matrix { rows: self.rows, cols: other.cols, data })
+    }
+
+    fn square(&self, name: &str) -> Result<(), String> {
+        if self.is_square() { Ok(()) } else { Err(format!("`{}` needs a square matrix, found {}", name, self.shape())) }
+    }
+
+    /// 行列式, 以列主元高斯消元计算
+    pub fn determinant(&self) -> Result<f64, String> {
+        self.square("det")?;
+        let n = self.rows;
+        let mut rows: Vec<Vec<f64>> = self.data
+            .chunks(n)
+            .map(<[f64]>::to_vec)
+            .collect();
+        let mut det = 1.0;
+        for col in 0..n {
+            let pivot = (col..n).max_by(|&a, &b| rows[a][col].abs().total_cmp(&rows[b][col].abs())).unwrap_or(col);
+            if rows[pivot][col] == 0.0 {
+                return Ok(0.0);
+            }
+            if pivot != col {
+                rows.swap(col, pivot);
+                det = -det;
+            }
+            det *= rows[col][col];
+            let pivot_row = rows[col].clone();
+            for row in rows.iter_mut().skip(col + 1) {
+                let factor = row[col] / pivot_row[col];
+                for (value, pivot) in row.iter_mut().zip(&pivot_row).skip(col) {
+                    *value -= factor * pivot;
+                }
+            }
+        }
+        Ok(det)
+    }
+
+    /// 逐列求解 A*x = b, 系数矩阵奇异时报错
+    fn solve(&self, constants: &Matrix) -> Result<Matrix, String> {
+        self.square("solve")?;
+        // 向量形式的右端按列处理, 结果保持其方向
+        let (constants, row_vector) = if constants.rows == 1 && self.rows > 1 {
+            (constants.transpose(), true)
+        } else {
+            (constants.clone(), false)
+        };
+        if constants.rows != self.rows {
+            return Err(format!("Cannot solve a {} system for a {} right-hand side", self.shape(), constants.shape()));
+        }
+        let coefficients: Vec<Vec<f64>> = self.data
+            .chunks(self.cols)
+            .map(<[f64]>::to_vec)
+            .collect();
+        let mut columns = Vec::with_capacity(constants.cols);
+        for col in 0..constants.cols {
+            let column = (0..constants.rows).map(|row| constants.get(row, col)).collect();
+            columns.push(solve_linear_system(coefficients.clone(), column).ok_or_else(|| "Matrix is singular".to_string())?);
+        }
+        let solution = Matrix::from_rows(columns)?.transpose();
+        Ok(if row_vector { solution.transpose() } else { solution })
+    }
+
+    pub fn inverse(&self) -> Result<Matrix, String> {
+        self.square("inverse")?;
+        self.solve(&Matrix::identity(self.rows))
+    }
+
+    /// 方阵的整数次幂, 负数次幂为逆矩阵的幂
+    fn power(&self, exponent: f64) -> Result<Matrix, String> {
+        self.square("^")?;
+        if exponent.fract() != 0.0 || exponent.abs() > 1024.0 {
+            return Err("Matrix powers need a whole exponent".to_string());
+        }
+        let base = if exponent < 0.0 { self.inverse()? } else { self.clone() };
+        let mut result = Matrix::identity(self.rows);
+        for _ in 0..(exponent.abs() as usize) {
+            result = result.product(&base)?;
+        }
+        Ok(result)
+    }
+
+    /// 逐元素运算, 两边大小相同或其中一边为数值
+    fn elementwise(&self, other: &Matrix, verb: &str, f: impl Fn(f64, f64) -> f64) -> Result<Matrix, String> {
+        if self.is_scalar() {
+            return Ok(other.map(|value| f(self.data[0], value)));
+        }
+        if other.is_scalar() {
+            return Ok(self.map(|value| f(value, other.data[0])));
+        }
+        if self.rows != other.rows || self.cols != other.cols {
+            return Err(format!("Cannot {} a {} matrix and a {} matrix", verb, self.shape(), other.shape()));
+        }
+        let data = self.data
+            .iter()
+            .zip(&other.data)
+            .map(|(a, b)| f(*a, *b))
+            .collect();
+        Ok(Matrix { data, ..self.clone() })
+    }
+
+    fn vector(&self, name: &str) -> Result<&[f64], String> {
+        if self.is_vector() { Ok(&self.data) } else { Err(format!("`{}` needs vectors, found a {} matrix", name, self.shape())) }
+    }
+}
+
+/// 行变量中保存的写法 `[1 2;3 4]`, 元素以空格分隔, 避免与千位分隔符混淆
+pub fn matrix_text(matrix: &Matrix) -> String {
+    let rows: Vec<String> = matrix.data
+        .chunks(matrix.cols)
+        .map(|row| {
+            row.iter()
+                .map(f64::to_string)
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+        .collect();
+    format!("[{}]", rows.join(";"))
+}
+
+/// 解析行变量中保存的矩阵 `[1 2;3 4]`
+pub fn parse_matrix(text: &str) -> Option<Matrix> {
+    let body = text.trim().strip_prefix('[')?.strip_suffix(']')?;
+    let rows = body
+        .split(';')
+        .map(|row| {
+            row.split_whitespace()
+                .map(|value| value.parse::<f64>().ok())
+                .collect::<Option<Vec<f64>>>()
+        })
+        .collect::<Option<Vec<_>>>()?;
+    Matrix::from_rows(rows).ok()
+}
+
+/// 按小数位数显示的元素, 不带千位分隔符
+fn element_text(value: f64, decimal_places: usize) -> String {
+    remove_thousands_separator(&format_with_thousands_separator(value, decimal_places))
+}
+
+/// 单行显示的向量或矩阵 `[1, 2; 3, 4]`, `columns` 和 `rows` 为元素和行之间的分隔符
+pub fn format_matrix(matrix: &Matrix, decimal_places: usize, columns: &str, rows: &str) -> String {
+    let lines: Vec<String> = matrix.data
+        .chunks(matrix.cols)
+        .map(|row| {
+            row.iter()
+                .map(|value| element_text(*value, decimal_places))
+                .collect::<Vec<_>>()
+                .join(columns)
+        })
+        .collect();
+    format!("[{}]", lines.join(rows))
+}
+
+/// 各列右对齐的多行显示, 用于 TUI 中的矩阵详情
+pub fn matrix_lines(matrix: &Matrix, decimal_places: usize) -> Vec<String> {
+    let texts: Vec<Vec<String>> = matrix.data
+        .chunks(matrix.cols)
+        .map(|row| {
+            row.iter()
+                .map(|value| element_text(*value, decimal_places))
+                .collect()
+        })
+        .collect();
+    let widths: Vec<usize> = (0..matrix.cols)
+        .map(|col| texts.iter().map(|row| row[col].chars().count()).max().unwrap_or(0))
+        .collect();
+    let body: Vec<String> = texts
+        .iter()
+        .map(|row| {
+            row.iter()
+                .zip(&widths)
+                .map(|(text, width)| format!("{:>width$}", text, width = *width))
+                .collect::<Vec<_>>()
+                .join("  ")
+        })
+        .collect();
+    let inner = body.first().map_or(0, |line| line.chars().count());
+    let mut lines = vec![format!("┌ {:inner$} ┐", "")];
+    lines.extend(body.iter().map(|line| format!("│ {} │", line)));
+    lines.push(format!("└ {:inner$} ┘", ""));
+    lines
+}
+
+/// 矩阵运算: `+`、`-` 和 `%` 逐元素计算, `*` 为矩阵乘法, 与数值运算时作用于每个元素
+impl Number for Matrix {
+    fn literal(value: f64, _digits: &str) -> Result<Self, String> {
+        Ok(Matrix::scalar(value))
+    }
+
+    fn parse(text: &str) -> Option<Self> {
+        parse_matrix(text).or_else(|| parse_number(text).map(Matrix::scalar))
+    }
+
+    fn from_f64(value: f64) -> Option<Self> {
+        Some(Matrix::scalar(value))
+    }
+
+    /// 不是 1×1 时为 NaN
+    fn to_f64(self) -> f64 {
+        if self.is_scalar() { self.data[0] } else { f64::NAN }
+    }
+
+    fn binary(op: BinaryOp, lhs: Self, rhs: Self) -> Result<Self, String> {
+        match op {
+            BinaryOp::Add => lhs.elementwise(&rhs, "add", |a, b| a + b),
+            BinaryOp::Sub => lhs.elementwise(&rhs, "subtract", |a, b| a - b),
+            BinaryOp::Mul if lhs.is_scalar() || rhs.is_scalar() => lhs.elementwise(&rhs, "multiply", |a, b| a * b),
+            BinaryOp::Mul => lhs.product(&rhs),
+            BinaryOp::Div if rhs.is_scalar() => lhs.elementwise(&rhs, "divide", |a, b| a / b),
+            BinaryOp::Div => Err("Matrices can only be divided by a number, use `inverse`".to_string()),
+            BinaryOp::Rem => lhs.elementwise(&rhs, "divide", |a, b| a % b),
+            BinaryOp::Pow if lhs.is_scalar() && rhs.is_scalar() => Ok(Matrix::scalar(lhs.data[0].powf(rhs.data[0]))),
+            BinaryOp::Pow if rhs.is_scalar() => lhs.power(rhs.data[0]),
+            BinaryOp::Pow => Err("Matrix exponents must be numbers".to_string()),
+        }
+    }
+
+    fn matrix(rows: Vec<Vec<Self>>) -> Result<Self, String> {
+        let rows = rows
+            .into_iter()
+            .map(|row| {
+                row.into_iter()
+                    .map(|element| {
+                        if element.is_scalar() { Ok(element.data[0]) } else { Err("Matrix elements must be numbers".to_string()) }
+                    })
+                    .collect::<Result<Vec<f64>, String>>()
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Matrix::from_rows(rows)
+    }
+
+    fn neg(self) -> Self {
+        self.map(|value| -value)
+    }
+
+    fn percent(self) -> Self {
+        self.map(|value| value / 100.0)
+    }
+
+    /// 单参数函数作用于每个元素
+    fn call(name: &str, args: &[Self]) -> Result<Self, String> {
+        if name == "polar" {
+            return Err("Matrices cannot hold complex numbers".to_string());
+        }
+        if let [matrix] = args {
+            return Ok(matrix.map(|value| call_function(name, &[value])));
+        }
+        match args.iter().all(Matrix::is_scalar) {
+            true => {
+                let args: Vec<f64> = args
+                    .iter()
+                    .map(|arg| arg.data[0])
+                    .collect();
+                Ok(Matrix::scalar(call_function(name, &args)))
+            }
+            false => Err(format!("`{}` takes numbers, not matrices", name)),
+        }
+    }
+
+    fn call_matrix(name: &str, args: &[Self]) -> Result<Self, String> {
+        let a = &args[0];
+        match name {
+            "transpose" => Ok(a.transpose()),
+            "inverse" => a.inverse(),
+            "det" => a.determinant().map(Matrix::scalar),
+            "trace" => {
+                a.square("trace")?;
+                Ok(Matrix::scalar((0..a.rows).map(|i| a.get(i, i)).sum()))
+            }
+            "identity" => {
+                let n = a.clone().to_f64();
+                if n.fract() != 0.0 || !(1.0..=100.0).contains(&n) {
+                    return Err("`identity` needs a size between 1 and 100".to_string());
+                }
+                Ok(Matrix::identity(n as usize))
+            }
+            "dot" => {
+                let (x, y) = (a.vector(name)?, args[1].vector(name)?);
+                if x.len() != y.len() {
+                    return Err(format!("`dot` needs vectors of the same length, found {} and {}", x.len(), y.len()));
+                }
+                Ok(Matrix::scalar(x.iter().zip(y).map(|(x, y)| x * y).sum()))
+            }
+            "cross" => {
+                let (x, y) = match (a.vector(name)?, args[1].vector(name)?) {
+                    ([x0, x1, x2], [y0, y1, y2]) => ([*x0, *x1, *x2], [*y0, *y1, *y2]),
+                    _ => {
+                        return Err("`cross` needs two vectors with 3 elements".to_string());
+                    }
+                };
+                let data = vec![x[1] * y[2] - x[2] * y[1], x[2] * y[0] - x[0] * y[2], x[0] * y[1] - x[1] * y[0]];
+                Ok(Matrix { data, ..a.clone() })
+            }
+            "solve" => a.solve(&args[1]),
+            _ => Err(format!("Unknown function `{}`", name)),
+        }
+    }
+
+    /// 聚合所有元素, `sum([1, 2, 3])` 为 6
+    fn aggregate(aggregate: Aggregate, values: &[Self]) -> Option<Self> {
+        let values: Vec<f64> = values
+            .iter()
+            .flat_map(|value| value.data.iter().copied())
+            .collect();
+        Some(Matrix::scalar(aggregate.apply(&values)))
+    }
+
+    fn call_lists(name: &str, args: &[Vec<Self>]) -> Result<Self, String> {
+        let args: Vec<Vec<f64>> = args
+            .iter()
+            .map(|values| values.iter().flat_map(|value| value.data.iter().copied()).collect())
+            .collect();
+        Ok(Matrix::scalar(call_list_function(name, &args)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matrix(rows: &[&[f64]]) -> Matrix {
+        Matrix::from_rows(rows.iter().map(|row| row.to_vec()).collect()).unwrap()
+    }
+
+    #[test]
+    fn determinant_and_inverse() {
+        let m = matrix(&[&[1.0, 2.0], &[3.0, 4.0]]);
+        assert_eq!(m.determinant().unwrap(), -2.0);
+        assert_eq!(format_matrix(&m.inverse().unwrap(), 2, ", ", "; "), "[-2, 1; 1.5, -0.5]");
+
+        let singular = matrix(&[&[1.0, 2.0], &[2.0, 4.0]]);
+        assert!(singular.inverse().is_err());
+    }
+
+    #[test]
+    fn product_with_inverse_is_identity() {
+        let m = matrix(&[&[2.0, 0.0, 1.0], &[1.0, 3.0, 2.0], &[1.0, 1.0, 2.0]]);
+        let product = Matrix::binary(BinaryOp::Mul, m.clone(), m.inverse().unwrap()).unwrap();
+        for row in 0..3 {
+            for col in 0..3 {
+                let expected = if row == col { 1.0 } else { 0.0 };
+                assert!((product.get(row, col) - expected).abs() < 1e-12);
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_ragged_rows() {
+        assert!(Matrix::from_rows(vec![vec![1.0, 2.0], vec![3.0]]).is_err());
+        // 行变量中保存的写法
+        let m = matrix(&[&[1.0, 2.0], &[3.0, 4.0]]);
+        assert_eq!(matrix_text(&m), "[1 2;3 4]");
+        assert_eq!(parse_matrix("[1 2;3 4]"), Some(m));
+    }
+}
@@ -0,0 +1,1184 @@
+use std::ops::Range;
+
+use crate::aggregate::Aggregate;
+use crate::dates::{ parse_date, parse_time };
+use crate::engine::row_index;
+use crate::error::EvalError;
+use crate::evaluator::{ accepts_range, function_arity };
+use crate::format::{ split_chinese_unit, NumberFormat };
+use crate::functions::UserFunctions;
+use crate::integer::parse_radix;
+use crate::locale::Locale;
+use crate::units::Unit;
+
+/// 输入中的字符位置区间
+pub type Span = Range<usize>;
+
+#[derive(Debug, Clone, PartialEq)]
+enum TokenKind {
+    Number(f64),
+    /// `4i` 或 `4j`
+    Imaginary(f64),
+    /// 日期 `2026-10-17`, 为天数
+    Date(f64),
+    /// 时长 `14:30`, 为天数
+    Time(f64),
+    Ident(String),
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    Caret,
+    Percent,
+    /// 按位运算 `&`、`|`、`~`、`<<`、`>>`, 异或为 `xor`
+    Amp,
+    Pipe,
+    Tilde,
+    Shl,
+    Shr,
+    /// 极坐标 `5∠53.13`
+    Angle,
+    LParen,
+    RParen,
+    /// 矩阵 `[1, 2; 3, 4]`
+    LBracket,
+    RBracket,
+    Semicolon,
+    Comma,
+    Equals,
+    At,
+    /// 行末的显示格式 `@sci`、`@%`, 数字形式的 `@2` 仍为 `At` 和数值
+    Format(NumberFormat),
+    DotDot,
+    /// 行区间 `A:F`
+    Colon,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Token {
+    kind: TokenKind,
+    span: Span,
+}
+
+/// 二元运算符
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Pow,
+}
+
+/// 按位运算符, 优先级低于加减: `|` < `xor` < `&` < 移位
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BitOp {
+    And,
+    Or,
+    Xor,
+    Shl,
+    Shr,
+}
+
+/// 表达式树的节点, `span` 为该节点在输入中的位置
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expr {
+    pub kind: ExprKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExprKind {
+    /// 数值及其在输入中的写法 (已去掉千位分隔符), 十进制模式按写法精确解析
+    Number(f64, String),
+    /// 虚数 `4i`, 单独的 `i` 是行引用
+    Imaginary(f64),
+    /// 日期字面量 `2026-10-17`, 值为从 1899-12-30 起的天数
+    Date(f64),
+    /// 时长字面量 `14:30`, 值为天数
+    Duration(f64),
+    /// 数值或括号之后的单位, `12 kg` 解析为 `12 * kg`
+    Unit(Unit),
+    /// 单位换算 `A to km`
+    Convert(Box<Expr>, Unit),
+    /// 行引用, 例如 `A`、`ab`
+    Row(usize),
+    /// 未知数或方程组中已求解的变量, 统一为小写
+    Name(String),
+    /// 求和区域的聚合值, 例如 `z`、`avg`
+    Aggregate(Aggregate),
+    /// 后缀百分号, `50%` 即 0.5
+    Percent(Box<Expr>),
+    Neg(Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+    Bitwise(BitOp, Box<Expr>, Box<Expr>),
+    /// 按位取反 `~x`
+    BitNot(Box<Expr>),
+    /// 函数调用, 函数名已去掉 `math::` 前缀
+    Call(String, Vec<Expr>),
+    /// 行区间 `A:F`, 两端都包含在内, 只能作为 `min`、`irr` 等函数的参数
+    Range(Range<usize>),
+    /// 矩阵 `[1, 2; 3, 4]` 的各行元素
+    Matrix(Vec<Vec<Expr>>),
+}
+
+impl Expr {
+    /// 依次访问表达式及其所有子表达式
+    pub fn walk(&self, f: &mut dyn FnMut(&Expr)) {
+        f(self);
+        match &self.kind {
+            ExprKind::Percent(expr) | ExprKind::Neg(expr) | ExprKind::BitNot(expr) | ExprKind::Convert(expr, _) => expr.walk(f),
+            ExprKind::Binary(_, lhs, rhs) | ExprKind::Bitwise(_, lhs, rhs) => {
+                lhs.walk(f);
+                rhs.walk(f);
+            }
+            ExprKind::Call(_, args) => {
+                for arg in args {
+                    arg.walk(f);
+                }
+            }
+            ExprKind::Matrix(rows) => {
+                for element in rows.iter().flatten() {
+                    element.walk(f);
+                }
+            }
+            ExprKind::Number(..) |
+            ExprKind::Imaginary(_) |
+            ExprKind::Date(_) |
+            ExprKind::Duration(_) |
+            ExprKind::Unit(_) |
+            ExprKind::Row(_) |
+            ExprKind::Range(_) |
+            ExprKind::Name(_) |
+            ExprKind::Aggregate(_) => {}
+        }
+    }
+
+    /// 表达式中的未知数, 按首次出现的顺序排列
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = Vec::new();
+        self.walk(
+            &mut (|expr| {
+                if let ExprKind::Name(name) = &expr.kind {
+                    if !names.contains(name) {
+                        names.push(name.clone());
+                    }
+                }
+            })
+        );
+        names
+    }
+}
+
+/// 方程末尾 `@` 之后的提示
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RootHint {
+    /// `@ 2`: 导出第 2 个根
+    Index(usize),
+    /// `@ 0..1`: 只在该区间内求根
+    Interval(f64, f64),
+    /// `@ x = 0.5`: 从该值开始迭代
+    Guess(f64),
+}
+
+/// 一行输入的语法结构
+#[derive(Debug, Clone, PartialEq)]
+pub enum Statement {
+    /// 空行或只有注释的行
+    Empty,
+    /// 表达式及行末的显示格式
+    Expression(Expr, Option<NumberFormat>),
+    Equation {
+        lhs: Expr,
+        rhs: Expr,
+        hint: Option<RootHint>,
+    },
+}
+
+impl Statement {
+    /// 依次访问语句中的所有表达式节点
+    pub fn walk(&self, f: &mut dyn FnMut(&Expr)) {
+        match self {
+            Statement::Empty => {}
+            Statement::Expression(expr, _) => expr.walk(f),
+            Statement::Equation { lhs, rhs, .. } => {
+                lhs.walk(f);
+                rhs.walk(f);
+            }
+        }
+    }
+
+    /// 方程两边的未知数, 按首次出现的顺序排列, 不是方程时为空
+    pub fn unknowns(&self) -> Vec<String> {
+        match self {
+            Statement::Equation { lhs, rhs, .. } => {
+                let mut names = lhs.names();
+                for name in rhs.names() {
+                    if !names.contains(&name) {
+                        names.push(name);
+                    }
+                }
+                names
+            }
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// 解析一行输入, `#` 之后为注释
+///
+/// 函数调用的括号之外, `1,200` 这样的千位分隔数字按一个数处理;
+/// 括号之内的逗号分隔参数, 例如 `max(1, 200)`。
+pub fn parse(input: &str) -> Result<Statement, EvalError> {
+    parse_localized(input, &Locale::default())
+}
+
+/// 按区域的小数点和分组符号解析一行输入, 小数点为逗号时函数参数以 `;` 分隔
+pub fn parse_localized(input: &str, locale: &Locale) -> Result<Statement, EvalError> {
+    parse_with_functions(input, locale, &UserFunctions::default())
+}
+
+/// 与 [`parse_localized`] 相同, 另外可以调用 [functions] 中定义的函数
+pub fn parse_with_functions(input: &str, locale: &Locale, functions: &UserFunctions) -> Result<Statement, EvalError> {
+    let chars: Vec<char> = input.chars().collect();
+    let tokens = tokenize(&chars, locale)?;
+    let end = chars
+        .iter()
+        .position(|c| *c == '#')
+        .unwrap_or(chars.len());
+    let mut parser = Parser { chars: &chars, tokens, pos: 0, end, locale, functions, params: &[] };
+    parser.statement()
+}
+
+/// 解析用户函数的函数体, 使用标准写法, 名称只能是函数的参数
+pub fn parse_function_body(input: &str, params: &[String], functions: &UserFunctions) -> Result<Expr, EvalError> {
+    let chars: Vec<char> = input.chars().collect();
+    let locale = Locale::default();
+    let tokens = tokenize(&chars, &locale)?;
+    if tokens.is_empty() {
+        return Err(EvalError::new("Function body is empty"));
+    }
+    let end = chars.len();
+    let mut parser = Parser { chars: &chars, tokens, pos: 0, end, locale: &locale, functions, params };
+    let body = parser.expression()?;
+    if parser.pos < parser.tokens.len() {
+        return Err(parser.unexpected());
+    }
+    let mut outside = None;
+    body.walk(
+        &mut (|expr| {
+            let is_outside = match &expr.kind {
+                ExprKind::Name(name) => !params.contains(name),
+                ExprKind::Row(_) | ExprKind::Aggregate(_) | ExprKind::Range(_) => true,
+                _ => false,
+            };
+            if is_outside && outside.is_none() {
+                outside = Some(expr.span.clone());
+            }
+        })
+    );
+    match outside {
+        Some(span) => Err(EvalError::at(format!("`{}` is not a parameter", parser.text(&span)), span)),
+        None => Ok(body),
+    }
+}
+
+fn tokenize(chars: &[char], locale: &Locale) -> Result<Vec<Token>, EvalError> {
+    let mut tokens: Vec<Token> = Vec::new();
+    // 每层括号是否为函数调用的括号
+    let mut calls: Vec<bool> = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let start = i;
+        if c == '#' {
+            break;
+        }
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+
+        let is_digit = |index: usize| chars.get(index).is_some_and(char::is_ascii_digit);
+        let is_ident = |index: usize| {
+            chars.get(index).is_some_and(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | 'Ω' | 'μ' | 'µ'))
+        };
+        let kind = if c == '0' && matches!(chars.get(i + 1), Some('x' | 'X' | 'o' | 'O' | 'b' | 'B')) && is_ident(i + 2) {
+            // `0xFF`、`0o17`、`0b1010`, 数字之间可以有 `_`
+            i += 2;
+            while is_ident(i) {
+                i += 1;
+            }
+            let text: String = chars[start..i].iter().collect();
+            match parse_radix(&text).filter(|value| *value <= u128::from(u64::MAX)) {
+                Some(value) => TokenKind::Number(value as f64),
+                None => {
+                    return Err(EvalError::at(format!("Invalid number `{}`", text), start..i));
+                }
+            }
+        } else if let Some((kind, end)) = date_or_time(chars, i) {
+            i = end;
+            match kind {
+                Some(kind) => kind,
+                None => {
+                    let text: String = chars[start..end].iter().collect();
+                    return Err(EvalError::at(format!("Invalid date or time `{}`", text), start..end));
+                }
+            }
+        } else if c.is_ascii_digit() || (c == '.' && locale.decimal == '.' && is_digit(i + 1)) {
+            let mut text = String::new();
+            while is_digit(i) {
+                text.push(chars[i]);
+                i += 1;
+            }
+            // 分组符号: 其后恰好一组数字 (默认三位), 与参数分隔符相同时只在函数调用之外有效
+            let grouping = locale.group != locale.argument_separator() || calls.last() != Some(&true);
+            let group_digits = |index: usize| {
+                (index + 1..)
+                    .take_while(|index| is_digit(*index))
+                    .count()
+            };
+            while
+                grouping &&
+                !text.is_empty() &&
+                chars.get(i) == Some(&locale.group) &&
+                locale.group_size_ok(group_digits(i))
+            {
+                let size = group_digits(i);
+                text.extend(&chars[i + 1..i + 1 + size]);
+                i += 1 + size;
+            }
+            let decimal = chars.get(i) == Some(&locale.decimal) && match locale.decimal {
+                '.' => chars.get(i + 1) != Some(&'.'),
+                _ => is_digit(i + 1),
+            };
+            if decimal {
+                text.push('.');
+                i += 1;
+                while is_digit(i) {
+                    text.push(chars[i]);
+                    i += 1;
+                }
+            }
+            if matches!(chars.get(i), Some('e' | 'E')) {
+                let sign = matches!(chars.get(i + 1), Some('+' | '-'));
+                let digits = if sign { i + 2 } else { i + 1 };
+                if is_digit(digits) {
+                    text.extend(&chars[i..digits]);
+                    i = digits;
+                    while is_digit(i) {
+                        text.push(chars[i]);
+                        i += 1;
+                    }
+                }
+            }
+            // 数字后紧跟的 `万`、`亿` 作为十的幂次, `3.5万` 即 `3.5e4`
+            let mut exponent = 0;
+            while !text.contains(['e', 'E']) && matches!(chars.get(i), Some('万' | '亿')) {
+                exponent += if chars[i] == '万' { 4 } else { 8 };
+                i += 1;
+            }
+            if exponent > 0 {
+                text.push_str(&format!("e{}", exponent));
+            }
+            let value = match text.parse::<f64>() {
+                Ok(value) => value,
+                Err(_) => {
+                    return Err(EvalError::at(format!("Invalid number `{}`", text), start..i));
+                }
+            };
+            // 数字后紧跟的 `i` 或 `j` 为虚数单位
+            if matches!(chars.get(i), Some('i' | 'j')) && !is_ident(i + 1) {
+                i += 1;
+                TokenKind::Imaginary(value)
+            } else {
+                TokenKind::Number(value)
+            }
+        } else if c.is_ascii_alphabetic() || matches!(c, '_' | 'Ω' | 'μ' | 'µ') {
+            while
+                is_ident(i) ||
+                (chars.get(i) == Some(&':') &&
+                    chars.get(i + 1) == Some(&':') &&
+                    chars.get(i + 2).is_some_and(char::is_ascii_alphabetic))
+            {
+                i += if chars[i] == ':' { 2 } else { 1 };
+            }
+            TokenKind::Ident(chars[start..i].iter().collect())
+        } else {
+            i += 1;
+            match c {
+                '+' => TokenKind::Plus,
+                '-' => TokenKind::Minus,
+                '*' => TokenKind::Star,
+                '/' => TokenKind::Slash,
+                '^' => TokenKind::Caret,
+                '%' => TokenKind::Percent,
+                '&' => TokenKind::Amp,
+                '|' => TokenKind::Pipe,
+                '~' => TokenKind::Tilde,
+                '<' if chars.get(i) == Some(&'<') => {
+                    i += 1;
+                    TokenKind::Shl
+                }
+                '>' if chars.get(i) == Some(&'>') => {
+                    i += 1;
+                    TokenKind::Shr
+                }
+                '∠' => TokenKind::Angle,
+                '(' => {
+                    let is_call = matches!(
+                        tokens.last(),
+                        Some(Token { kind: TokenKind::Ident(_), .. })
+                    );
+                    calls.push(is_call);
+                    TokenKind::LParen
+                }
+                ')' => {
+                    calls.pop();
+                    TokenKind::RParen
+                }
+                // 方括号内与函数调用一样, 逗号为元素的分隔符
+                '[' => {
+                    calls.push(true);
+                    TokenKind::LBracket
+                }
+                ']' => {
+                    calls.pop();
+                    TokenKind::RBracket
+                }
+                ',' => TokenKind::Comma,
+                ';' if locale.argument_separator() == ';' => TokenKind::Comma,
+                ';' => TokenKind::Semicolon,
+                '=' => TokenKind::Equals,
+                '@' => {
+                    // `@` 之后直到注释为格式名称时整体作为一个记号
+                    let end = chars[i..]
+                        .iter()
+                        .position(|c| *c == '#')
+                        .map_or(chars.len(), |offset| i + offset);
+                    let directive: String = chars[i..end].iter().collect();
+                    let named = directive
+                        .trim_start()
+                        .starts_with(|c: char| c.is_alphabetic() || c == '%');
+                    match NumberFormat::from_directive(&directive) {
+                        Some(format) if named => {
+                            i = end;
+                            TokenKind::Format(format)
+                        }
+                        _ => TokenKind::At,
+                    }
+                }
+                '.' if chars.get(i) == Some(&'.') => {
+                    i += 1;
+                    TokenKind::DotDot
+                }
+                ':' => TokenKind::Colon,
+                _ => {
+                    return Err(EvalError::at(format!("Unexpected character `{}`", c), start..i));
+                }
+            }
+        };
+        tokens.push(Token { kind, span: start..i });
+    }
+    Ok(tokens)
+}
+
+/// `2026-10-17`、`2026-10-17T14:30` 或 `14:30` 形式的字面量及其结束位置
+///
+/// 形式正确但不是有效的日期或时间 (如 `2026-02-30`) 时记号为 None; 其后紧跟数字或字母时不是字面量。
+fn date_or_time(chars: &[char], start: usize) -> Option<(Option<TokenKind>, usize)> {
+    let digits = |from: usize| {
+        (from..)
+            .take_while(|index| chars.get(*index).is_some_and(char::is_ascii_digit))
+            .count()
+    };
+    // `14:30` 或 `14:30:05` 的结束位置
+    let time_end = |from: usize| {
+        let hours = digits(from);
+        if !(1..=2).contains(&hours) || chars.get(from + hours) != Some(&':') || digits(from + hours + 1) != 2 {
+            return None;
+        }
+        let end = from + hours + 3;
+        if chars.get(end) == Some(&':') && digits(end + 1) == 2 { Some(end + 3) } else { Some(end) }
+    };
+
+    let (is_date, end) = if digits(start) == 4 && chars.get(start + 4) == Some(&'-') {
+        let month = digits(start + 5);
+        let day_start = start + 6 + month;
+        let day = digits(day_start);
+        if !(1..=2).contains(&month) || chars.get(start + 5 + month) != Some(&'-') || !(1..=2).contains(&day) {
+            return None;
+        }
+        let end = day_start + day;
+        match chars.get(end) {
+            Some('T') => (true, time_end(end + 1)?),
+            _ => (true, end),
+        }
+    } else {
+        (false, time_end(start)?)
+    };
+    if chars.get(end).is_some_and(char::is_ascii_alphanumeric) {
+        return None;
+    }
+    let text: String = chars[start..end].iter().collect();
+    let kind = if is_date { parse_date(&text).map(TokenKind::Date) } else { parse_time(&text).map(TokenKind::Time) };
+    Some((kind, end))
+}
+
+struct Parser<'a> {
+    chars: &'a [char],
+    tokens: Vec<Token>,
+    pos: usize,
+    /// 注释之前的长度, 用于输入意外结束时的错误位置
+    end: usize,
+    locale: &'a Locale,
+    functions: &'a UserFunctions,
+    /// 正在解析的用户函数的参数, 优先于行标签和单位
+    params: &'a [String],
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&TokenKind> {
+        self.tokens.get(self.pos).map(|token| &token.kind)
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<&TokenKind> {
+        self.tokens.get(self.pos + offset).map(|token| &token.kind)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn eat(&mut self, kind: &TokenKind) -> Option<Span> {
+        if self.peek() == Some(kind) { self.next().map(|token| token.span) } else { None }
+    }
+
+    fn text(&self, span: &Span) -> String {
+        self.chars[span.clone()].iter().collect()
+    }
+
+    /// 当前位置的 "意外" 错误, 已到末尾时指向输入之后的位置
+    fn unexpected(&self) -> EvalError {
+        match self.tokens.get(self.pos) {
+            Some(token) => EvalError::at(format!("Unexpected `{}`", self.text(&token.span)), token.span.clone()),
+            None => EvalError::at("Unexpected end of expression", self.end..self.end + 1),
+        }
+    }
+
+    fn statement(&mut self) -> Result<Statement, EvalError> {
+        if self.tokens.is_empty() {
+            return Ok(Statement::Empty);
+        }
+        let mut lhs = self.expression()?;
+        if self.peek() == Some(&TokenKind::Ident("to".to_string())) {
+            lhs = self.conversion(lhs)?;
+        }
+        let statement = if self.eat(&TokenKind::Equals).is_some() {
+            let rhs = self.expression()?;
+            if let Some(Token { kind: TokenKind::Format(_), span }) = self.tokens.get(self.pos) {
+                return Err(EvalError::at("Format directives are only valid in expressions", span.clone()));
+            }
+            let hint = match self.eat(&TokenKind::At) {
+                Some(_) => Some(self.hint()?),
+                None => None,
+            };
+            Statement::Equation { lhs, rhs, hint }
+        } else {
+            let format = self.format()?;
+            Statement::Expression(lhs, format)
+        };
+        if self.pos < self.tokens.len() {
+            return Err(self.unexpected());
+        }
+        Ok(statement)
+    }
+
+    /// 表达式末尾的 `@sci`、`@%` 或 `@2`
+    fn format(&mut self) -> Result<Option<NumberFormat>, EvalError> {
+        let token = match self.tokens.get(self.pos) {
+            Some(token) => token.clone(),
+            None => {
+                return Ok(None);
+            }
+        };
+        match token.kind {
+            TokenKind::Format(format) => {
+                self.next();
+                Ok(Some(format))
+            }
+            TokenKind::At => {
+                self.next();
+                match self.peek() {
+                    Some(TokenKind::Number(value)) if value.fract() == 0.0 && *value <= 28.0 => {
+                        let format = NumberFormat::Fixed(*value as usize);
+                        self.next();
+                        Ok(Some(format))
+                    }
+                    _ => {
+                        let end = self.tokens.get(self.pos).map_or(token.span.end, |next| next.span.end);
+                        Err(
+                            EvalError::at(
+                                "Unknown format, use `@2`, `@sci`, `@eng`, `@%`, `@hex` or `@currency:CNY`",
+                                token.span.start..end
+                            )
+                        )
+                    }
+                }
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// `A to km`, 只能用于整行表达式的末尾
+    fn conversion(&mut self, expr: Expr) -> Result<Expr, EvalError> {
+        let to = self.next().map_or(expr.span.end, |token| token.span.end);
+        match self.unit() {
+            Some((unit, span)) => {
+                let span = expr.span.start..span.end;
+                Ok(Expr { kind: ExprKind::Convert(Box::new(expr), unit), span })
+            }
+            None =>
+                match self.tokens.get(self.pos) {
+                    Some(token) =>
+                        Err(EvalError::at(format!("Unknown unit `{}`", self.text(&token.span)), token.span.clone())),
+                    None => Err(EvalError::at("Missing unit after `to`", to..to + 1)),
+                }
+        }
+    }
+
+    /// 当前位置之后第 `offset` 个记号是否为已知的单位 (不是函数调用)
+    fn unit_at(&self, offset: usize) -> Option<Unit> {
+        match self.peek_at(offset) {
+            Some(TokenKind::Ident(name)) if self.peek_at(offset + 1) != Some(&TokenKind::LParen) && !self.is_param(name) => {
+                Unit::lookup(name)
+            }
+            _ => None,
+        }
+    }
+
+    fn is_param(&self, name: &str) -> bool {
+        self.params.contains(&name.to_lowercase())
+    }
+
+    /// 单位表达式 `kg*m^2/s`, 只有已知的单位名称才作为单位, `/` 只作用于其后的一个单位
+    fn unit(&mut self) -> Option<(Unit, Span)> {
+        let first = self.unit_at(0)?;
+        let start = self.next().map_or(0, |token| token.span.start);
+        let mut unit = self.unit_power(first);
+        loop {
+            let divide = match self.peek() {
+                Some(TokenKind::Star) => false,
+                Some(TokenKind::Slash) => true,
+                _ => {
+                    break;
+                }
+            };
+            let factor = match self.unit_at(1) {
+                Some(factor) => factor,
+                None => {
+                    break;
+                }
+            };
+            self.pos += 2;
+            let factor = self.unit_power(factor);
+            unit = if divide { unit.div(&factor) } else { unit.mul(&factor) };
+        }
+        let span = start..self.tokens[self.pos - 1].span.end;
+        let name = self
+            .text(&span)
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .collect();
+        Some((Unit { name, ..unit }, span))
+    }
+
+    /// 单位后的整数次幂 `^2`、`^-1`
+    fn unit_power(&mut self, unit: Unit) -> Unit {
+        if self.peek() != Some(&TokenKind::Caret) {
+            return unit;
+        }
+        let negative = self.peek_at(1) == Some(&TokenKind::Minus);
+        let offset = if negative { 2 } else { 1 };
+        match self.peek_at(offset) {
+            Some(TokenKind::Number(value)) if value.fract() == 0.0 && value.abs() <= 127.0 => {
+                let exponent = if negative { -(*value as i32) } else { *value as i32 };
+                self.pos += offset + 1;
+                unit.powi(exponent)
+            }
+            _ => unit,
+        }
+    }
+
+    /// 数值或括号之后的单位, `9.81 m/s^2` 即 `9.81 * (m/s^2)`
+    fn with_unit(&mut self, expr: Expr) -> Expr {
+        match self.unit() {
+            Some((unit, span)) => binary(BinaryOp::Mul, expr, Expr { kind: ExprKind::Unit(unit), span }),
+            None => expr,
+        }
+    }
+
+    /// `@ 2`、`@ 0..1` 或 `@ x = 0.5`
+    fn hint(&mut self) -> Result<RootHint, EvalError> {
+        let start = self.pos;
+        let hint = if matches!(self.peek(), Some(TokenKind::Ident(_))) {
+            self.next();
+            self.eat(&TokenKind::Equals).and_then(|_| self.signed_number()).map(RootHint::Guess)
+        } else {
+            match self.signed_number() {
+                Some(a) if self.eat(&TokenKind::DotDot).is_some() => {
+                    self.signed_number().map(|b| RootHint::Interval(a, b))
+                }
+                Some(index) if index >= 0.0 && index.fract() == 0.0 => {
+                    Some(RootHint::Index(index as usize))
+                }
+                _ => None,
+            }
+        };
+        match hint {
+            Some(hint) if self.pos >= self.tokens.len() => Ok(hint),
+            _ => {
+                let from = self.tokens.get(start).map_or(self.end, |token| token.span.start);
+                Err(
+                    EvalError::at(
+                        "Invalid hint, use `@ 2`, `@ 0..1` or `@ x = 0.5`",
+                        from..self.end.max(from + 1)
+                    )
+                )
+            }
+        }
+    }
+
+    fn signed_number(&mut self) -> Option<f64> {
+        let negative = self.eat(&TokenKind::Minus).is_some();
+        match self.peek() {
+            Some(TokenKind::Number(value)) => {
+                let value = *value;
+                self.next();
+                Some(if negative { -value } else { value })
+            }
+            _ => None,
+        }
+    }
+
+    /// 按位或, 优先级最低
+    fn expression(&mut self) -> Result<Expr, EvalError> {
+        self.bitwise(0)
+    }
+
+    /// 按位运算, 各级依次为 `|`、`xor`、`&` 和移位, 同级左结合
+    fn bitwise(&mut self, level: usize) -> Result<Expr, EvalError> {
+        if level == 4 {
+            return self.additive();
+        }
+        let mut lhs = self.bitwise(level + 1)?;
+        loop {
+            let op = match (level, self.peek()) {
+                (0, Some(TokenKind::Pipe)) => BitOp::Or,
+                (1, Some(TokenKind::Ident(name))) if name.eq_ignore_ascii_case("xor") => BitOp::Xor,
+                (2, Some(TokenKind::Amp)) => BitOp::And,
+                (3, Some(TokenKind::Shl)) => BitOp::Shl,
+                (3, Some(TokenKind::Shr)) => BitOp::Shr,
+                _ => {
+                    return Ok(lhs);
+                }
+            };
+            self.next();
+            let rhs = self.bitwise(level + 1)?;
+            let span = lhs.span.start..rhs.span.end;
+            lhs = Expr { kind: ExprKind::Bitwise(op, Box::new(lhs), Box::new(rhs)), span };
+        }
+    }
+
+    /// 加减
+    fn additive(&mut self) -> Result<Expr, EvalError> {
+        let mut lhs = self.term()?;
+        loop {
+            let op = match self.peek() {
+                Some(TokenKind::Plus) => BinaryOp::Add,
+                Some(TokenKind::Minus) => BinaryOp::Sub,
+                _ => {
+                    return Ok(lhs);
+                }
+            };
+            self.next();
+            let rhs = self.term()?;
+            lhs = binary(op, lhs, rhs);
+        }
+    }
+
+    /// 乘除和取余, `%` 后面跟着操作数时为取余, 否则为百分号 (见 [`Parser::postfix`])
+    ///
+    /// 极坐标 `r∠θ` 与乘除同级, 解析为 `polar(r, θ)`。
+    fn term(&mut self) -> Result<Expr, EvalError> {
+        let mut lhs = self.unary()?;
+        loop {
+            let op = match self.peek() {
+                Some(TokenKind::Star) => BinaryOp::Mul,
+                Some(TokenKind::Slash) => BinaryOp::Div,
+                Some(TokenKind::Percent) => BinaryOp::Rem,
+                Some(TokenKind::Angle) => {
+                    self.next();
+                    let angle = self.unary()?;
+                    let span = lhs.span.start..angle.span.end;
+                    lhs = Expr { kind: ExprKind::Call("polar".to_string(), vec![lhs, angle]), span };
+                    continue;
+                }
+                _ => {
+                    return Ok(lhs);
+                }
+            };
+            self.next();
+            let rhs = self.unary()?;
+            lhs = binary(op, lhs, rhs);
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, EvalError> {
+        match self.peek() {
+            Some(TokenKind::Minus) => {
+                let start = self.next().map_or(0, |token| token.span.start);
+                let operand = self.unary()?;
+                let span = start..operand.span.end;
+                Ok(Expr { kind: ExprKind::Neg(Box::new(operand)), span })
+            }
+            Some(TokenKind::Plus) => {
+                self.next();
+                self.unary()
+            }
+            Some(TokenKind::Tilde) => {
+                let start = self.next().map_or(0, |token| token.span.start);
+                let operand = self.unary()?;
+                let span = start..operand.span.end;
+                Ok(Expr { kind: ExprKind::BitNot(Box::new(operand)), span })
+            }
+            _ => self.power(),
+        }
+    }
+
+    /// 乘方为右结合, 并且优先于负号: `-2^2` 为 -4, `2^-1` 为 0.5
+    fn power(&mut self) -> Result<Expr, EvalError> {
+        let base = self.postfix()?;
+        if self.eat(&TokenKind::Caret).is_some() {
+            let exponent = self.unary()?;
+            return Ok(binary(BinaryOp::Pow, base, exponent));
+        }
+        Ok(base)
+    }
+
+    fn postfix(&mut self) -> Result<Expr, EvalError> {
+        let mut expr = self.primary()?;
+        while
+            self.peek() == Some(&TokenKind::Percent) &&
+            !matches!(
+                self.peek_at(1),
+                Some(TokenKind::Number(_) | TokenKind::Imaginary(_) | TokenKind::Ident(_) | TokenKind::LParen)
+            )
+        {
+            let end = self.next().map_or(expr.span.end, |token| token.span.end);
+            let span = expr.span.start..end;
+            expr = Expr { kind: ExprKind::Percent(Box::new(expr)), span };
+        }
+        Ok(expr)
+    }
+
+    fn primary(&mut self) -> Result<Expr, EvalError> {
+        let token = match self.tokens.get(self.pos) {
+            Some(token) => token.clone(),
+            None => {
+                return Err(self.unexpected());
+            }
+        };
+        match token.kind {
+            TokenKind::Number(value) => {
+                self.next();
+                let text = self.text(&token.span);
+                let digits = self.locale.canonical(&text);
+                let digits = match (parse_radix(&text), split_chinese_unit(&digits)) {
+                    (Some(value), _) => value.to_string(),
+                    (None, (digits, 0)) => digits.to_string(),
+                    (None, (digits, exponent)) => format!("{}e{}", digits, exponent),
+                };
+                Ok(self.with_unit(Expr { kind: ExprKind::Number(value, digits), span: token.span }))
+            }
+            TokenKind::Imaginary(value) => {
+                self.next();
+                Ok(Expr { kind: ExprKind::Imaginary(value), span: token.span })
+            }
+            TokenKind::Date(value) => {
+                self.next();
+                Ok(Expr { kind: ExprKind::Date(value), span: token.span })
+            }
+            TokenKind::Time(value) => {
+                self.next();
+                Ok(Expr { kind: ExprKind::Duration(value), span: token.span })
+            }
+            TokenKind::Ident(name) => {
+                self.next();
+                if self.peek() == Some(&TokenKind::LParen) {
+                    return self.call(&name, token.span);
+                }
+                if self.is_param(&name) {
+                    return Ok(Expr { kind: ExprKind::Name(name.to_lowercase()), span: token.span });
+                }
+                Ok(Expr { kind: identifier(&name), span: token.span })
+            }
+            TokenKind::LBracket => self.matrix(token.span),
+            TokenKind::LParen => {
+                self.next();
+                let inner = self.expression()?;
+                match self.eat(&TokenKind::RParen) {
+                    Some(close) => Ok(self.with_unit(Expr { kind: inner.kind, span: token.span.start..close.end })),
+                    None if self.pos >= self.tokens.len() => Err(EvalError::at("Missing `)`", token.span)),
+                    None => Err(self.unexpected()),
+                }
+            }
+            _ => Err(self.unexpected()),
+        }
+    }
+
+    /// 矩阵 `[1, 2; 3, 4]`, 元素以参数分隔符分隔, 行以 `;` 分隔
+    ///
+    /// 小数点为逗号时 `;` 已是参数分隔符, 行改以 `|` 分隔, 元素中不能再使用按位或。
+    fn matrix(&mut self, open: Span) -> Result<Expr, EvalError> {
+        self.next();
+        let row_separator = if self.locale.argument_separator() == ';' { TokenKind::Pipe } else { TokenKind::Semicolon };
+        let mut rows = Vec::new();
+        let mut row = Vec::new();
+        loop {
+            let element = if row_separator == TokenKind::Pipe { self.bitwise(1)? } else { self.expression()? };
+            row.push(element);
+            match self.peek() {
+                Some(TokenKind::Comma) => {
+                    self.next();
+                }
+                Some(kind) if *kind == row_separator => {
+                    self.next();
+                    rows.push(std::mem::take(&mut row));
+                }
+                Some(TokenKind::RBracket) => {
+                    rows.push(row);
+                    break;
+                }
+                None => {
+                    return Err(EvalError::at("Missing `]`", open));
+                }
+                _ => {
+                    return Err(self.unexpected());
+                }
+            }
+        }
+        let close = self.next().map_or(open.end, |token| token.span.end);
+        let span = open.start..close;
+        if rows.iter().any(|row| row.len() != rows[0].len()) {
+            return Err(EvalError::at("Matrix rows must have the same number of elements", span));
+        }
+        Ok(Expr { kind: ExprKind::Matrix(rows), span })
+    }
+
+    fn call(&mut self, name: &str, name_span: Span) -> Result<Expr, EvalError> {
+        let open = self.next().map_or(name_span.end, |token| token.span.start);
+        let lower = name.to_lowercase();
+        let function = lower.strip_prefix("math::").unwrap_or(&lower).to_string();
+        let arity = function_arity(&function).or_else(|| self.functions.arity(&function).map(|count| (count, count)));
+        let (min_args, max_args) = match arity {
+            Some(arity) => arity,
+            None => {
+                return Err(EvalError::at(format!("Unknown function `{}`", name), name_span));
+            }
+        };
+
+        let mut args = Vec::new();
+        if self.eat(&TokenKind::RParen).is_none() {
+            loop {
+                args.push(self.argument(&function)?);
+                if self.eat(&TokenKind::Comma).is_some() {
+                    continue;
+                }
+                if self.eat(&TokenKind::RParen).is_some() {
+                    break;
+                }
+                if self.pos >= self.tokens.len() {
+                    return Err(EvalError::at("Missing `)`", open..open + 1));
+                }
+                return Err(self.unexpected());
+            }
+        }
+        let span = name_span.start..self.tokens[self.pos - 1].span.end;
+        if args.len() < min_args || args.len() > max_args {
+            let expected = if min_args == max_args {
+                min_args.to_string()
+            } else if max_args == usize::MAX {
+                format!("at least {}", min_args)
+            } else {
+                format!("{} to {}", min_args, max_args)
+            };
+            return Err(
+                EvalError::at(
+                    format!("`{}` takes {} argument(s), found {}", function, expected, args.len()),
+                    span
+                )
+            );
+        }
+        Ok(Expr { kind: ExprKind::Call(function, args), span })
+    }
+
+    /// 函数参数, 可以是行区间 `A:F`
+    fn argument(&mut self, function: &str) -> Result<Expr, EvalError> {
+        let (first, last) = match (self.peek(), self.peek_at(1), self.peek_at(2)) {
+            (Some(TokenKind::Ident(first)), Some(TokenKind::Colon), Some(TokenKind::Ident(last))) =>
+                (first.to_uppercase(), last.to_uppercase()),
+            _ => {
+                return self.expression();
+            }
+        };
+        let span = self.tokens[self.pos].span.start..self.tokens[self.pos + 2].span.end;
+        let rows = match (row_index(&first), row_index(&last)) {
+            (Some(start), Some(end)) if start <= end => start..end + 1,
+            _ => {
+                return Err(EvalError::at(format!("Invalid row range `{}`", self.text(&span)), span));
+            }
+        };
+        if !accepts_range(function) {
+            return Err(EvalError::at(format!("`{}` does not take a row range", function), span));
+        }
+        self.pos += 3;
+        Ok(Expr { kind: ExprKind::Range(rows), span })
+    }
+}
+
+fn binary(op: BinaryOp, lhs: Expr, rhs: Expr) -> Expr {
+    let span = lhs.span.start..rhs.span.end;
+    Expr { kind: ExprKind::Binary(op, Box::new(lhs), Box::new(rhs)), span }
+}
+
+/// 标识符依次作为行标签、聚合值 (`z` 即 `sum`) 或未知数, 不区分大小写
+fn identifier(name: &str) -> ExprKind {
+    let lower = name.to_lowercase();
+    if let Some(index) = row_index(&name.to_uppercase()) {
+        return ExprKind::Row(index);
+    }
+    if lower == "z" {
+        return ExprKind::Aggregate(Aggregate::Sum);
+    }
+    match Aggregate::ALL.into_iter().find(|aggregate| aggregate.name() == lower) {
+        Some(aggregate) => ExprKind::Aggregate(aggregate),
+        None => ExprKind::Name(lower),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dates::days_from_civil;
+
+    /// 以前缀形式写出表达式树, 便于比较结构
+    fn tree(expr: &Expr) -> String {
+        match &expr.kind {
+            ExprKind::Number(_, digits) => digits.clone(),
+            ExprKind::Date(days) => format!("date {}", days),
+            ExprKind::Duration(days) => format!("time {}", days),
+            ExprKind::Row(index) => format!("row {}", index),
+            ExprKind::Name(name) => format!("name {}", name),
+            ExprKind::Percent(expr) => format!("(% {})", tree(expr)),
+            ExprKind::Neg(expr) => format!("(neg {})", tree(expr)),
+            ExprKind::Binary(op, lhs, rhs) => format!("({:?} {} {})", op, tree(lhs), tree(rhs)),
+            ExprKind::Call(name, args) => {
+                let args: Vec<String> = args.iter().map(tree).collect();
+                format!("({} {})", name, args.join(" "))
+            }
+            kind => format!("{:?}", kind),
+        }
+    }
+
+    fn expression(input: &str) -> String {
+        match parse(input) {
+            Ok(Statement::Expression(expr, _)) => tree(&expr),
+            other => panic!("{} is not an expression: {:?}", input, other),
+        }
+    }
+
+    fn error(input: &str) -> (String, Option<usize>) {
+        let error = parse(input).unwrap_err();
+        let column = error.column();
+        (error.message, column)
+    }
+
+    #[test]
+    fn precedence() {
+        assert_eq!(expression("1 + 2 * 3"), "(Add 1 (Mul 2 3))");
+        assert_eq!(expression("(1 + 2) * 3"), "(Mul (Add 1 2) 3)");
+        assert_eq!(expression("10 - 4 - 3"), "(Sub (Sub 10 4) 3)");
+        // 乘方右结合, 且高于取负
+        assert_eq!(expression("2^3^2"), "(Pow 2 (Pow 3 2))");
+        assert_eq!(expression("-2^2"), "(neg (Pow 2 2))");
+    }
+
+    #[test]
+    fn percent_and_remainder() {
+        assert_eq!(expression("50%"), "(% 50)");
+        assert_eq!(expression("200 * 15%"), "(Mul 200 (% 15))");
+        assert_eq!(expression("10 % 3"), "(Rem 10 3)");
+    }
+
+    #[test]
+    fn grouping_separators_and_argument_commas() {
+        assert_eq!(expression("1,234,567.5"), "1234567.5");
+        assert_eq!(expression("max(1,200)"), "(max 1 200)");
+        assert_eq!(expression("max(1, 200)"), "(max 1 200)");
+        assert_eq!(expression("max(1,234,5)"), "(max 1 234 5)");
+
+        let de = Locale::from_name("de").unwrap();
+        match parse_localized("max(1.234,5; 2)", &de) {
+            Ok(Statement::Expression(expr, _)) => assert_eq!(tree(&expr), "(max 1234.5 2)"),
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn error_columns() {
+        assert_eq!(error("2 * (3 + 4"), ("Missing `)`".to_string(), Some(5)));
+        assert_eq!(error("1 + $"), ("Unexpected character `$`".to_string(), Some(5)));
+        assert_eq!(error("1 +"), ("Unexpected end of expression".to_string(), Some(4)));
+        assert_eq!(error("foo(1)"), ("Unknown function `foo`".to_string(), Some(1)));
+    }
+
+    #[test]
+    fn unknowns_and_rows() {
+        // U..Z 不是行, `x` 和 `X` 都是未知数
+        assert_eq!(expression("x"), "name x");
+        assert_eq!(expression("X"), "name x");
+        assert_eq!(expression("A"), "row 0");
+        assert_eq!(expression("t"), "row 19");
+        assert_eq!(expression("ab"), "row 21");
+        // 乘号不能省略
+        assert_eq!(expression("2 * x"), "(Mul 2 name x)");
+        assert_eq!(error("2x"), ("Unexpected `x`".to_string(), Some(2)));
+    }
+
+    #[test]
+    fn date_literals() {
+        assert_eq!(expression("2026-10-17"), format!("date {}", days_from_civil(2026, 10, 17)));
+        assert_eq!(expression("2026-10-17T12:00"), format!("date {}", days_from_civil(2026, 10, 17) as f64 + 0.5));
+        assert_eq!(expression("6:00"), "time 0.25");
+        assert_eq!(expression("2026-10-17 - 30"), format!("(Sub date {} 30)", days_from_civil(2026, 10, 17)));
+        // 不是四位年份时仍为减法
+        assert_eq!(expression("10-3"), "(Sub 10 3)");
+        assert_eq!(error("2026-02-30"), ("Invalid date or time `2026-02-30`".to_string(), Some(1)));
+    }
+
+    #[test]
+    fn equation_hints() {
+        match parse("x^2 = 4 @ 2") {
+            Ok(Statement::Equation { hint, .. }) => assert_eq!(hint, Some(RootHint::Index(2))),
+            other => panic!("{:?}", other),
+        }
+        match parse("x^2 = 4 @ 0..5") {
+            Ok(Statement::Equation { hint, .. }) => assert_eq!(hint, Some(RootHint::Interval(0.0, 5.0))),
+            other => panic!("{:?}", other),
+        }
+    }
+}
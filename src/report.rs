@@ -0,0 +1,307 @@
+use crate::aggregate::Aggregate;
+use crate::engine::{ range_label, row_label, Engine };
+use crate::error::EvalError;
+use crate::format::{ display_width, parse_number };
+
+/// 单行的评估结果
+#[derive(Debug, Clone, PartialEq)]
+pub struct RowReport {
+    pub label: String,
+    pub input: String,
+    pub comment: String,
+    pub result: String,
+    pub value: Option<f64>,
+    /// 错误信息, 能定位时附带出错的列号
+    pub error: Option<String>,
+}
+
+/// 求和区域的一个聚合值
+#[derive(Debug, Clone, PartialEq)]
+pub struct AggregateReport {
+    pub aggregate: Aggregate,
+    pub value: f64,
+    pub display: String,
+}
+
+impl AggregateReport {
+    pub fn new(aggregate: Aggregate, engine: &Engine) -> Self {
+        let value = engine.aggregate(aggregate);
+        Self { aggregate, value, display: engine.format_aggregate(aggregate) }
+    }
+
+    /// 合计栏中的名称, Sum 附带别名 Z
+    pub fn caption(&self) -> String {
+        match self.aggregate {
+            Aggregate::Sum => "Sum = Z".to_string(),
+            aggregate => aggregate.label().to_string(),
+        }
+    }
+}
+
+/// 整个 section 的评估结果, 包含求和区域的合计
+#[derive(Debug, Clone, PartialEq)]
+pub struct SheetReport {
+    pub section: String,
+    pub rows: Vec<RowReport>,
+    /// 求和区域, 例如 `A - Q`
+    pub region: String,
+    pub sum: f64,
+    pub count: usize,
+    pub average: f64,
+    /// section 中配置的聚合值, 按配置顺序排列
+    pub aggregates: Vec<AggregateReport>,
+    /// 求和区域无法合计的原因, 此时各聚合值为 NaN
+    pub error: Option<String>,
+    sum_display: String,
+    average_display: String,
+}
+
+impl SheetReport {
+    /// 由输入和 [`Engine::evaluate_sheet`] 的结果生成报告, 空行会被跳过
+    pub fn new(
+        section: &str,
+        inputs: &[String],
+        results: &[Result<String, EvalError>],
+        engine: &Engine
+    ) -> Self {
+        let rows = inputs
+            .iter()
+            .zip(results)
+            .enumerate()
+            .filter(|(_, (input, _))| !input.trim().is_empty())
+            .map(|(i, (input, result))| {
+                let label = row_label(i);
+                let comment = input
+                    .split_once('#')
+                    .map(|(_, comment)| comment.trim().to_string())
+                    .unwrap_or_default();
+                let (result, value, error) = match result {
+                    // 多根方程的结果列显示全部的根, 数值取导出为行变量的根
+                    Ok(res) => {
+                        let value = engine
+                            .variables()
+                            .get(&label)
+                            .unwrap_or(res);
+                        let value = parse_number(value);
+                        (res.clone(), value, None)
+                    }
+                    Err(err) => ("Error".to_string(), None, Some(err.to_string())),
+                };
+                RowReport {
+                    label,
+                    input: input.clone(),
+                    comment,
+                    result,
+                    value,
+                    error,
+                }
+            })
+            .collect();
+
+        Self {
+            section: section.to_string(),
+            rows,
+            region: range_label(&engine.sum_rows()),
+            sum: engine.sum(),
+            count: engine.count(),
+            average: engine.average(),
+            aggregates: engine
+                .aggregates()
+                .iter()
+                .map(|aggregate| AggregateReport::new(*aggregate, engine))
+                .collect(),
+            error: engine.region_error().map(str::to_string),
+            sum_display: engine.format_aggregate(Aggregate::Sum),
+            average_display: engine.format_aggregate(Aggregate::Average),
+        }
+    }
+
+    /// 与 TUI 相同的 `A: [结果] = [表达式]` 文本格式
+    pub fn to_table(&self) -> String {
+        let mut output = String::new();
+        let label_width = self.rows
+            .iter()
+            .map(|row| row.label.len())
+            .max()
+            .unwrap_or(1);
+        for row in &self.rows {
+            // 全角字符占两列
+            let width = 20 - (display_width(&row.result) - row.result.chars().count());
+            output.push_str(
+                &format!(
+                    "{:<label_width$}: [{:>width$}] = [{}]\n",
+                    row.label,
+                    row.result,
+                    row.input,
+                    label_width = label_width
+                )
+            );
+        }
+        output.push('\n');
+        if let Some(err) = &self.error {
+            output.push_str(&format!("({}) {}\n", self.region, err));
+            return output;
+        }
+        for aggregate in &self.aggregates {
+            output.push_str(
+                &format!("({}) {} = {}\n", self.region, aggregate.caption(), aggregate.display)
+            );
+        }
+        output
+    }
+
+    pub fn to_json(&self) -> String {
+        let rows = self.rows
+            .iter()
+            .map(|row| {
+                format!(
+                    "    {{\"label\": {}, \"input\": {}, \"comment\": {}, \"result\": {}, \"value\": {}, \"error\": {}}}",
+                    json_string(&row.label),
+                    json_string(&row.input),
+                    json_string(&row.comment),
+                    json_string(&row.result),
+                    row.value.map_or("null".to_string(), json_number),
+                    row.error.as_deref().map_or("null".to_string(), json_string)
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(",\n");
+
+        let aggregates = self.aggregates
+            .iter()
+            .map(|aggregate| {
+                format!("{}: {}", json_string(aggregate.aggregate.name()), json_number(aggregate.value))
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        format!(
+            "{{\n  \"section\": {},\n  \"rows\": [\n{}\n  ],\n  \"region\": {},\n  \"sum\": {},\n  \"count\": {},\n  \"average\": {},\n  \"aggregates\": {{{}}},\n  \"error\": {}\n}}\n",
+            json_string(&self.section),
+            rows,
+            json_string(&self.region),
+            json_number(self.sum),
+            self.count,
+            json_number(self.average),
+            aggregates,
+            self.error.as_deref().map_or("null".to_string(), json_string)
+        )
+    }
+
+    /// CSV 格式, 合计以 Sum / Count / Average 及其他配置的聚合值行附在末尾
+    pub fn to_csv(&self) -> String {
+        let mut output = String::from("label,input,comment,result,value,error\n");
+        for row in &self.rows {
+            output.push_str(
+                &format!(
+                    "{},{},{},{},{},{}\n",
+                    csv_field(&row.label),
+                    csv_field(&row.input),
+                    csv_field(&row.comment),
+                    csv_field(&row.result),
+                    row.value.map(|value| value.to_string()).unwrap_or_default(),
+                    csv_field(row.error.as_deref().unwrap_or(""))
+                )
+            );
+        }
+        if let Some(err) = &self.error {
+            output.push_str(&format!("Sum,,,Error,,{}\n", csv_field(err)));
+            return output;
+        }
+        output.push_str(&format!("Sum,,,{},{},\n", csv_field(&self.sum_display), self.sum));
+        output.push_str(&format!("Count,,,{},{},\n", self.count, self.count));
+        output.push_str(
+            &format!("Average,,,{},{},\n", csv_field(&self.average_display), self.average)
+        );
+        for aggregate in &self.aggregates {
+            if
+                matches!(
+                    aggregate.aggregate,
+                    Aggregate::Sum | Aggregate::Count | Aggregate::Average
+                )
+            {
+                continue;
+            }
+            output.push_str(
+                &format!(
+                    "{},,,{},{},\n",
+                    aggregate.aggregate.label(),
+                    csv_field(&aggregate.display),
+                    aggregate.value
+                )
+            );
+        }
+        output
+    }
+
+    pub fn to_markdown(&self) -> String {
+        let mut output = String::from("| Label | Result | Expression | Comment |\n");
+        output.push_str("|:-----:|-------:|:-----------|:--------|\n");
+        for row in &self.rows {
+            let expression = row.input.split('#').next().unwrap_or("").trim();
+            let result = match &row.error {
+                Some(err) => format!("Error: {}", err),
+                None => row.result.clone(),
+            };
+            output.push_str(
+                &format!(
+                    "| {} | {} | {} | {} |\n",
+                    row.label,
+                    markdown_cell(&result),
+                    markdown_cell(expression),
+                    markdown_cell(&row.comment)
+                )
+            );
+        }
+        output.push('\n');
+        if let Some(err) = &self.error {
+            output.push_str(&format!("**({}):** Error: {}\n", self.region, markdown_cell(err)));
+            return output;
+        }
+        let footer = self.aggregates
+            .iter()
+            .map(|aggregate| {
+                format!("**({}) {}:** {}", self.region, aggregate.aggregate.label(), aggregate.display)
+            })
+            .collect::<Vec<_>>()
+            .join("  \n");
+        output.push_str(&footer);
+        output.push('\n');
+        output
+    }
+}
+
+fn json_string(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len() + 2);
+    escaped.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+/// JSON 不支持 NaN 与无穷大, 以 null 表示
+fn json_number(value: f64) -> String {
+    if value.is_finite() { value.to_string() } else { "null".to_string() }
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn markdown_cell(value: &str) -> String {
+    value.replace('|', "\\|")
+}
@@ -0,0 +1,560 @@
+use num_complex::Complex64;
+
+/// 能识别的最高多项式次数
+pub const MAX_DEGREE: usize = 8;
+
+/// 验证多项式时使用的负数和非整数取值点
+const PROBES: [f64; 4] = [-0.5, -2.75, 0.3, 4.6];
+
+/// 由函数值还原 `x` 的多项式系数 (从常数项开始)
+///
+/// 在 x = 0, 1, 2 ... 处取值并计算差分, 某一阶差分全部为零时即得到次数,
+/// 再由牛顿前向差分公式展开为系数。超过 [`MAX_DEGREE`] 次、取值不是有限数,
+/// 或在负数和非整数处与函数值不符 (例如 `abs(x)`、`floor(x)`) 时视为非多项式, 返回 None。
+pub fn polynomial_coefficients<F, E>(f: F) -> Result<Option<Vec<f64>>, E>
+    where F: Fn(f64) -> Result<f64, E>
+{
+    let mut samples = Vec::with_capacity(MAX_DEGREE + 2);
+    for i in 0..MAX_DEGREE + 2 {
+        let value = f(i as f64)?;
+        if !value.is_finite() {
+            return Ok(None);
+        }
+        samples.push(value);
+    }
+
+    // differences[k] 为 x = 0 处的 k 阶前向差分
+    let scale = samples.iter().fold(1.0_f64, |max, value| max.max(value.abs()));
+    let mut differences = Vec::with_capacity(samples.len());
+    let mut current = samples;
+    while !current.is_empty() {
+        differences.push(current[0]);
+        current = current
+            .windows(2)
+            .map(|pair| pair[1] - pair[0])
+            .collect();
+    }
+
+    let is_zero = |value: f64| value.abs() <= scale * 1e-9;
+    let degree = match (0..=MAX_DEGREE).find(|&d| differences[d + 1..].iter().all(|&v| is_zero(v))) {
+        Some(degree) => degree,
+        None => {
+            return Ok(None);
+        }
+    };
+
+    // p(x) = Σ Δᵏf(0) · x(x-1)...(x-k+1) / k!
+    let mut coefficients = vec![0.0; degree + 1];
+    let mut falling = vec![1.0];
+    let mut factorial = 1.0;
+    for (k, difference) in differences.iter().enumerate().take(degree + 1) {
+        if k > 0 {
+            factorial *= k as f64;
+            falling = multiply_linear(&falling, -((k - 1) as f64));
+        }
+        for (i, c) in falling.iter().enumerate() {
+            coefficients[i] += (difference * c) / factorial;
+        }
+    }
+    // 次数已由差分确定, 其余系数即使很小也保留, 例如 `x^2 = 0.00000001` 的常数项
+    while coefficients.len() > 1 && coefficients[coefficients.len() - 1] == 0.0 {
+        coefficients.pop();
+    }
+
+    // 整数点上与多项式一致的函数不一定是多项式, 在其他点验证
+    for x in PROBES {
+        let expected = evaluate_polynomial(&coefficients, x);
+        let actual = match f(x) {
+            Ok(value) if value.is_finite() => value,
+            _ => {
+                return Ok(None);
+            }
+        };
+        if (actual - expected).abs() > (scale + expected.abs().max(actual.abs())) * 1e-9 {
+            return Ok(None);
+        }
+    }
+    Ok(Some(coefficients))
+}
+
+/// 多项式乘以 (x + a)
+fn multiply_linear(poly: &[f64], a: f64) -> Vec<f64> {
+    let mut result = vec![0.0; poly.len() + 1];
+    for (i, c) in poly.iter().enumerate() {
+        result[i] += c * a;
+        result[i + 1] += c;
+    }
+    result
+}
+
+/// 计算多项式在 x 处的值
+pub fn evaluate_polynomial(coefficients: &[f64], x: f64) -> f64 {
+    coefficients.iter().rev().fold(0.0, |acc, c| acc * x + c)
+}
+
+fn derivative(coefficients: &[f64]) -> Vec<f64> {
+    coefficients
+        .iter()
+        .enumerate()
+        .skip(1)
+        .map(|(i, c)| c * (i as f64))
+        .collect()
+}
+
+/// 求多项式的全部实根, 按从小到大排列, 重根只保留一个
+///
+/// 一次至三次方程使用求根公式, 更高次数的方程在导数的零点之间二分求解。
+pub fn real_roots(coefficients: &[f64]) -> Vec<f64> {
+    let mut roots = match coefficients.len() {
+        0 | 1 => vec![],
+        2 => vec![-coefficients[0] / coefficients[1]],
+        3 => quadratic_roots(coefficients[2], coefficients[1], coefficients[0]),
+        4 => cubic_roots(coefficients[3], coefficients[2], coefficients[1], coefficients[0]),
+        _ => bracketed_roots(coefficients),
+    };
+    for root in roots.iter_mut() {
+        *root = polish_root(coefficients, *root);
+        if root.abs() < 1e-12 {
+            *root = 0.0;
+        }
+    }
+    roots.sort_by(|a, b| a.total_cmp(b));
+    roots.dedup_by(|a, b| (*a - *b).abs() <= 1e-9 * a.abs().max(1.0));
+    roots
+}
+
+/// 求多项式的全部非实数根, 共轭成对出现, 按实部、虚部从小到大排列
+///
+/// 二次方程使用求根公式, 更高次数的方程用 Durand-Kerner 方法同时迭代所有根。
+pub fn complex_roots(coefficients: &[f64]) -> Vec<Complex64> {
+    let n = coefficients.len().saturating_sub(1);
+    if n < 2 {
+        return vec![];
+    }
+    let lead = coefficients[n];
+    let monic: Vec<Complex64> = coefficients
+        .iter()
+        .map(|c| Complex64::new(c / lead, 0.0))
+        .collect();
+    let value = |z: Complex64| monic.iter().rev().fold(Complex64::new(0.0, 0.0), |acc, c| acc * z + c);
+
+    let mut roots: Vec<Complex64> = if n == 2 {
+        let (b, c) = (monic[1].re, monic[0].re);
+        let discriminant = Complex64::new(b * b - 4.0 * c, 0.0).sqrt();
+        vec![(-b + discriminant) / 2.0, (-b - discriminant) / 2.0]
+    } else {
+        let seed = Complex64::new(0.4, 0.9);
+        let mut roots: Vec<Complex64> = (0..n).map(|k| seed.powi(k as i32)).collect();
+        for _ in 0..500 {
+            let mut change = 0.0_f64;
+            for i in 0..n {
+                let denominator = (0..n)
+                    .filter(|j| *j != i)
+                    .fold(Complex64::new(1.0, 0.0), |acc, j| acc * (roots[i] - roots[j]));
+                if denominator.norm() == 0.0 {
+                    continue;
+                }
+                let step = value(roots[i]) / denominator;
+                roots[i] -= step;
+                change = change.max(step.norm() / roots[i].norm().max(1.0));
+            }
+            if change < 1e-15 {
+                break;
+            }
+        }
+        roots
+    };
+
+    // 只保留上半平面的根, 再补上共轭根, 使结果严格成对
+    roots.retain(|root| root.im > 1e-9 * root.norm().max(1.0));
+    for root in roots.iter_mut() {
+        if root.re.abs() < 1e-12 * root.norm().max(1.0) {
+            root.re = 0.0;
+        }
+    }
+    let conjugates: Vec<Complex64> = roots
+        .iter()
+        .map(|root| root.conj())
+        .collect();
+    roots.extend(conjugates);
+    roots.sort_by(|a, b| a.re.total_cmp(&b.re).then(a.im.total_cmp(&b.im)));
+    roots
+}
+
+fn quadratic_roots(a: f64, b: f64, c: f64) -> Vec<f64> {
+    let discriminant = b * b - 4.0 * a * c;
+    let scale = (b * b).max((4.0 * a * c).abs());
+    if discriminant.abs() <= scale * 1e-12 {
+        return vec![-b / (2.0 * a)];
+    }
+    if discriminant < 0.0 {
+        return vec![];
+    }
+    // 避免 b 与判别式平方根相减造成的精度损失
+    let sign = if b < 0.0 { -1.0 } else { 1.0 };
+    let q = -0.5 * (b + sign * discriminant.sqrt());
+    if q == 0.0 {
+        return vec![0.0];
+    }
+    vec![q / a, c / q]
+}
+
+fn cubic_roots(a: f64, b: f64, c: f64, d: f64) -> Vec<f64> {
+    // 化为 t³ + pt + q = 0, x = t - b / 3a
+    let (b, c, d) = (b / a, c / a, d / a);
+    let shift = b / 3.0;
+    let p = c - (b * b) / 3.0;
+    let q = (2.0 * b * b * b) / 27.0 - (b * c) / 3.0 + d;
+    let discriminant = (q * q) / 4.0 + (p * p * p) / 27.0;
+    let scale = ((q * q) / 4.0).max((p * p * p / 27.0).abs());
+
+    if discriminant.abs() <= scale * 1e-12 {
+        if p.abs() <= 1e-12 {
+            return vec![-shift];
+        }
+        // 一个单根和一个二重根
+        return vec![(3.0 * q) / p - shift, (-3.0 * q) / (2.0 * p) - shift];
+    }
+    if discriminant > 0.0 {
+        let sqrt = discriminant.sqrt();
+        let t = (-q / 2.0 + sqrt).cbrt() + (-q / 2.0 - sqrt).cbrt();
+        return vec![t - shift];
+    }
+    // 三个不同实根, 使用三角函数形式
+    let r = 2.0 * (-p / 3.0).sqrt();
+    let phi = ((3.0 * q) / (p * r)).clamp(-1.0, 1.0).acos() / 3.0;
+    (0..3)
+        .map(|k| r * (phi - (2.0 * std::f64::consts::PI * (k as f64)) / 3.0).cos() - shift)
+        .collect()
+}
+
+/// 在导数的相邻零点之间二分查找实根
+fn bracketed_roots(coefficients: &[f64]) -> Vec<f64> {
+    let n = coefficients.len() - 1;
+    let lead = coefficients[n];
+    // Cauchy 上界, 所有实根都在 [-bound, bound] 内
+    let bound =
+        1.0 +
+        coefficients[..n].iter().fold(0.0_f64, |max, c| max.max((c / lead).abs()));
+
+    let mut points = vec![-bound];
+    points.extend(
+        real_roots(&derivative(coefficients))
+            .into_iter()
+            .filter(|x| x.abs() < bound)
+    );
+    points.push(bound);
+
+    let scale = coefficients.iter().fold(0.0_f64, |max, c| max.max(c.abs()));
+    let mut roots = Vec::new();
+    for pair in points.windows(2) {
+        let (lo, hi) = (pair[0], pair[1]);
+        let (f_lo, f_hi) = (evaluate_polynomial(coefficients, lo), evaluate_polynomial(coefficients, hi));
+        if f_lo.abs() <= scale * 1e-12 {
+            roots.push(lo);
+        } else if f_lo.signum() != f_hi.signum() {
+            roots.push(bisect(coefficients, lo, hi));
+        }
+    }
+    if evaluate_polynomial(coefficients, bound).abs() <= scale * 1e-12 {
+        roots.push(bound);
+    }
+    roots
+}
+
+fn bisect(coefficients: &[f64], mut lo: f64, mut hi: f64) -> f64 {
+    let f_lo_sign = evaluate_polynomial(coefficients, lo).signum();
+    for _ in 0..200 {
+        let mid = (lo + hi) / 2.0;
+        if mid == lo || mid == hi {
+            break;
+        }
+        if evaluate_polynomial(coefficients, mid).signum() == f_lo_sign {
+            lo = mid;
+        } else {
+            hi = mid;
+        }
+    }
+    (lo + hi) / 2.0
+}
+
+/// 用几步牛顿迭代提高求根公式的精度, 迭代变差时保留原值
+fn polish_root(coefficients: &[f64], root: f64) -> f64 {
+    let slope = derivative(coefficients);
+    let mut x = root;
+    for _ in 0..4 {
+        let d = evaluate_polynomial(&slope, x);
+        if d == 0.0 {
+            break;
+        }
+        let next = x - evaluate_polynomial(coefficients, x) / d;
+        if
+            !next.is_finite() ||
+            evaluate_polynomial(coefficients, next).abs() >
+                evaluate_polynomial(coefficients, x).abs()
+        {
+            break;
+        }
+        x = next;
+    }
+    x
+}
+
+/// 在 [a, b] 内查找全部实根, 按从小到大排列
+///
+/// 将区间等分后在每个符号变化处用 Brent 方法求解, 没有符号变化时再从区间中点尝试牛顿法。
+pub fn roots_in_interval<F>(f: &F, a: f64, b: f64) -> Vec<f64> where F: Fn(f64) -> Option<f64> {
+    const STEPS: usize = 1000;
+    let (a, b) = if a <= b { (a, b) } else { (b, a) };
+    let width = (b - a) / (STEPS as f64);
+
+    let mut roots = Vec::new();
+    let mut previous = (a, f(a));
+    for i in 1..=STEPS {
+        let x = if i == STEPS { b } else { a + width * (i as f64) };
+        let current = (x, f(x));
+        if let (Some(f_lo), Some(f_hi)) = (previous.1, current.1) {
+            if f_lo == 0.0 {
+                if touches_zero(f, previous.0) {
+                    roots.push(previous.0);
+                }
+            } else if f_lo.signum() != f_hi.signum() && f_hi != 0.0 {
+                roots.extend(brent(f, previous.0, current.0, f_lo, f_hi));
+            }
+        }
+        previous = current;
+    }
+    if previous.1 == Some(0.0) && touches_zero(f, b) {
+        roots.push(b);
+    }
+
+    if roots.is_empty() {
+        roots.extend(newton(f, (a + b) / 2.0).filter(|x| (a..=b).contains(x)));
+    }
+    roots.dedup_by(|x, y| (*x - *y).abs() <= 1e-9 * x.abs().max(1.0));
+    roots
+}
+
+/// 从 x0 出发向两侧逐步扩大搜索范围, 返回离 x0 最近的根
+///
+/// 找不到符号变化时 (例如与 x 轴相切的根) 改用牛顿法。
+pub fn root_near<F>(f: &F, x0: f64) -> Option<f64> where F: Fn(f64) -> Option<f64> {
+    let mut inner = 0.0;
+    let mut outer = 1e-3 * (1.0 + x0.abs());
+    while outer < 1e9 * (1.0 + x0.abs()) {
+        let right = bracket_root(f, x0 + inner, x0 + outer);
+        let left = bracket_root(f, x0 - outer, x0 - inner);
+        match (left, right) {
+            (Some(left), Some(right)) => {
+                return Some(if x0 - left <= right - x0 { left } else { right });
+            }
+            (Some(root), None) | (None, Some(root)) => {
+                return Some(root);
+            }
+            (None, None) => {}
+        }
+        inner = outer;
+        outer *= 1.25;
+    }
+    newton(f, x0)
+}
+
+fn bracket_root<F>(f: &F, a: f64, b: f64) -> Option<f64> where F: Fn(f64) -> Option<f64> {
+    let (f_a, f_b) = (f(a)?, f(b)?);
+    if f_a == 0.0 && touches_zero(f, a) {
+        Some(a)
+    } else if f_b == 0.0 && touches_zero(f, b) {
+        Some(b)
+    } else if f_a.signum() != f_b.signum() {
+        brent(f, a, b, f_a, f_b)
+    } else {
+        None
+    }
+}
+
+/// f(x) 为零时, 附近是否有非零值
+///
+/// 整段为零通常是下溢 (例如 `exp(x) = 0` 在 x < -745 处), 不算作根。
+fn touches_zero<F>(f: &F, x: f64) -> bool where F: Fn(f64) -> Option<f64> {
+    let h = 1e-6 * (1.0 + x.abs());
+    [x - h, x + h].iter().any(|&x| f(x).is_some_and(|value| value != 0.0))
+}
+
+/// Brent 方法, 要求 f(a) 与 f(b) 异号
+///
+/// 收敛后若函数值远大于两端的值, 说明区间内是间断点而不是根, 返回 None。
+fn brent<F>(f: &F, mut a: f64, mut b: f64, mut f_a: f64, mut f_b: f64) -> Option<f64>
+    where F: Fn(f64) -> Option<f64>
+{
+    let limit = 1e-6 * (1.0 + f_a.abs().max(f_b.abs()));
+    if f_a.abs() < f_b.abs() {
+        std::mem::swap(&mut a, &mut b);
+        std::mem::swap(&mut f_a, &mut f_b);
+    }
+    let (mut c, mut f_c) = (a, f_a);
+    let mut d = b - a;
+    let mut bisected = true;
+
+    for _ in 0..200 {
+        if f_b == 0.0 || (b - a).abs() <= 1e-14 * (1.0 + b.abs()) {
+            break;
+        }
+        let mut s = if f_a != f_c && f_b != f_c {
+            // 逆二次插值
+            (a * f_b * f_c) / ((f_a - f_b) * (f_a - f_c)) +
+                (b * f_a * f_c) / ((f_b - f_a) * (f_b - f_c)) +
+                (c * f_a * f_b) / ((f_c - f_a) * (f_c - f_b))
+        } else {
+            // 割线法
+            b - (f_b * (b - a)) / (f_b - f_a)
+        };
+
+        let bound = (3.0 * a + b) / 4.0;
+        let out_of_range = !((bound.min(b)..=bound.max(b)).contains(&s));
+        let slow = if bisected {
+            (s - b).abs() >= (b - c).abs() / 2.0
+        } else {
+            (s - b).abs() >= (c - d).abs() / 2.0
+        };
+        bisected = out_of_range || slow || !s.is_finite();
+        if bisected {
+            s = (a + b) / 2.0;
+        }
+
+        let f_s = f(s)?;
+        d = c;
+        c = b;
+        f_c = f_b;
+        if f_a.signum() != f_s.signum() {
+            b = s;
+            f_b = f_s;
+        } else {
+            a = s;
+            f_a = f_s;
+        }
+        if f_a.abs() < f_b.abs() {
+            std::mem::swap(&mut a, &mut b);
+            std::mem::swap(&mut f_a, &mut f_b);
+        }
+    }
+
+    if f_b.abs() <= limit { Some(b) } else { None }
+}
+
+/// 使用数值导数的牛顿法, 不收敛或 x 发散时返回 None
+fn newton<F>(f: &F, x0: f64) -> Option<f64> where F: Fn(f64) -> Option<f64> {
+    let limit = 1e-9 * (1.0 + f(x0)?.abs());
+    let mut x = x0;
+    for _ in 0..100 {
+        let f_x = f(x)?;
+        if f_x == 0.0 {
+            return Some(x);
+        }
+        let h = 1e-7 * (1.0 + x.abs());
+        let slope = (f(x + h)? - f(x - h)?) / (2.0 * h);
+        if slope == 0.0 || !slope.is_finite() {
+            return None;
+        }
+        let step = f_x / slope;
+        x -= step;
+        // 与 root_near 的搜索范围一致, 超出时视为发散 (例如 `exp(x) = 0`)
+        if !x.is_finite() || (x - x0).abs() > 1e9 * (1.0 + x0.abs()) {
+            return None;
+        }
+        if step.abs() <= 1e-12 * (1.0 + x.abs()) {
+            return f(x).filter(|f_x| f_x.abs() <= limit).map(|_| x);
+        }
+    }
+    None
+}
+
+/// 高斯消元法 (列主元) 求解线性方程组 Ax = b, 系数矩阵奇异时返回 None
+pub fn solve_linear_system(mut matrix: Vec<Vec<f64>>, mut constants: Vec<f64>) -> Option<Vec<f64>> {
+    let n = constants.len();
+    let scale = matrix
+        .iter()
+        .flatten()
+        .fold(0.0_f64, |max, value| max.max(value.abs()));
+    for col in 0..n {
+        let pivot = (col..n).max_by(|&a, &b| matrix[a][col].abs().total_cmp(&matrix[b][col].abs()))?;
+        if matrix[pivot][col].abs() <= scale * 1e-12 {
+            return None;
+        }
+        matrix.swap(col, pivot);
+        constants.swap(col, pivot);
+        for row in col + 1..n {
+            let factor = matrix[row][col] / matrix[col][col];
+            let (upper, lower) = matrix.split_at_mut(row);
+            for (target, source) in lower[0][col..].iter_mut().zip(&upper[col][col..]) {
+                *target -= factor * source;
+            }
+            constants[row] -= factor * constants[col];
+        }
+    }
+
+    let mut solution = vec![0.0; n];
+    for row in (0..n).rev() {
+        let known: f64 = (row + 1..n).map(|k| matrix[row][k] * solution[k]).sum();
+        solution[row] = (constants[row] - known) / matrix[row][row];
+    }
+    Some(solution)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn coefficients(f: impl Fn(f64) -> f64) -> Option<Vec<f64>> {
+        polynomial_coefficients(|x| Ok::<f64, ()>(f(x))).unwrap()
+    }
+
+    #[test]
+    fn recognises_polynomials() {
+        assert_eq!(coefficients(|x| x * x - 4.0), Some(vec![-4.0, 0.0, 1.0]));
+        assert_eq!(coefficients(|x| 3.0 * x - 1.0), Some(vec![-1.0, 3.0]));
+        assert_eq!(coefficients(|x| x.powi(3) - 2.0 * x - 1.0), Some(vec![-1.0, -2.0, 0.0, 1.0]));
+        assert_eq!(coefficients(|_| 5.0), Some(vec![5.0]));
+    }
+
+    #[test]
+    fn keeps_small_constants() {
+        // x^2 = 0.00000001, 1000*x^2 = 0.00001
+        assert_eq!(coefficients(|x| x * x - 0.00000001), Some(vec![-0.00000001, 0.0, 1.0]));
+        assert_eq!(real_roots(&[-0.00000001, 0.0, 1.0]), vec![-0.0001, 0.0001]);
+        let fitted = coefficients(|x| 1000.0 * x * x - 0.00001).unwrap();
+        let roots = real_roots(&fitted);
+        assert_eq!(roots.len(), 2);
+        assert!((roots[1] - 0.0001).abs() < 1e-12 && (roots[0] + 0.0001).abs() < 1e-12);
+    }
+
+    #[test]
+    fn rejects_functions_that_only_match_on_integers() {
+        // abs(x) = -1, abs(x) = 3
+        assert_eq!(coefficients(|x| x.abs() + 1.0), None);
+        assert_eq!(coefficients(|x| x.abs() - 3.0), None);
+        // floor(x) = 2.5, round(x) = x
+        assert_eq!(coefficients(|x| x.floor() - 2.5), None);
+        assert_eq!(coefficients(|x| x.round() - x), None);
+    }
+
+    #[test]
+    fn rejects_functions_undefined_at_probes() {
+        let f = |x: f64| if x < 0.0 { Err(()) } else { Ok(x * 2.0) };
+        assert_eq!(polynomial_coefficients(f), Ok(None));
+    }
+
+    #[test]
+    fn root_near_finds_tangent_roots() {
+        let root = root_near(&(|x: f64| Some(x.sin() - 1.0)), 1.5).unwrap();
+        assert!((root - std::f64::consts::FRAC_PI_2).abs() < 1e-6);
+        assert_eq!(root_near(&(|x: f64| Some((x - 2.0).exp() - 1.0)), 0.0), Some(2.0));
+    }
+
+    #[test]
+    fn root_near_rejects_divergence() {
+        // exp(x) = 0 下溢为零, 1/(x-1) = 0 时 x 发散
+        assert_eq!(root_near(&(|x: f64| Some(x.exp())), 0.0), None);
+        assert_eq!(root_near(&(|x: f64| Some(1.0 / (x - 1.0)).filter(|v| v.is_finite())), 0.0), None);
+        assert_eq!(root_near(&(|x: f64| Some(x.abs() + 1.0)), 0.0), None);
+    }
+}
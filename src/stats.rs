@@ -0,0 +1,191 @@
+/// 统计函数及其参数个数范围, 空行和出错的行与聚合函数一样被跳过
+///
+/// `slope`、`intercept` 和 `rsq` 的参数顺序与电子表格相同, 先 y 后 x, 两个区间按行对应。
+pub const FUNCTIONS: [(&str, usize, usize); 11] = [
+    // 样本方差和总体方差、总体标准差, 样本标准差为聚合函数 `stdev`
+    ("var", 1, usize::MAX),
+    ("varp", 1, usize::MAX),
+    ("stdevp", 1, usize::MAX),
+    // percentile(values, k), k 在 0 到 1 之间
+    ("percentile", 2, 2),
+    // quartile(values, q), q 为 0 到 4
+    ("quartile", 2, 2),
+    ("mode", 1, usize::MAX),
+    ("geomean", 1, usize::MAX),
+    ("harmean", 1, usize::MAX),
+    ("slope", 2, 2),
+    ("intercept", 2, 2),
+    ("rsq", 2, 2),
+];
+
+/// 是否为统计函数
+pub fn is_stats_function(name: &str) -> bool {
+    FUNCTIONS.iter().any(|(function, _, _)| *function == name)
+}
+
+/// 两个区间按行对应的统计函数
+pub fn is_paired(name: &str) -> bool {
+    matches!(name, "slope" | "intercept" | "rsq")
+}
+
+/// 调用统计函数, 每个参数为一个数值或行区间中有数值的各行
+pub fn call_stats(name: &str, args: &[Vec<f64>]) -> Result<f64, String> {
+    match name {
+        "percentile" | "quartile" => {
+            let k = match args[1].as_slice() {
+                [k] => *k,
+                _ => {
+                    return Err(format!("`{}` argument 2 must be a single value", name));
+                }
+            };
+            let k = if name == "quartile" {
+                if k.fract() != 0.0 || !(0.0..=4.0).contains(&k) {
+                    return Err("`quartile` needs 0, 1, 2, 3 or 4".to_string());
+                }
+                k / 4.0
+            } else {
+                k
+            };
+            if !(0.0..=1.0).contains(&k) {
+                return Err("`percentile` needs a value between 0 and 1".to_string());
+            }
+            non_empty(name, &args[0])?;
+            Ok(percentile(&args[0], k))
+        }
+        "slope" | "intercept" | "rsq" => regression(name, &args[0], &args[1]),
+        _ => {
+            let values = args.concat();
+            non_empty(name, &values)?;
+            match name {
+                "var" | "varp" | "stdevp" => {
+                    let sample = name == "var";
+                    if sample && values.len() < 2 {
+                        return Err("`var` needs at least 2 values".to_string());
+                    }
+                    let variance = variance(&values, sample);
+                    Ok(if name == "stdevp" { variance.sqrt() } else { variance })
+                }
+                "mode" => mode(&values).ok_or_else(|| "`mode` needs a value that occurs more than once".to_string()),
+                "geomean" | "harmean" => {
+                    if values.iter().any(|value| *value <= 0.0) {
+                        return Err(format!("`{}` needs positive values", name));
+                    }
+                    let count = values.len() as f64;
+                    Ok(if name == "geomean" {
+                        (values.iter().map(|value| value.ln()).sum::<f64>() / count).exp()
+                    } else {
+                        count / values.iter().map(|value| 1.0 / value).sum::<f64>()
+                    })
+                }
+                _ => Err(format!("Unknown function `{}`", name)),
+            }
+        }
+    }
+}
+
+fn non_empty(name: &str, values: &[f64]) -> Result<(), String> {
+    if values.is_empty() { Err(format!("`{}` needs at least one value", name)) } else { Ok(()) }
+}
+
+fn mean(values: &[f64]) -> f64 {
+    values.iter().sum::<f64>() / (values.len() as f64)
+}
+
+/// 方差, `sample` 为真时除以 n - 1
+pub fn variance(values: &[f64], sample: bool) -> f64 {
+    let mean = mean(values);
+    let squares: f64 = values
+        .iter()
+        .map(|value| (value - mean).powi(2))
+        .sum();
+    squares / ((values.len() - (sample as usize)) as f64)
+}
+
+/// 百分位数, 在排序后的相邻两个数之间线性插值 (与 PERCENTILE.INC 相同)
+pub fn percentile(values: &[f64], k: f64) -> f64 {
+    let mut sorted = values.to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let rank = k * ((sorted.len() - 1) as f64);
+    let lower = rank.floor() as usize;
+    let upper = rank.ceil() as usize;
+    sorted[lower] + (sorted[upper] - sorted[lower]) * (rank - (lower as f64))
+}
+
+/// 出现次数最多的数值, 次数相同时取先出现的, 没有重复的数值时返回 None
+pub fn mode(values: &[f64]) -> Option<f64> {
+    let mut best: Option<(f64, usize)> = None;
+    for value in values {
+        let count = values
+            .iter()
+            .filter(|other| *other == value)
+            .count();
+        if count > 1 && best.is_none_or(|(_, best_count)| count > best_count) {
+            best = Some((*value, count));
+        }
+    }
+    best.map(|(value, _)| value)
+}
+
+/// 最小二乘直线 y = slope * x + intercept 及决定系数
+fn regression(name: &str, ys: &[f64], xs: &[f64]) -> Result<f64, String> {
+    if ys.len() < 2 {
+        return Err(format!("`{}` needs at least 2 pairs of values", name));
+    }
+    let (mean_x, mean_y) = (mean(xs), mean(ys));
+    let (mut sxx, mut syy, mut sxy) = (0.0, 0.0, 0.0);
+    for (x, y) in xs.iter().zip(ys) {
+        sxx += (x - mean_x).powi(2);
+        syy += (y - mean_y).powi(2);
+        sxy += (x - mean_x) * (y - mean_y);
+    }
+    if sxx == 0.0 {
+        return Err(format!("`{}` needs x values that are not all equal", name));
+    }
+    let slope = sxy / sxx;
+    match name {
+        "slope" => Ok(slope),
+        "intercept" => Ok(mean_y - slope * mean_x),
+        _ => {
+            if syy == 0.0 {
+                return Err("`rsq` needs y values that are not all equal".to_string());
+            }
+            Ok((sxy * sxy) / (sxx * syy))
+        }
+    }
+}
+
+/// 求和区域的描述统计, 显示在 TUI 的统计面板中
+#[derive(Debug, Clone, PartialEq)]
+pub struct Summary {
+    pub count: usize,
+    pub median: f64,
+    pub mode: Option<f64>,
+    /// 样本标准差和样本方差, 只有一个数值时为 0
+    pub stdev: f64,
+    pub variance: f64,
+    pub q1: f64,
+    pub q3: f64,
+    pub min: f64,
+    pub max: f64,
+}
+
+impl Summary {
+    /// 没有数值时返回 None
+    pub fn new(values: &[f64]) -> Option<Summary> {
+        if values.is_empty() {
+            return None;
+        }
+        let variance = if values.len() < 2 { 0.0 } else { variance(values, true) };
+        Some(Summary {
+            count: values.len(),
+            median: percentile(values, 0.5),
+            mode: mode(values),
+            stdev: variance.sqrt(),
+            variance,
+            q1: percentile(values, 0.25),
+            q3: percentile(values, 0.75),
+            min: values.iter().copied().fold(f64::INFINITY, f64::min),
+            max: values.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+        })
+    }
+}
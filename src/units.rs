@@ -0,0 +1,456 @@
+use std::fmt;
+
+use crate::aggregate::Aggregate;
+use crate::evaluator::{ call_function, call_list_function, Number };
+use crate::format::{ format_with_thousands_separator, parse_number };
+use crate::parser::BinaryOp;
+
+/// SI 基本量纲的指数: 长度、质量、时间、电流、温度、物质的量、发光强度
+pub type Dimension = [i8; 7];
+
+const DIMENSIONLESS: Dimension = [0; 7];
+
+/// 显示时基本单位的顺序, 与 [`Dimension`] 的下标对应
+const BASE_UNITS: [(&str, usize); 7] = [
+    ("kg", 1),
+    ("m", 0),
+    ("s", 2),
+    ("A", 3),
+    ("K", 4),
+    ("mol", 5),
+    ("cd", 6),
+];
+
+/// 结果的量纲与之相同时按导出单位显示, 例如 kg·m/s² 显示为 N
+const DERIVED_UNITS: [(&str, Dimension); 10] = [
+    ("Hz", [0, 0, -1, 0, 0, 0, 0]),
+    ("N", [1, 1, -2, 0, 0, 0, 0]),
+    ("J", [2, 1, -2, 0, 0, 0, 0]),
+    ("W", [2, 1, -3, 0, 0, 0, 0]),
+    ("Pa", [-1, 1, -2, 0, 0, 0, 0]),
+    ("C", [0, 0, 1, 1, 0, 0, 0]),
+    ("V", [2, 1, -3, -1, 0, 0, 0]),
+    ("Ω", [2, 1, -3, -2, 0, 0, 0]),
+    ("F", [-2, -1, 4, 2, 0, 0, 0]),
+    ("H", [2, 1, -2, -2, 0, 0, 0]),
+];
+
+/// 可以加 SI 前缀的单位及其换算到 SI 的系数, `g` 的系数使 `kg` 为 1
+const PREFIXABLE_UNITS: [(&str, f64, Dimension); 22] = [
+    ("m", 1.0, [1, 0, 0, 0, 0, 0, 0]),
+    ("g", 1e-3, [0, 1, 0, 0, 0, 0, 0]),
+    ("s", 1.0, [0, 0, 1, 0, 0, 0, 0]),
+    ("A", 1.0, [0, 0, 0, 1, 0, 0, 0]),
+    ("K", 1.0, [0, 0, 0, 0, 1, 0, 0]),
+    ("mol", 1.0, [0, 0, 0, 0, 0, 1, 0]),
+    ("cd", 1.0, [0, 0, 0, 0, 0, 0, 1]),
+    ("N", 1.0, [1, 1, -2, 0, 0, 0, 0]),
+    ("J", 1.0, [2, 1, -2, 0, 0, 0, 0]),
+    ("W", 1.0, [2, 1, -3, 0, 0, 0, 0]),
+    ("Pa", 1.0, [-1, 1, -2, 0, 0, 0, 0]),
+    ("Hz", 1.0, [0, 0, -1, 0, 0, 0, 0]),
+    ("C", 1.0, [0, 0, 1, 1, 0, 0, 0]),
+    ("V", 1.0, [2, 1, -3, -1, 0, 0, 0]),
+    ("Ω", 1.0, [2, 1, -3, -2, 0, 0, 0]),
+    ("ohm", 1.0, [2, 1, -3, -2, 0, 0, 0]),
+    ("F", 1.0, [-2, -1, 4, 2, 0, 0, 0]),
+    ("H", 1.0, [2, 1, -2, -2, 0, 0, 0]),
+    ("L", 1e-3, [3, 0, 0, 0, 0, 0, 0]),
+    ("Wh", 3600.0, [2, 1, -2, 0, 0, 0, 0]),
+    ("bar", 1e5, [-1, 1, -2, 0, 0, 0, 0]),
+    ("eV", 1.602176634e-19, [2, 1, -2, 0, 0, 0, 0]),
+];
+
+/// 不加前缀的单位, 先于前缀查找, 因此 `min` 是分钟而 `mi` 是英里
+const OTHER_UNITS: [(&str, f64, Dimension); 21] = [
+    ("min", 60.0, [0, 0, 1, 0, 0, 0, 0]),
+    ("h", 3600.0, [0, 0, 1, 0, 0, 0, 0]),
+    ("d", 86400.0, [0, 0, 1, 0, 0, 0, 0]),
+    ("l", 1e-3, [3, 0, 0, 0, 0, 0, 0]),
+    ("t", 1000.0, [0, 1, 0, 0, 0, 0, 0]),
+    ("in", 0.0254, [1, 0, 0, 0, 0, 0, 0]),
+    ("ft", 0.3048, [1, 0, 0, 0, 0, 0, 0]),
+    ("yd", 0.9144, [1, 0, 0, 0, 0, 0, 0]),
+    ("mi", 1609.344, [1, 0, 0, 0, 0, 0, 0]),
+    ("nmi", 1852.0, [1, 0, 0, 0, 0, 0, 0]),
+    ("lb", 0.45359237, [0, 1, 0, 0, 0, 0, 0]),
+    ("oz", 0.028349523125, [0, 1, 0, 0, 0, 0, 0]),
+    ("lbf", 4.4482216152605, [1, 1, -2, 0, 0, 0, 0]),
+    ("mph", 0.44704, [1, 0, -1, 0, 0, 0, 0]),
+    ("kn", 1852.0 / 3600.0, [1, 0, -1, 0, 0, 0, 0]),
+    ("ha", 1e4, [2, 0, 0, 0, 0, 0, 0]),
+    ("gal", 3.785411784e-3, [3, 0, 0, 0, 0, 0, 0]),
+    ("atm", 101325.0, [-1, 1, -2, 0, 0, 0, 0]),
+    ("psi", 6894.757293168, [-1, 1, -2, 0, 0, 0, 0]),
+    ("cal", 4.184, [2, 1, -2, 0, 0, 0, 0]),
+    ("kcal", 4184.0, [2, 1, -2, 0, 0, 0, 0]),
+];
+
+const PREFIXES: [(&str, f64); 13] = [
+    ("P", 1e15),
+    ("T", 1e12),
+    ("G", 1e9),
+    ("M", 1e6),
+    ("k", 1e3),
+    ("h", 1e2),
+    ("c", 1e-2),
+    ("m", 1e-3),
+    ("u", 1e-6),
+    ("μ", 1e-6),
+    ("µ", 1e-6),
+    ("n", 1e-9),
+    ("p", 1e-12),
+];
+
+/// 单位, `name` 为输入中的写法, 例如 `m/s^2`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Unit {
+    pub name: String,
+    /// 换算到 SI 单位的系数
+    pub factor: f64,
+    pub dims: Dimension,
+}
+
+impl Unit {
+    /// 按名称查找单位 (区分大小写), 支持 `km`、`mA` 等 SI 前缀
+    pub fn lookup(name: &str) -> Option<Unit> {
+        let unit = |factor: f64, dims: Dimension| Unit { name: name.to_string(), factor, dims };
+        let find = |table: &[(&str, f64, Dimension)], name: &str| {
+            table
+                .iter()
+                .find(|(unit, _, _)| *unit == name)
+                .map(|(_, factor, dims)| (*factor, *dims))
+        };
+        if let Some((factor, dims)) = find(&OTHER_UNITS, name).or_else(|| find(&PREFIXABLE_UNITS, name)) {
+            return Some(unit(factor, dims));
+        }
+        PREFIXES.iter().find_map(|(prefix, scale)| {
+            let (factor, dims) = find(&PREFIXABLE_UNITS, name.strip_prefix(prefix)?)?;
+            Some(unit(scale * factor, dims))
+        })
+    }
+
+    pub fn mul(&self, other: &Unit) -> Unit {
+        Unit {
+            name: format!("{}*{}", self.name, other.name),
+            factor: self.factor * other.factor,
+            dims: combine(self.dims, other.dims, 1),
+        }
+    }
+
+    pub fn div(&self, other: &Unit) -> Unit {
+        Unit {
+            name: format!("{}/{}", self.name, other.name),
+            factor: self.factor / other.factor,
+            dims: combine(self.dims, other.dims, -1),
+        }
+    }
+
+    pub fn powi(&self, exponent: i32) -> Unit {
+        Unit {
+            name: format!("{}^{}", self.name, exponent),
+            factor: self.factor.powi(exponent),
+            dims: self.dims.map(|d| d * (exponent as i8)),
+        }
+    }
+}
+
+impl fmt::Display for Unit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+fn combine(lhs: Dimension, rhs: Dimension, sign: i8) -> Dimension {
+    let mut dims = lhs;
+    for (d, r) in dims.iter_mut().zip(rhs) {
+        *d += sign * r;
+    }
+    dims
+}
+
+/// 解析 `m/s^2`、`kg*m^2/s` 形式的单位, `/` 只作用于其后的一个单位
+pub fn parse_unit(text: &str) -> Option<Unit> {
+    let text: String = text
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect();
+    let mut unit: Option<Unit> = None;
+    let mut rest = text.as_str();
+    let mut divide = false;
+    while !rest.is_empty() {
+        let end = rest.find(['*', '/']).unwrap_or(rest.len());
+        let (factor, exponent) = match rest[..end].split_once('^') {
+            Some((name, exponent)) => (Unit::lookup(name)?, exponent.parse::<i32>().ok()?),
+            None => (Unit::lookup(&rest[..end])?, 1),
+        };
+        let factor = if exponent == 1 { factor } else { factor.powi(exponent) };
+        unit = Some(match unit {
+            None => factor,
+            Some(unit) if divide => unit.div(&factor),
+            Some(unit) => unit.mul(&factor),
+        });
+        divide = rest[end..].starts_with('/');
+        rest = rest.get(end + 1..).unwrap_or("");
+    }
+    unit.map(|unit| Unit { name: text, ..unit })
+}
+
+/// 带单位的数值, `value` 以 SI 单位表示, `unit` 为显示时优先使用的单位
+#[derive(Debug, Clone, PartialEq)]
+pub struct Quantity {
+    pub value: f64,
+    pub dims: Dimension,
+    pub unit: Option<Unit>,
+}
+
+impl Quantity {
+    pub fn number(value: f64) -> Quantity {
+        Quantity { value, dims: DIMENSIONLESS, unit: None }
+    }
+
+    pub fn is_dimensionless(&self) -> bool {
+        self.dims == DIMENSIONLESS
+    }
+
+    /// 显示用的单位名称
+    pub fn unit_name(&self) -> String {
+        match &self.unit {
+            Some(unit) => unit.name.clone(),
+            None => dims_name(self.dims),
+        }
+    }
+
+    /// 以显示单位表示的数值
+    pub fn display_value(&self) -> f64 {
+        self.value / self.unit.as_ref().map_or(1.0, |unit| unit.factor)
+    }
+
+    /// 换算到指定单位, 量纲不同时报错
+    pub fn convert(self, unit: &Unit) -> Result<Quantity, String> {
+        if self.dims != unit.dims {
+            return Err(format!("Cannot convert {} to {}", self.unit_name(), unit));
+        }
+        Ok(Quantity { unit: Some(unit.clone()), ..self })
+    }
+
+    /// 加减、取余以及比较时量纲必须相同
+    fn same_dims(&self, other: &Quantity) -> Result<(), String> {
+        if self.dims == other.dims {
+            Ok(())
+        } else {
+            Err(format!("Incompatible units: {} and {}", self.describe(), other.describe()))
+        }
+    }
+
+    fn describe(&self) -> String {
+        if self.is_dimensionless() { "a plain number".to_string() } else { self.unit_name() }
+    }
+
+    /// 乘除后的单位: 与纯数值相乘时保留原来的单位, 否则按量纲显示
+    fn product(self, other: Quantity, sign: i8) -> Quantity {
+        let value = if sign > 0 { self.value * other.value } else { self.value / other.value };
+        let unit = match (self.is_dimensionless(), other.is_dimensionless()) {
+            (false, true) => self.unit,
+            (true, false) if sign > 0 => other.unit,
+            _ => None,
+        };
+        Quantity { value, dims: combine(self.dims, other.dims, sign), unit }
+    }
+
+    /// 在显示单位下取整, `floor(2.5 km)` 为 2 km
+    fn map_display(self, f: fn(f64) -> f64) -> Quantity {
+        let factor = self.unit.as_ref().map_or(1.0, |unit| unit.factor);
+        Quantity { value: f(self.value / factor) * factor, ..self }
+    }
+}
+
+/// 量纲对应的单位名称, 优先使用导出单位, 例如 `N`、`m/s^2`、`s^-1`
+pub fn dims_name(dims: Dimension) -> String {
+    if let Some((name, _)) = DERIVED_UNITS.iter().find(|(_, derived)| *derived == dims) {
+        return name.to_string();
+    }
+    let power = |name: &str, exponent: i8| {
+        if exponent == 1 { name.to_string() } else { format!("{}^{}", name, exponent) }
+    };
+    let numerator: Vec<String> = BASE_UNITS.iter()
+        .filter(|(_, i)| dims[*i] > 0)
+        .map(|(name, i)| power(name, dims[*i]))
+        .collect();
+    let denominator = BASE_UNITS.iter().filter(|(_, i)| dims[*i] < 0);
+    if numerator.is_empty() {
+        return denominator
+            .map(|(name, i)| power(name, dims[*i]))
+            .collect::<Vec<_>>()
+            .join("*");
+    }
+    let mut name = numerator.join("*");
+    for (unit, i) in denominator {
+        name.push('/');
+        name.push_str(&power(unit, -dims[*i]));
+    }
+    name
+}
+
+/// 解析行变量中保存的 `117.72 N` 或纯数值
+pub fn parse_quantity(text: &str) -> Option<Quantity> {
+    let text = text.trim();
+    match text.split_once(' ') {
+        Some((value, unit)) => {
+            let unit = parse_unit(unit)?;
+            Some(Quantity { value: parse_number(value)? * unit.factor, dims: unit.dims, unit: Some(unit) })
+        }
+        None => parse_number(text).map(Quantity::number),
+    }
+}
+
+/// 以显示单位格式化, 例如 `117.72 N`
+pub fn format_quantity(quantity: &Quantity, decimal_places: usize) -> String {
+    let value = format_with_thousands_separator(quantity.display_value(), decimal_places);
+    if quantity.is_dimensionless() {
+        value
+    } else {
+        format!("{} {}", value, quantity.unit_name())
+    }
+}
+
+/// 带单位的运算, 以 SI 单位计算并检查量纲
+impl Number for Quantity {
+    fn literal(value: f64, _digits: &str) -> Result<Self, String> {
+        Ok(Quantity::number(value))
+    }
+
+    fn unit(unit: &Unit) -> Result<Self, String> {
+        Ok(Quantity { value: unit.factor, dims: unit.dims, unit: Some(unit.clone()) })
+    }
+
+    fn convert(self, unit: &Unit) -> Result<Self, String> {
+        Quantity::convert(self, unit)
+    }
+
+    fn parse(text: &str) -> Option<Self> {
+        parse_quantity(text)
+    }
+
+    fn from_f64(value: f64) -> Option<Self> {
+        Some(Quantity::number(value))
+    }
+
+    fn to_f64(self) -> f64 {
+        self.value
+    }
+
+    fn binary(op: BinaryOp, lhs: Self, rhs: Self) -> Result<Self, String> {
+        Ok(match op {
+            BinaryOp::Add | BinaryOp::Sub | BinaryOp::Rem => {
+                lhs.same_dims(&rhs)?;
+                let value = match op {
+                    BinaryOp::Add => lhs.value + rhs.value,
+                    BinaryOp::Sub => lhs.value - rhs.value,
+                    _ => lhs.value % rhs.value,
+                };
+                let unit = lhs.unit.or(rhs.unit);
+                Quantity { value, unit, ..lhs }
+            }
+            BinaryOp::Mul => lhs.product(rhs, 1),
+            BinaryOp::Div => lhs.product(rhs, -1),
+            BinaryOp::Pow => {
+                if !rhs.is_dimensionless() {
+                    return Err("Exponents must be plain numbers".to_string());
+                }
+                let exponent = rhs.value;
+                let dims = lhs.dims.map(|d| (d as f64) * exponent);
+                if dims.iter().any(|d| d.fract() != 0.0 || d.abs() > 127.0) {
+                    return Err(format!("Cannot raise {} to the power {}", lhs.unit_name(), exponent));
+                }
+                let unit = lhs.unit
+                    .filter(|_| exponent.fract() == 0.0)
+                    .map(|unit| unit.powi(exponent as i32));
+                Quantity { value: lhs.value.powf(exponent), dims: dims.map(|d| d as i8), unit }
+            }
+        })
+    }
+
+    fn neg(self) -> Self {
+        Quantity { value: -self.value, ..self }
+    }
+
+    fn percent(self) -> Self {
+        Quantity { value: self.value / 100.0, ..self }
+    }
+
+    fn call(name: &str, args: &[Self]) -> Result<Self, String> {
+        let x = args[0].clone();
+        match name {
+            "abs" => Ok(x.map_display(f64::abs)),
+            "floor" => Ok(x.map_display(f64::floor)),
+            "ceil" => Ok(x.map_display(f64::ceil)),
+            "round" => Ok(x.map_display(f64::round)),
+            "sqrt" => Number::binary(BinaryOp::Pow, x, Quantity::number(0.5)),
+            "cbrt" => Number::binary(BinaryOp::Pow, x, Quantity::number(1.0 / 3.0)),
+            "pow" => Number::binary(BinaryOp::Pow, x, args[1].clone()),
+            "min" | "max" | "hypot" => {
+                for arg in args {
+                    x.same_dims(arg)?;
+                }
+                let values: Vec<f64> = args
+                    .iter()
+                    .map(|arg| arg.value)
+                    .collect();
+                Ok(Quantity { value: call_function(name, &values), ..x })
+            }
+            _ => {
+                if let Some(arg) = args.iter().find(|arg| !arg.is_dimensionless()) {
+                    return Err(format!("`{}` needs a plain number, found {}", name, arg.unit_name()));
+                }
+                let values: Vec<f64> = args
+                    .iter()
+                    .map(|arg| arg.value)
+                    .collect();
+                Ok(Quantity::number(call_function(name, &values)))
+            }
+        }
+    }
+
+    fn call_lists(name: &str, args: &[Vec<Self>]) -> Result<Self, String> {
+        if let Some(arg) = args.iter().flatten().find(|arg| !arg.is_dimensionless()) {
+            return Err(format!("`{}` needs a plain number, found {}", name, arg.unit_name()));
+        }
+        let args: Vec<Vec<f64>> = args
+            .iter()
+            .map(|values| values.iter().map(|value| value.value).collect())
+            .collect();
+        Ok(Quantity::number(call_list_function(name, &args)?))
+    }
+
+    fn aggregate(aggregate: Aggregate, values: &[Self]) -> Option<Self> {
+        aggregate.apply_quantity(values)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quantity(text: &str) -> Quantity {
+        parse_quantity(text).unwrap()
+    }
+
+    #[test]
+    fn checks_dimensions() {
+        let error = Quantity::binary(BinaryOp::Add, quantity("3 m"), quantity("2 s")).unwrap_err();
+        assert_eq!(error, "Incompatible units: m and s");
+
+        let sum = Quantity::binary(BinaryOp::Add, quantity("5 km"), quantity("300 m")).unwrap();
+        assert_eq!(format_quantity(&sum, 1), "5.3 km");
+    }
+
+    #[test]
+    fn converts_derived_units() {
+        let force = Quantity::binary(BinaryOp::Mul, quantity("12 kg"), quantity("9.81 m/s^2")).unwrap();
+        let force = force.convert(&parse_unit("N").unwrap()).unwrap();
+        assert_eq!(format_quantity(&force, 2), "117.72 N");
+
+        assert!(quantity("3 m").convert(&parse_unit("kg").unwrap()).is_err());
+    }
+}